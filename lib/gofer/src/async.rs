@@ -0,0 +1,159 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Asynchronous counterparts of [`open`](crate::open), built on Tokio.
+//!
+//! These support the `data`, `file`, `git`, `git+file`, `http`, `https`,
+//! `ipfs`, `ipns`, and `stdin` schemes. Custom handlers in the
+//! [`default_registry`](crate::default_registry) are blocking and are not
+//! consulted here.
+//!
+//! ```edition2021,no_run
+//! # async fn example() -> gofer::Result<()> {
+//! use tokio::io::AsyncReadExt;
+//!
+//! let mut reader = gofer::r#async::open("https://www.google.com/robots.txt").await?;
+//! let mut text = String::new();
+//! reader.read_to_string(&mut text).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Client, Error, OpenOptions, Resource, Result, Url};
+use bytes::Bytes;
+use core::pin::Pin;
+use futures_core::Stream;
+use tokio::io::AsyncRead;
+
+/// An asynchronous reader over an opened resource.
+pub type AsyncReader = Pin<Box<dyn AsyncRead + Send>>;
+
+/// An asynchronous stream of the chunks of an opened resource.
+pub type ByteStream = Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send>>;
+
+/// Opens the given URL for reading, asynchronously, using the
+/// [`global`](Client::global) client.
+pub async fn open(url: impl AsRef<str>) -> Result<AsyncReader> {
+    open_with(url, Client::global().options()).await
+}
+
+/// Opens the given URL for reading with the given options, asynchronously.
+pub async fn open_with(url: impl AsRef<str>, options: &OpenOptions) -> Result<AsyncReader> {
+    let stream = open_stream_with(url, options).await?;
+
+    // See: https://docs.rs/tokio-util/latest/tokio_util/io/struct.StreamReader.html
+    Ok(Box::pin(tokio_util::io::StreamReader::new(stream)))
+}
+
+/// Opens the given URL as a stream of chunks, asynchronously, using the
+/// [`global`](Client::global) client.
+pub async fn open_stream(url: impl AsRef<str>) -> Result<ByteStream> {
+    open_stream_with(url, Client::global().options()).await
+}
+
+/// Opens the given URL as a stream of chunks with the given options,
+/// asynchronously.
+///
+/// As with [`OpenOptions::open`], the contents are checked against any
+/// expected digest, decompressed, and limited to the maximum size.
+pub async fn open_stream_with(url: impl AsRef<str>, options: &OpenOptions) -> Result<ByteStream> {
+    let url = url.as_ref().parse::<Url>()?;
+
+    #[cfg(feature = "integrity")]
+    let expected_digest = match options.get_expected_digest() {
+        Some(digest) => Some(digest.clone()),
+        None => url
+            .fragment_str()
+            .and_then(crate::Digest::from_fragment)
+            .transpose()?,
+    };
+
+    #[cfg_attr(not(feature = "decompress"), allow(unused_variables))]
+    let (stream, metadata) = open_url(&url, options).await?;
+
+    #[cfg(feature = "integrity")]
+    let stream = match expected_digest {
+        Some(digest) => crate::readers::check_stream_digest(stream, digest),
+        None => stream,
+    };
+
+    // Decompression follows the integrity check, since digests are taken of
+    // the raw bytes, and precedes the size limit, so as to bound the output:
+    #[cfg(feature = "decompress")]
+    let stream = match options.get_decompress() {
+        true => crate::compression::decompress_stream(stream, &metadata, &url).await?,
+        false => stream,
+    };
+
+    Ok(match options.get_max_size() {
+        Some(limit) => crate::readers::limit_stream(stream, limit),
+        None => stream,
+    })
+}
+
+/// Opens a URL with the protocol handler for its scheme, returning the
+/// stream together with any metadata reported for it, as a resource
+/// without contents.
+#[cfg_attr(
+    not(any(
+        feature = "data",
+        feature = "file",
+        feature = "git",
+        feature = "http",
+        feature = "ipfs",
+        feature = "stdin"
+    )),
+    allow(unused_variables)
+)]
+async fn open_url(url: &Url<'_>, options: &OpenOptions) -> Result<(ByteStream, Resource)> {
+    #[cfg(any(
+        feature = "data",
+        feature = "file",
+        feature = "git",
+        feature = "ipfs",
+        feature = "stdin"
+    ))]
+    let undescribed = |stream| (stream, Resource::new(std::io::empty()));
+
+    match url.scheme_str().to_ascii_lowercase().as_str() {
+        #[cfg(feature = "data")]
+        "data" => crate::data::open_async(url, options).await.map(undescribed),
+        #[cfg(feature = "file")]
+        "file" => crate::file::open_async(url, options).await.map(undescribed),
+        #[cfg(feature = "git")]
+        "git" | "git+file" => crate::git::open_async(url, options).await.map(undescribed),
+        #[cfg(feature = "http")]
+        "http" => crate::http::open_async_with_metadata(url, false, options).await,
+        #[cfg(feature = "https")]
+        "https" => crate::http::open_async_with_metadata(url, true, options).await,
+        #[cfg(feature = "ipfs")]
+        "ipfs" | "ipns" => crate::ipfs::open_async(url, options).await.map(undescribed),
+        #[cfg(feature = "stdin")]
+        "stdin" => crate::stdin::open_async(url, options)
+            .await
+            .map(undescribed),
+        scheme => Err(Error::UnknownScheme(scheme.to_string())),
+    }
+}
+
+#[cfg(all(test, feature = "data"))]
+mod test {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn open_data() {
+        let mut reader = open("data:,hello").await.unwrap();
+        let mut text = String::new();
+        reader.read_to_string(&mut text).await.unwrap();
+        assert_eq!(text, "hello");
+    }
+
+    #[tokio::test]
+    async fn max_size() {
+        let mut reader = open_with("data:,hello", OpenOptions::new().max_size(4))
+            .await
+            .unwrap();
+        let mut text = String::new();
+        assert!(reader.read_to_string(&mut text).await.is_err());
+    }
+}
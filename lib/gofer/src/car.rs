@@ -0,0 +1,699 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Trustless retrieval of IPFS content, verifying the blocks of a CAR
+//! (content-addressed archive) stream against the requested CID.
+//!
+//! See: https://ipld.io/specs/transport/car/carv1/
+//! See: https://specs.ipfs.tech/http-gateways/trustless-gateway/
+
+use crate::{Error, Read, Result};
+use sha2::{Digest as _, Sha256, Sha512};
+use std::collections::{HashMap, VecDeque};
+
+const DAG_PB: u64 = 0x70;
+const RAW: u64 = 0x55;
+
+pub(crate) const IDENTITY: u64 = 0x00;
+pub(crate) const SHA2_256: u64 = 0x12;
+const SHA2_512: u64 = 0x13;
+
+/// The maximum size of a CAR section, well above the 2 MiB that IPFS
+/// implementations accept for a block.
+const MAX_SECTION_LEN: usize = 8 << 20;
+
+/// The most bytes of blocks held until they are needed, which a gateway
+/// that sends blocks in depth-first order, as requested, never approaches.
+const MAX_BUFFERED_LEN: usize = 32 << 20;
+
+/// A content identifier, which names a block by the hash of its contents.
+///
+/// See: https://github.com/multiformats/cid
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct Cid {
+    pub(crate) codec: u64,
+    pub(crate) hash: u64,
+    pub(crate) digest: Vec<u8>,
+}
+
+impl Cid {
+    /// Parses a CID given as a base58btc CIDv0 (`Qm…`), or as a CIDv1 in
+    /// base32, base36, base58btc, or base16 multibase encoding.
+    pub(crate) fn parse(input: &str) -> Option<Self> {
+        let bytes = match input.as_bytes() {
+            [b'Q', b'm', ..] if input.len() == 46 => base58_decode(input)?,
+            [b'b' | b'B', rest @ ..] => base32_decode(rest)?,
+            [b'k' | b'K', rest @ ..] => base36_decode(rest)?,
+            [b'z', rest @ ..] => base58_decode(std::str::from_utf8(rest).ok()?)?,
+            [b'f' | b'F', rest @ ..] => base16_decode(rest)?,
+            _ => return None,
+        };
+        let mut bytes = bytes.as_slice();
+        let cid = Self::read(&mut bytes)?;
+        bytes.is_empty().then_some(cid)
+    }
+
+    /// Reads a CID in its binary form from the start of the input.
+    fn read(input: &mut &[u8]) -> Option<Self> {
+        // A CIDv0 is a bare SHA-256 multihash of a dag-pb block:
+        if input.starts_with(&[0x12, 0x20]) {
+            let digest = take(input, 34)?[2..].to_vec();
+            return Some(Self {
+                codec: DAG_PB,
+                hash: SHA2_256,
+                digest,
+            });
+        }
+        if read_varint(input)? != 1 {
+            return None;
+        }
+        let codec = read_varint(input)?;
+        let hash = read_varint(input)?;
+        let len = usize::try_from(read_varint(input)?).ok()?;
+        let digest = take(input, len)?.to_vec();
+        Some(Self {
+            codec,
+            hash,
+            digest,
+        })
+    }
+
+    /// Checks that the given block is the one named by this CID.
+    fn verify(&self, block: &[u8]) -> Result<()> {
+        let valid = match self.hash {
+            IDENTITY => self.digest == block,
+            SHA2_256 => Sha256::digest(block)[..] == self.digest[..],
+            SHA2_512 => Sha512::digest(block)[..] == self.digest[..],
+            hash => return Err(unsupported(format!("multihash 0x{:x} in {}", hash, self))),
+        };
+        match valid {
+            true => Ok(()),
+            false => Err(invalid(format!("block does not match {}", self))),
+        }
+    }
+}
+
+impl core::fmt::Display for Cid {
+    /// Formats the CID as a CIDv1 in base32, as used in subdomain gateways.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut bytes = Vec::with_capacity(self.digest.len() + 8);
+        for value in [1, self.codec, self.hash, self.digest.len() as u64] {
+            write_varint(&mut bytes, value);
+        }
+        bytes.extend_from_slice(&self.digest);
+        write!(f, "b{}", base32_encode(&bytes))
+    }
+}
+
+/// Verifies a CAR stream as it arrives, yielding the contents of the
+/// requested entity: the bytes of a UnixFS file, or the names of the
+/// entries of a UnixFS directory, one per line.
+///
+/// Blocks may arrive in any order, but each is checked against its CID
+/// before it is used, and only blocks reachable from the root CID through
+/// verified links contribute to the output. Blocks that arrive before they
+/// are needed are held up to a limit, so that a gateway can't exhaust the
+/// memory with blocks that are valid but never needed.
+pub(crate) struct CarVerifier {
+    buffer: Vec<u8>,
+    header: bool,
+    path: VecDeque<String>,
+    pending: Vec<Cid>,
+    blocks: HashMap<Cid, Vec<u8>>,
+    buffered: usize,
+    max_buffered: usize,
+}
+
+impl CarVerifier {
+    /// Creates a verifier for the entity at the given path below the root.
+    pub(crate) fn new(root: Cid, path: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            buffer: Vec::new(),
+            header: false,
+            path: path.into_iter().collect(),
+            pending: vec![root],
+            blocks: HashMap::new(),
+            buffered: 0,
+            max_buffered: MAX_BUFFERED_LEN,
+        }
+    }
+
+    /// Consumes the next part of the CAR stream, appending any contents
+    /// that can now be verified to the output.
+    pub(crate) fn push(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        self.buffer.extend_from_slice(input);
+
+        let mut consumed = 0;
+        loop {
+            let mut rest = &self.buffer[consumed..];
+            let Some(len) = read_varint(&mut rest) else {
+                break;
+            };
+            let len = usize::try_from(len)
+                .ok()
+                .filter(|len| *len <= MAX_SECTION_LEN)
+                .ok_or_else(|| invalid("CAR section is too large"))?;
+            let Some(mut section) = rest.get(..len) else {
+                break;
+            };
+            consumed = self.buffer.len() - rest.len() + len;
+
+            // The header names the roots, but only the requested CID matters:
+            if !self.header {
+                self.header = true;
+                continue;
+            }
+
+            let cid = Cid::read(&mut section).ok_or_else(|| invalid("malformed CID in CAR"))?;
+            cid.verify(section)?;
+            let block = section.to_vec();
+            self.buffered += block.len();
+            if let Some(previous) = self.blocks.insert(cid, block) {
+                self.buffered -= previous.len();
+            }
+            // Blocks sent in the order they are needed are used right away,
+            // and others are held until they are needed, up to a limit:
+            self.drain(output)?;
+            if self.buffered > self.max_buffered {
+                return Err(invalid("CAR holds too many blocks that aren't needed yet"));
+            }
+        }
+        self.buffer.drain(..consumed);
+
+        Ok(())
+    }
+
+    /// Checks that the CAR stream contained every block of the entity.
+    pub(crate) fn finish(&mut self, output: &mut Vec<u8>) -> Result<()> {
+        self.drain(output)?;
+        match self.pending.last() {
+            Some(cid) => Err(invalid(format!("CAR is missing block {}", cid))),
+            None if !self.buffer.is_empty() => Err(invalid("CAR ends with a partial section")),
+            None => Ok(()),
+        }
+    }
+
+    /// Visits the pending blocks that have arrived, in depth-first order.
+    fn drain(&mut self, output: &mut Vec<u8>) -> Result<()> {
+        while let Some(cid) = self.pending.last() {
+            let block = match cid.hash {
+                IDENTITY => cid.digest.clone(),
+                _ => match self.blocks.remove(cid) {
+                    Some(block) => {
+                        self.buffered -= block.len();
+                        block
+                    }
+                    None => break,
+                },
+            };
+            let cid = self.pending.pop().expect("a CID is pending");
+            self.visit(&cid, &block, output)?;
+        }
+        Ok(())
+    }
+
+    fn visit(&mut self, cid: &Cid, block: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        let not_a_directory = |name: &str| invalid(format!("{} has no entry {:?}", cid, name));
+
+        if cid.codec == RAW {
+            if let Some(name) = self.path.front() {
+                return Err(not_a_directory(name));
+            }
+            output.extend_from_slice(block);
+            return Ok(());
+        }
+        if cid.codec != DAG_PB {
+            return Err(unsupported(format!("codec 0x{:x} in {}", cid.codec, cid)));
+        }
+
+        let malformed = || invalid(format!("malformed dag-pb block {}", cid));
+        let node = PbNode::decode(block).ok_or_else(malformed)?;
+        let unixfs = UnixFsData::decode(node.data).ok_or_else(malformed)?;
+
+        // See: https://specs.ipfs.tech/unixfs/#data-types
+        match (unixfs.kind, self.path.pop_front()) {
+            (UnixFsData::RAW | UnixFsData::FILE, None) => {
+                output.extend_from_slice(unixfs.data);
+                let links = node.links.into_iter().rev().map(|link| link.cid);
+                self.pending.extend(links);
+            }
+            (UnixFsData::DIRECTORY, None) => {
+                for link in node.links {
+                    output.extend_from_slice(link.name.as_bytes());
+                    output.push(b'\n');
+                }
+            }
+            (UnixFsData::DIRECTORY, Some(name)) => {
+                let link = node
+                    .links
+                    .into_iter()
+                    .find(|link| link.name == name)
+                    .ok_or_else(|| not_a_directory(&name))?;
+                self.pending.push(link.cid);
+            }
+            (UnixFsData::HAMT_SHARD, _) => {
+                return Err(unsupported(format!("sharded directory {}", cid)))
+            }
+            (UnixFsData::SYMLINK, _) => return Err(unsupported(format!("symlink {}", cid))),
+            (_, Some(name)) => return Err(not_a_directory(&name)),
+            (kind, None) => return Err(unsupported(format!("UnixFS type {} in {}", kind, cid))),
+        }
+        Ok(())
+    }
+}
+
+/// A reader that verifies a CAR stream, yielding the contents of the
+/// requested entity.
+pub(crate) struct CarReader<R> {
+    inner: R,
+    verifier: CarVerifier,
+    output: Vec<u8>,
+    position: usize,
+    done: bool,
+}
+
+impl<R: Read> CarReader<R> {
+    pub(crate) fn new(inner: R, verifier: CarVerifier) -> Self {
+        Self {
+            inner,
+            verifier,
+            output: Vec::new(),
+            position: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Read for CarReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut input = [0; 16 << 10];
+        while self.position == self.output.len() && !self.done {
+            self.output.clear();
+            self.position = 0;
+            match self.inner.read(&mut input)? {
+                0 => {
+                    self.done = true;
+                    self.verifier.finish(&mut self.output)?;
+                }
+                n => self.verifier.push(&input[..n], &mut self.output)?,
+            }
+        }
+        let n = buf.len().min(self.output.len() - self.position);
+        buf[..n].copy_from_slice(&self.output[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Wraps a stream of CAR chunks so that it yields the verified contents of
+/// the requested entity.
+#[cfg(feature = "async")]
+pub(crate) fn verify_stream(
+    stream: crate::r#async::ByteStream,
+    verifier: CarVerifier,
+) -> crate::r#async::ByteStream {
+    use futures_util::StreamExt;
+
+    let state = (stream, verifier, false);
+    Box::pin(futures_util::stream::unfold(
+        state,
+        |(mut stream, mut verifier, done)| async move {
+            if done {
+                return None;
+            }
+            let mut output = Vec::new();
+            let result = match stream.next().await {
+                Some(Ok(chunk)) => verifier.push(&chunk, &mut output),
+                Some(Err(error)) => return Some((Err(error), (stream, verifier, true))),
+                None => {
+                    let result = verifier.finish(&mut output);
+                    let result = result.map(|_| bytes::Bytes::from(output));
+                    return Some((result.map_err(Into::into), (stream, verifier, true)));
+                }
+            };
+            let done = result.is_err();
+            let result = result.map(|_| bytes::Bytes::from(output));
+            Some((result.map_err(Into::into), (stream, verifier, done)))
+        },
+    ))
+}
+
+/// The parts of a dag-pb node that matter for UnixFS.
+///
+/// See: https://ipld.io/specs/codecs/dag-pb/spec/
+struct PbNode<'a> {
+    data: &'a [u8],
+    links: Vec<PbLink>,
+}
+
+struct PbLink {
+    cid: Cid,
+    name: String,
+}
+
+impl<'a> PbNode<'a> {
+    fn decode(input: &'a [u8]) -> Option<Self> {
+        let mut node = Self {
+            data: &[],
+            links: Vec::new(),
+        };
+        for field in ProtobufFields(input) {
+            match field? {
+                (1, Field::Bytes(data)) => node.data = data,
+                (2, Field::Bytes(link)) => node.links.push(PbLink::decode(link)?),
+                _ => {}
+            }
+        }
+        Some(node)
+    }
+}
+
+impl PbLink {
+    fn decode(input: &[u8]) -> Option<Self> {
+        let (mut cid, mut name) = (None, String::new());
+        for field in ProtobufFields(input) {
+            match field? {
+                (1, Field::Bytes(mut hash)) => {
+                    cid = Some(Cid::read(&mut hash).filter(|_| hash.is_empty())?)
+                }
+                (2, Field::Bytes(bytes)) => name = String::from_utf8(bytes.to_vec()).ok()?,
+                _ => {}
+            }
+        }
+        Some(Self { cid: cid?, name })
+    }
+}
+
+/// The parts of the UnixFS metadata of a dag-pb node that matter here.
+///
+/// See: https://specs.ipfs.tech/unixfs/#dag-pb-node
+struct UnixFsData<'a> {
+    kind: u64,
+    data: &'a [u8],
+}
+
+impl<'a> UnixFsData<'a> {
+    const RAW: u64 = 0;
+    const DIRECTORY: u64 = 1;
+    const FILE: u64 = 2;
+    const SYMLINK: u64 = 4;
+    const HAMT_SHARD: u64 = 5;
+
+    fn decode(input: &'a [u8]) -> Option<Self> {
+        let (mut kind, mut data) = (None, &[][..]);
+        for field in ProtobufFields(input) {
+            match field? {
+                (1, Field::Varint(value)) => kind = Some(value),
+                (2, Field::Bytes(bytes)) => data = bytes,
+                _ => {}
+            }
+        }
+        Some(Self { kind: kind?, data })
+    }
+}
+
+pub(crate) enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Iterates over the fields of a protobuf message, yielding `None` if the
+/// message is malformed.
+pub(crate) struct ProtobufFields<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Iterator for ProtobufFields<'a> {
+    type Item = Option<(u64, Field<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let field = (|| {
+            let key = read_varint(&mut self.0)?;
+            let value = match key & 7 {
+                0 => Field::Varint(read_varint(&mut self.0)?),
+                1 => take(&mut self.0, 8).map(|_| Field::Fixed)?,
+                2 => {
+                    let len = usize::try_from(read_varint(&mut self.0)?).ok()?;
+                    Field::Bytes(take(&mut self.0, len)?)
+                }
+                5 => take(&mut self.0, 4).map(|_| Field::Fixed)?,
+                _ => return None,
+            };
+            Some((key >> 3, value))
+        })();
+        if field.is_none() {
+            self.0 = &[];
+        }
+        Some(field)
+    }
+}
+
+/// Reads an unsigned LEB128 varint, as used by multiformats and protobuf.
+pub(crate) fn read_varint(input: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (i, byte) in input.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *input = &input[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+pub(crate) fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if input.len() < len {
+        return None;
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Some(head)
+}
+
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+const BASE36: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const BASE58: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+fn base32_decode(input: &[u8]) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 5 / 8);
+    let (mut bits, mut count) = (0u32, 0);
+    for byte in input {
+        let value = BASE32
+            .iter()
+            .position(|c| *c == byte.to_ascii_lowercase())?;
+        bits = (bits << 5) | value as u32;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            output.push((bits >> count) as u8);
+        }
+    }
+    Some(output)
+}
+
+fn base32_encode(input: &[u8]) -> String {
+    let mut output = String::with_capacity((input.len() * 8).div_ceil(5));
+    let (mut bits, mut count) = (0u32, 0);
+    for byte in input {
+        bits = (bits << 8) | u32::from(*byte);
+        count += 8;
+        while count >= 5 {
+            count -= 5;
+            output.push(BASE32[(bits >> count) as usize & 31] as char);
+        }
+    }
+    if count > 0 {
+        output.push(BASE32[(bits << (5 - count)) as usize & 31] as char);
+    }
+    output
+}
+
+fn base36_decode(input: &[u8]) -> Option<Vec<u8>> {
+    base_x_decode(&input.to_ascii_lowercase(), BASE36)
+}
+
+pub(crate) fn base58_decode(input: &str) -> Option<Vec<u8>> {
+    base_x_decode(input.as_bytes(), BASE58)
+}
+
+/// Decodes a big-endian number in the given alphabet, as used by base36
+/// and base58btc.
+fn base_x_decode(input: &[u8], alphabet: &[u8]) -> Option<Vec<u8>> {
+    // Each leading zero digit stands for a leading zero byte:
+    let zeros = input.iter().take_while(|c| **c == alphabet[0]).count();
+    let mut output: Vec<u8> = Vec::with_capacity(input.len());
+    for byte in &input[zeros..] {
+        let mut carry = alphabet.iter().position(|c| c == byte)? as u32;
+        for digit in output.iter_mut().rev() {
+            carry += u32::from(*digit) * alphabet.len() as u32;
+            *digit = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            output.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    let mut bytes = vec![0; zeros];
+    bytes.extend(output);
+    Some(bytes)
+}
+
+fn base16_decode(input: &[u8]) -> Option<Vec<u8>> {
+    input
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::IpfsVerificationFailed(message.into())
+}
+
+fn unsupported(message: impl Into<String>) -> Error {
+    Error::UnsupportedIpfsContent(message.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Encodes a CAR with the given blocks, which are named by the CIDv1
+    /// of the given codec and the SHA-256 of their contents.
+    fn car(blocks: &[(u64, &[u8])]) -> (Vec<Cid>, Vec<u8>) {
+        let mut cids = Vec::new();
+        let mut car = vec![1, 0]; // an empty header, for the purposes of the verifier
+        for (codec, block) in blocks {
+            let cid = Cid {
+                codec: *codec,
+                hash: SHA2_256,
+                digest: Sha256::digest(block).to_vec(),
+            };
+            let mut section = Vec::new();
+            for value in [1, cid.codec, cid.hash, 32] {
+                write_varint(&mut section, value);
+            }
+            section.extend_from_slice(&cid.digest);
+            section.extend_from_slice(block);
+            write_varint(&mut car, section.len() as u64);
+            car.extend(section);
+            cids.push(cid);
+        }
+        (cids, car)
+    }
+
+    fn verify(root: &Cid, path: &[&str], car: &[u8]) -> Result<Vec<u8>> {
+        let path = path.iter().map(|name| name.to_string());
+        let mut verifier = CarVerifier::new(root.clone(), path);
+        let mut output = Vec::new();
+        // Feed the CAR one byte at a time, to exercise partial sections:
+        for byte in car {
+            verifier.push(std::slice::from_ref(byte), &mut output)?;
+        }
+        verifier.finish(&mut output)?;
+        Ok(output)
+    }
+
+    /// Encodes a dag-pb node with UnixFS metadata of the given type.
+    fn dag_pb(kind: u8, data: &[u8], links: &[(&Cid, &str)]) -> Vec<u8> {
+        let mut node = Vec::new();
+        for (cid, name) in links {
+            let mut hash = Vec::new();
+            for value in [1, cid.codec, cid.hash, 32] {
+                write_varint(&mut hash, value);
+            }
+            hash.extend_from_slice(&cid.digest);
+            let mut link = vec![0x0a, hash.len() as u8];
+            link.extend(hash);
+            link.extend([0x12, name.len() as u8]);
+            link.extend(name.as_bytes());
+            node.extend([0x12, link.len() as u8]);
+            node.extend(link);
+        }
+        let mut unixfs = vec![0x08, kind];
+        if !data.is_empty() {
+            unixfs.extend([0x12, data.len() as u8]);
+            unixfs.extend(data);
+        }
+        node.extend([0x0a, unixfs.len() as u8]);
+        node.extend(unixfs);
+        node
+    }
+
+    #[test]
+    fn parse_cid() {
+        let v0 = Cid::parse("QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n").unwrap();
+        let v1 = Cid::parse("bafybeihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku").unwrap();
+        assert_eq!(v0, v1);
+        assert_eq!(
+            v0.to_string(),
+            "bafybeihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+        assert_eq!(Cid::parse("QmInvalid"), None);
+        assert_eq!(Cid::parse("example.org"), None);
+    }
+
+    #[test]
+    fn verify_file() {
+        let (cids, chunks) = car(&[(RAW, b"hello "), (RAW, b"world")]);
+        let root = dag_pb(
+            UnixFsData::FILE as u8,
+            b"",
+            &[(&cids[0], ""), (&cids[1], "")],
+        );
+        let (root_cid, root_car) = car(&[(DAG_PB, &root)]);
+
+        // The root may come after its children:
+        let mut car = chunks.clone();
+        car.extend(&root_car[2..]);
+        assert_eq!(verify(&root_cid[0], &[], &car).unwrap(), b"hello world");
+
+        // A tampered block fails verification:
+        let mut tampered = car.clone();
+        let at = tampered.windows(5).position(|w| w == b"world").unwrap();
+        tampered[at] = b'W';
+        assert!(verify(&root_cid[0], &[], &tampered).is_err());
+
+        // A missing block fails verification:
+        assert!(verify(&root_cid[0], &[], &root_car).is_err());
+    }
+
+    #[test]
+    fn verify_path() {
+        let (file, file_car) = car(&[(RAW, b"hello")]);
+        let directory = dag_pb(UnixFsData::DIRECTORY as u8, b"", &[(&file[0], "hello.txt")]);
+        let (root, mut car) = car(&[(DAG_PB, &directory)]);
+        car.extend(&file_car[2..]);
+
+        assert_eq!(verify(&root[0], &["hello.txt"], &car).unwrap(), b"hello");
+        assert_eq!(verify(&root[0], &[], &car).unwrap(), b"hello.txt\n");
+        assert!(verify(&root[0], &["other.txt"], &car).is_err());
+    }
+
+    #[test]
+    fn limit_buffered_blocks() {
+        let (cids, car) = car(&[(RAW, b"unrelated"), (RAW, b"blocks"), (RAW, b"hello")]);
+        let mut output = Vec::new();
+
+        // Blocks that are needed right away are never held:
+        let mut verifier = CarVerifier::new(cids[2].clone(), []);
+        verifier.max_buffered = 16;
+        verifier.push(&car, &mut output).unwrap();
+        verifier.finish(&mut output).unwrap();
+        assert_eq!(output, b"hello");
+
+        // Blocks that aren't needed yet are held up to the limit:
+        let mut verifier = CarVerifier::new(cids[2].clone(), []);
+        verifier.max_buffered = 8;
+        assert!(verifier.push(&car, &mut output).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{OpenOptions, Read, ReadSeek, Resource, Result};
+use std::sync::LazyLock;
+
+static GLOBAL_CLIENT: LazyLock<Client> = LazyLock::new(Client::new);
+
+/// A reusable client that keeps connections alive between requests.
+///
+/// A client owns a set of default [`OpenOptions`] together with the state
+/// shared by the protocol handlers, such as pooled HTTP connections. It is
+/// cheap to clone, and clones share the same state.
+///
+/// The free [`open`](crate::open) function delegates to the
+/// [`global`](Client::global) client.
+///
+/// # Examples
+///
+/// ```edition2021,no_run
+/// # use gofer::*;
+/// let client = Client::new();
+/// for path in ["robots.txt", "humans.txt"] {
+///     let result = client.open(format!("https://www.google.com/{}", path));
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Client {
+    options: OpenOptions,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<OpenOptions> for Client {
+    fn from(options: OpenOptions) -> Self {
+        Self::with_options(options)
+    }
+}
+
+impl Client {
+    /// Creates a client with the default options.
+    pub fn new() -> Self {
+        Self::with_options(OpenOptions::new())
+    }
+
+    /// Creates a client with the given default options.
+    ///
+    /// If the options were obtained from another client, the new client
+    /// shares that client's state.
+    pub fn with_options(mut options: OpenOptions) -> Self {
+        options.state.get_or_insert_with(Default::default);
+        Self { options }
+    }
+
+    /// Returns the process-wide client used by [`open`](crate::open).
+    pub fn global() -> &'static Client {
+        &GLOBAL_CLIENT
+    }
+
+    /// Returns the default options used by this client.
+    ///
+    /// Options cloned from these and then modified continue to share this
+    /// client's state.
+    pub fn options(&self) -> &OpenOptions {
+        &self.options
+    }
+
+    /// Opens the given URL for reading.
+    pub fn open(&self, url: impl AsRef<str>) -> Result<Box<dyn Read>> {
+        self.options.open(url)
+    }
+
+    /// Opens the given URL, returning the reader together with the
+    /// metadata reported by the protocol handler.
+    pub fn open_with_metadata(&self, url: impl AsRef<str>) -> Result<Resource> {
+        self.options.open_with_metadata(url)
+    }
+
+    /// Opens the given URL for random access.
+    pub fn open_seekable(&self, url: impl AsRef<str>) -> Result<Box<dyn ReadSeek>> {
+        self.options.open_seekable(url)
+    }
+}
+
+/// The state shared by all clones of a [`Client`].
+#[derive(Default)]
+pub(crate) struct ClientState {
+    /// Pooled HTTP clients, keyed by their configuration.
+    #[cfg(any(feature = "http", feature = "https"))]
+    pub(crate) http: std::sync::Mutex<
+        std::collections::HashMap<crate::schemes::http::ClientKey, reqwest::blocking::Client>,
+    >,
+    /// Pooled async HTTP clients, keyed by their configuration.
+    #[cfg(all(feature = "async", any(feature = "http", feature = "https")))]
+    pub(crate) async_http: std::sync::Mutex<
+        std::collections::HashMap<crate::schemes::http::ClientKey, reqwest::Client>,
+    >,
+}
+
+impl core::fmt::Debug for ClientState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ClientState").finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, any(feature = "http", feature = "https")))]
+mod test {
+    use super::*;
+    use crate::schemes::http;
+
+    #[test]
+    fn pooled_http_clients() {
+        let client = Client::new();
+        let pool_size = || {
+            client
+                .options()
+                .get_state()
+                .unwrap()
+                .http
+                .lock()
+                .unwrap()
+                .len()
+        };
+
+        http::client(false, client.options()).unwrap();
+        http::client(false, client.options()).unwrap();
+        assert_eq!(pool_size(), 1);
+
+        let mut options = client.options().clone();
+        options.max_redirects(0);
+        http::client(false, &options).unwrap();
+        assert_eq!(pool_size(), 2);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn pooled_async_http_clients() {
+        let client = Client::new();
+        let pool_size = || {
+            client
+                .options()
+                .get_state()
+                .unwrap()
+                .async_http
+                .lock()
+                .unwrap()
+                .len()
+        };
+
+        http::async_client(false, client.options()).unwrap();
+        http::async_client(true, client.options()).unwrap();
+        http::async_client(false, client.options()).unwrap();
+        assert_eq!(pool_size(), 2);
+    }
+}
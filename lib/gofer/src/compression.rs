@@ -0,0 +1,328 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{Cursor, Error, Read, Resource, Result, Url};
+
+/// A compression format that can be transparently decompressed.
+///
+/// With the `decompress` feature enabled, opened resources are checked for
+/// compression using, in order of precedence, the `Content-Encoding` and
+/// `Content-Type` reported by the protocol handler, the file extension in
+/// the URL path, and, for resources reported with neither, the leading
+/// magic bytes of the contents, except on `stdin:`. Compressed resources
+/// are decompressed as they are read, unless disabled with
+/// [`OpenOptions::decompress`](crate::OpenOptions::decompress).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Compression {
+    /// Detects compression from a `Content-Encoding` value.
+    pub fn from_content_encoding(content_encoding: &str) -> Option<Self> {
+        match content_encoding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Compression::Gzip),
+            "zstd" => Some(Compression::Zstd),
+            "xz" => Some(Compression::Xz),
+            "bzip2" | "x-bzip2" => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Detects compression from a media type, without any parameters.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type {
+            "application/gzip" | "application/x-gzip" => Some(Compression::Gzip),
+            "application/zstd" => Some(Compression::Zstd),
+            "application/x-xz" => Some(Compression::Xz),
+            "application/x-bzip2" => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Detects compression from the file extension of a path.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let (_, extension) = path.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "gz" | "tgz" => Some(Compression::Gzip),
+            "zst" | "tzst" => Some(Compression::Zstd),
+            "xz" | "txz" => Some(Compression::Xz),
+            "bz2" | "tbz2" => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Detects compression from the leading bytes of the contents.
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            // With the only compression method, deflate:
+            [0x1F, 0x8B, 0x08, ..] => Some(Compression::Gzip),
+            [0x28, 0xB5, 0x2F, 0xFD, ..] => Some(Compression::Zstd),
+            [0xFD, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Compression::Xz),
+            // With a block size, then the magic of a block or of the end:
+            [b'B', b'Z', b'h', b'1'..=b'9', 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, ..]
+            | [b'B', b'Z', b'h', b'1'..=b'9', 0x17, 0x72, 0x45, 0x38, 0x50, 0x90, ..] => {
+                Some(Compression::Bzip2)
+            }
+            _ => None,
+        }
+    }
+
+    /// Wraps a reader of compressed contents with a decompressing reader.
+    pub fn decoder(self, reader: Box<dyn Read>) -> Result<Box<dyn Read>> {
+        Ok(match self {
+            // See: https://docs.rs/flate2/latest/flate2/read/struct.MultiGzDecoder.html
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            // See: https://docs.rs/ruzstd/latest/ruzstd/decoding/struct.StreamingDecoder.html
+            Compression::Zstd => Box::new(
+                ruzstd::decoding::StreamingDecoder::new(reader)
+                    .map_err(|e| Error::FailedDecompression(std::io::Error::other(e)))?,
+            ),
+            // See: https://docs.rs/xz2/latest/xz2/read/struct.XzDecoder.html
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+            // See: https://docs.rs/bzip2/latest/bzip2/read/struct.MultiBzDecoder.html
+            Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        })
+    }
+}
+
+/// The length of the longest magic number we detect.
+const MAGIC_LEN: usize = 10;
+
+/// Wraps the resource's reader with a decompressing reader, if the
+/// resource is compressed.
+pub(crate) fn decompress(resource: Resource, url: &Url) -> Result<Resource> {
+    let encoded = resource
+        .content_encoding
+        .as_deref()
+        .and_then(Compression::from_content_encoding);
+
+    let (resource, compression) = match detect(&resource, url) {
+        Some(compression) => (resource, Some(compression)),
+        None if sniffable(&resource, url) => sniff(resource)?,
+        None => (resource, None),
+    };
+
+    let Some(compression) = compression else {
+        return Ok(resource);
+    };
+
+    let mut resource = resource
+        .try_map_reader(|reader| compression.decoder(reader))?
+        .with_content_encoding(None)
+        .with_content_length(None);
+
+    // The media type describes the decompressed contents only if the
+    // compression was a content coding:
+    if encoded.is_none() {
+        resource.content_type = None;
+        resource.charset = None;
+    }
+
+    Ok(resource)
+}
+
+/// Wraps a stream with a decompressing stream, if the resource it belongs
+/// to, as described by the given metadata, is compressed.
+#[cfg(feature = "async")]
+pub(crate) async fn decompress_stream(
+    stream: crate::r#async::ByteStream,
+    metadata: &Resource,
+    url: &Url<'_>,
+) -> Result<crate::r#async::ByteStream> {
+    let (stream, compression) = match detect(metadata, url) {
+        Some(compression) => (stream, Some(compression)),
+        None if sniffable(metadata, url) => sniff_stream(stream).await?,
+        None => (stream, None),
+    };
+
+    Ok(match compression {
+        Some(compression) => decode_in_background(stream, compression),
+        None => stream,
+    })
+}
+
+/// Detects compression from the metadata of a resource.
+fn detect(resource: &Resource, url: &Url) -> Option<Compression> {
+    let encoded = resource
+        .content_encoding
+        .as_deref()
+        .and_then(Compression::from_content_encoding);
+
+    // The final URL reflects any redirects, or the member of an archive:
+    let final_url = resource
+        .final_url
+        .as_deref()
+        .and_then(|u| u.parse::<Url>().ok());
+    let path = final_url.as_ref().unwrap_or(url).path();
+
+    encoded.or_else(|| {
+        resource
+            .content_type
+            .as_deref()
+            .and_then(Compression::from_content_type)
+            .or_else(|| Compression::from_path(path))
+    })
+}
+
+/// Checks whether the contents of a resource may be sniffed: only those
+/// of resources that say nothing of their contents are, and never those
+/// of stdin, which would block until its first bytes arrive.
+fn sniffable(resource: &Resource, url: &Url) -> bool {
+    resource.content_type.is_none()
+        && resource.content_encoding.is_none()
+        && !url.scheme_str().eq_ignore_ascii_case("stdin")
+}
+
+/// Detects compression from the magic bytes at the start of the contents,
+/// without consuming them.
+fn sniff(mut resource: Resource) -> Result<(Resource, Option<Compression>)> {
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    resource
+        .by_ref()
+        .take(MAGIC_LEN as u64)
+        .read_to_end(&mut magic)
+        .map_err(Error::FailedDecompression)?;
+
+    let compression = Compression::from_magic(&magic);
+    let resource = resource.map_reader(|reader| Box::new(Cursor::new(magic).chain(reader)));
+    Ok((resource, compression))
+}
+
+/// Detects compression from the magic bytes at the start of a stream,
+/// without consuming them.
+#[cfg(feature = "async")]
+async fn sniff_stream(
+    mut stream: crate::r#async::ByteStream,
+) -> Result<(crate::r#async::ByteStream, Option<Compression>)> {
+    use futures_util::StreamExt;
+
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    while magic.len() < MAGIC_LEN {
+        match stream.next().await {
+            Some(chunk) => magic.extend_from_slice(&chunk.map_err(Error::FailedDecompression)?),
+            None => break,
+        }
+    }
+
+    let compression = Compression::from_magic(&magic);
+    let magic = futures_util::stream::once(async { Ok(bytes::Bytes::from(magic)) });
+    Ok((Box::pin(magic.chain(stream)), compression))
+}
+
+/// The size of the chunks that decompressed streams are read in.
+#[cfg(feature = "async")]
+const CHUNK_LEN: usize = 64 << 10;
+
+/// Decompresses a stream on a blocking thread, as the decoders are all
+/// blocking, passing on the decompressed chunks as they come.
+#[cfg(feature = "async")]
+fn decode_in_background(
+    stream: crate::r#async::ByteStream,
+    compression: Compression,
+) -> crate::r#async::ByteStream {
+    // See: https://docs.rs/tokio-util/latest/tokio_util/io/struct.SyncIoBridge.html
+    let reader = tokio_util::io::SyncIoBridge::new(tokio_util::io::StreamReader::new(stream));
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    tokio::task::spawn_blocking(move || {
+        let mut decoder = match compression.decoder(Box::new(reader)) {
+            Ok(decoder) => decoder,
+            Err(error) => return sender.blocking_send(Err(error.into())),
+        };
+        loop {
+            let mut chunk = vec![0; CHUNK_LEN];
+            let chunk = match decoder.read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(len) => {
+                    chunk.truncate(len);
+                    Ok(bytes::Bytes::from(chunk))
+                }
+                Err(error) => return sender.blocking_send(Err(error)),
+            };
+            // Stop once the stream is dropped:
+            sender.blocking_send(chunk)?;
+        }
+    });
+    Box::pin(futures_util::stream::poll_fn(move |cx| {
+        receiver.poll_recv(cx)
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn detection() {
+        assert_eq!(
+            Compression::from_content_encoding("GZIP"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_content_type("application/zstd"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(
+            Compression::from_path("/data/file.json.xz"),
+            Some(Compression::Xz)
+        );
+        assert_eq!(Compression::from_path("/data/file.json"), None);
+        assert_eq!(
+            Compression::from_magic(b"BZh91AY&SY"),
+            Some(Compression::Bzip2)
+        );
+        assert_eq!(Compression::from_magic(b"BZh, hello"), None);
+        assert_eq!(Compression::from_magic(&[0x1F, 0x8B, 0x00]), None);
+    }
+
+    #[test]
+    fn sniff_gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(b"hello").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let url = "file:///data".parse::<Url>().unwrap();
+        let resource = Resource::new(Cursor::new(compressed)).with_content_length(25);
+        let mut resource = decompress(resource, &url).unwrap();
+        assert_eq!(resource.content_length, None);
+
+        let mut output = String::new();
+        resource.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "hello");
+    }
+
+    #[test]
+    fn sniff_only_undescribed() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(b"hello").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        // Resources with a media type, and stdin, are taken as they are:
+        let described = Resource::new(Cursor::new(compressed.clone()))
+            .with_content_type("application/octet-stream");
+        let file = "file:///data".parse::<Url>().unwrap();
+        let stdin = Resource::new(Cursor::new(compressed.clone()));
+        let url = "stdin:".parse::<Url>().unwrap();
+        for (resource, url) in [(described, &file), (stdin, &url)] {
+            let mut output = Vec::new();
+            let mut resource = decompress(resource, url).unwrap();
+            resource.read_to_end(&mut output).unwrap();
+            assert_eq!(output, compressed);
+        }
+    }
+
+    #[test]
+    fn passthrough() {
+        let url = "file:///data".parse::<Url>().unwrap();
+        let resource = Resource::new(Cursor::new(b"hello")).with_content_length(5);
+        let mut resource = decompress(resource, &url).unwrap();
+        assert_eq!(resource.content_length, Some(5));
+
+        let mut output = String::new();
+        resource.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "hello");
+    }
+}
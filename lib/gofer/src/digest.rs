@@ -0,0 +1,148 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::Error;
+use core::{fmt, str::FromStr};
+use sha2::{Digest as _, Sha256, Sha384, Sha512};
+
+/// An expected cryptographic digest of a resource's contents.
+///
+/// Digests are written as an algorithm name and a hexadecimal value, e.g.
+/// `sha256=9f86d081…`. They may be given either in the fragment of the URL
+/// being opened, or with
+/// [`OpenOptions::expect_digest`](crate::OpenOptions::expect_digest).
+/// The contents are hashed as they are read, and reaching EOF fails with
+/// [`Error::IntegrityMismatch`] if the digest differs.
+///
+/// # Examples
+///
+/// ```edition2021
+/// # use gofer::*;
+/// let mut output = String::new();
+/// let result = open("data:,test#sha256=9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")?
+///     .read_to_string(&mut output);
+/// assert!(result.is_ok());
+/// # Ok::<(), gofer::Error>(())
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Digest {
+    Sha256([u8; 32]),
+    Sha384([u8; 48]),
+    Sha512([u8; 64]),
+}
+
+impl Digest {
+    /// Returns the name of the hash algorithm.
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Digest::Sha256(_) => "sha256",
+            Digest::Sha384(_) => "sha384",
+            Digest::Sha512(_) => "sha512",
+        }
+    }
+
+    /// Returns the digest value.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Digest::Sha256(bytes) => bytes,
+            Digest::Sha384(bytes) => bytes,
+            Digest::Sha512(bytes) => bytes,
+        }
+    }
+
+    /// Parses a digest from a URL fragment, if the fragment names a
+    /// supported hash algorithm.
+    pub(crate) fn from_fragment(fragment: &str) -> Option<Result<Self, Error>> {
+        let (algorithm, _) = fragment.split_once('=')?;
+        matches!(algorithm, "sha256" | "sha384" | "sha512").then(|| fragment.parse())
+    }
+}
+
+impl FromStr for Digest {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidDigest(input.to_string());
+        let (algorithm, hex) = input.split_once(['=', ':']).ok_or_else(invalid)?;
+        let bytes = decode_hex(hex).ok_or_else(invalid)?;
+        match algorithm.to_ascii_lowercase().as_str() {
+            "sha256" => bytes.try_into().map(Digest::Sha256).map_err(|_| invalid()),
+            "sha384" => bytes.try_into().map(Digest::Sha384).map_err(|_| invalid()),
+            "sha512" => bytes.try_into().map(Digest::Sha512).map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}=", self.algorithm())?;
+        self.as_bytes()
+            .iter()
+            .try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// An incremental hasher for the algorithm of an expected digest.
+pub(crate) enum Hasher {
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    pub(crate) fn new(expected: &Digest) -> Self {
+        match expected {
+            Digest::Sha256(_) => Hasher::Sha256(Sha256::new()),
+            Digest::Sha384(_) => Hasher::Sha384(Sha384::new()),
+            Digest::Sha512(_) => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha384(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    pub(crate) fn finalize(self) -> Digest {
+        match self {
+            Hasher::Sha256(hasher) => Digest::Sha256(hasher.finalize().into()),
+            Hasher::Sha384(hasher) => Digest::Sha384(hasher.finalize().into()),
+            Hasher::Sha512(hasher) => Digest::Sha512(hasher.finalize().into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        let input = "sha256=9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let digest: Digest = input.parse().unwrap();
+        assert_eq!(digest.to_string(), input);
+
+        let mut hasher = Hasher::new(&digest);
+        hasher.update(b"test");
+        assert_eq!(hasher.finalize(), digest);
+
+        assert!("sha256=9f86".parse::<Digest>().is_err());
+        assert!("md5=9f86d081884c7d659a2feaa0c55ad015"
+            .parse::<Digest>()
+            .is_err());
+        assert!(Digest::from_fragment("member.txt").is_none());
+    }
+}
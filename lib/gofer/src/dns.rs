@@ -0,0 +1,325 @@
+// This is free and unencumbered software released into the public domain.
+
+//! A minimal DNS stub resolver for looking up the TXT records that hold
+//! DNSLink values, which the standard library has no means to query.
+//!
+//! See: https://www.rfc-editor.org/rfc/rfc1035
+//! See: https://www.rfc-editor.org/rfc/rfc7766
+
+use crate::{Error, OpenOptions, Result};
+use std::{
+    hash::{BuildHasher, Hasher},
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpStream, UdpSocket},
+    time::Duration,
+};
+
+/// The default timeout for each query, as used by glibc.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const TXT: u16 = 16;
+const NXDOMAIN: u8 = 3;
+
+/// Looks up the TXT records of the given domain name, trying each of the
+/// configured DNS servers in turn.
+///
+/// Each record is returned with its strings joined together. A name that
+/// does not exist has no records.
+pub(crate) fn lookup_txt(name: &str, options: &OpenOptions) -> Result<Vec<String>> {
+    let servers = servers(options)?;
+    let query = Query::new(name)?;
+
+    options.get_retry().run(|| {
+        let mut last_error = None;
+        for server in &servers {
+            match exchange(&query, *server, options) {
+                Ok(records) => return Ok(records),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(Error::FailedDnsQuery(
+            last_error.expect("there is at least one server"),
+        ))
+    })
+}
+
+/// Looks up the TXT records of the given domain name, asynchronously.
+///
+/// See [`lookup_txt`].
+#[cfg(feature = "async")]
+pub(crate) async fn lookup_txt_async(name: &str, options: &OpenOptions) -> Result<Vec<String>> {
+    let servers = servers(options)?;
+    let query = Query::new(name)?;
+
+    let attempt = || async {
+        let mut last_error = None;
+        for server in &servers {
+            match exchange_async(&query, *server, options).await {
+                Ok(records) => return Ok(records),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(Error::FailedDnsQuery(
+            last_error.expect("there is at least one server"),
+        ))
+    };
+
+    options.get_retry().run_async(attempt).await
+}
+
+/// Sends the query over UDP, falling back to TCP if the response was
+/// truncated.
+fn exchange(
+    query: &Query,
+    server: SocketAddr,
+    options: &OpenOptions,
+) -> std::io::Result<Vec<String>> {
+    let timeout = options.get_read_timeout().unwrap_or(TIMEOUT);
+
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => ([0; 4], 0).into(),
+        SocketAddr::V6(_) => ([0; 16], 0).into(),
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(server)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.send(&query.bytes)?;
+    let mut response = [0; 512];
+    let response = loop {
+        // On Unix, a read timeout surfaces as `WouldBlock`:
+        let len = socket.recv(&mut response).map_err(|e| match e.kind() {
+            ErrorKind::WouldBlock => ErrorKind::TimedOut.into(),
+            _ => e,
+        })?;
+        // Ignore stray datagrams, such as late responses to earlier queries:
+        if query.matches(&response[..len]) {
+            break &response[..len];
+        }
+    };
+    if !is_truncated(response) {
+        return query.parse(response);
+    }
+
+    let connect_timeout = options.get_connect_timeout().unwrap_or(TIMEOUT);
+    let mut stream = TcpStream::connect_timeout(&server, connect_timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.write_all(&query.framed())?;
+    let mut len = [0; 2];
+    stream.read_exact(&mut len)?;
+    let mut response = vec![0; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response)?;
+    query.parse(&response)
+}
+
+#[cfg(feature = "async")]
+async fn exchange_async(
+    query: &Query,
+    server: SocketAddr,
+    options: &OpenOptions,
+) -> std::io::Result<Vec<String>> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::time::timeout;
+
+    let read_timeout = options.get_read_timeout().unwrap_or(TIMEOUT);
+    let timed_out = |_| std::io::Error::from(ErrorKind::TimedOut);
+
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => ([0; 4], 0).into(),
+        SocketAddr::V6(_) => ([0; 16], 0).into(),
+    };
+    let socket = tokio::net::UdpSocket::bind(local).await?;
+    socket.connect(server).await?;
+    socket.send(&query.bytes).await?;
+    let mut response = [0; 512];
+    let response = loop {
+        let len = timeout(read_timeout, socket.recv(&mut response))
+            .await
+            .map_err(timed_out)??;
+        if query.matches(&response[..len]) {
+            break &response[..len];
+        }
+    };
+    if !is_truncated(response) {
+        return query.parse(response);
+    }
+
+    let connect_timeout = options.get_connect_timeout().unwrap_or(TIMEOUT);
+    let mut stream = timeout(connect_timeout, tokio::net::TcpStream::connect(server))
+        .await
+        .map_err(timed_out)??;
+    stream.write_all(&query.framed()).await?;
+    let response = timeout(read_timeout, async {
+        let len = stream.read_u16().await?;
+        let mut response = vec![0; len as usize];
+        stream.read_exact(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    })
+    .await
+    .map_err(timed_out)??;
+    query.parse(&response)
+}
+
+/// Returns the configured DNS server, or else the nameservers listed in
+/// `/etc/resolv.conf`.
+fn servers(options: &OpenOptions) -> Result<Vec<SocketAddr>> {
+    if let Some(server) = options.get_dns_server() {
+        return Ok(vec![server]);
+    }
+    let servers: Vec<SocketAddr> = std::fs::read_to_string("/etc/resolv.conf")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.strip_prefix("nameserver"))
+        .filter_map(|address| {
+            // Zone indices (e.g., `fe80::1%eth0`) aren't supported:
+            let address = address.trim().parse::<std::net::IpAddr>().ok()?;
+            Some(SocketAddr::new(address, 53))
+        })
+        .collect();
+    match servers.is_empty() {
+        true => Err(Error::FailedDnsQuery(std::io::Error::new(
+            ErrorKind::NotFound,
+            "no DNS server configured",
+        ))),
+        false => Ok(servers),
+    }
+}
+
+fn is_truncated(response: &[u8]) -> bool {
+    response[2] & 0x02 != 0
+}
+
+/// A query for the TXT records of a domain name.
+struct Query {
+    id: u16,
+    bytes: Vec<u8>,
+}
+
+impl Query {
+    fn new(name: &str) -> Result<Self> {
+        // The ID only guards against mistaking stray responses for ours, so
+        // the randomly keyed hasher of the standard library suffices:
+        let id = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish() as u16;
+
+        let mut bytes = id.to_be_bytes().to_vec();
+        // A standard query, with recursion desired, and one question:
+        bytes.extend([0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name.trim_end_matches('.').split('.') {
+            if label.is_empty() || label.len() > 63 {
+                return Err(Error::IpnsResolutionFailed(format!(
+                    "invalid domain name {}",
+                    name
+                )));
+            }
+            bytes.push(label.len() as u8);
+            bytes.extend(label.as_bytes());
+        }
+        bytes.push(0);
+        bytes.extend(TXT.to_be_bytes());
+        bytes.extend(1u16.to_be_bytes()); // the Internet class
+        Ok(Self { id, bytes })
+    }
+
+    /// Returns the query prefixed with its length, as sent over TCP.
+    fn framed(&self) -> Vec<u8> {
+        let mut framed = (self.bytes.len() as u16).to_be_bytes().to_vec();
+        framed.extend(&self.bytes);
+        framed
+    }
+
+    /// Checks whether the given message is a response to this query.
+    fn matches(&self, response: &[u8]) -> bool {
+        response.len() >= 12 && response[..2] == self.id.to_be_bytes() && response[2] & 0x80 != 0
+    }
+
+    /// Returns the TXT records in the answer section of the response.
+    fn parse(&self, response: &[u8]) -> std::io::Result<Vec<String>> {
+        let malformed = || std::io::Error::new(ErrorKind::InvalidData, "malformed DNS response");
+        if !self.matches(response) {
+            return Err(malformed());
+        }
+        match response[3] & 0x0f {
+            0 => {}
+            NXDOMAIN => return Ok(Vec::new()),
+            rcode => {
+                return Err(std::io::Error::other(format!(
+                    "DNS server failed with response code {}",
+                    rcode
+                )))
+            }
+        }
+        let count = |i: usize| u16::from_be_bytes([response[i], response[i + 1]]);
+        let (questions, answers) = (count(4), count(6));
+
+        let mut input = &response[12..];
+        for _ in 0..questions {
+            skip_name(&mut input).ok_or_else(malformed)?;
+            take(&mut input, 4).ok_or_else(malformed)?;
+        }
+        let mut records = Vec::new();
+        for _ in 0..answers {
+            skip_name(&mut input).ok_or_else(malformed)?;
+            let header = take(&mut input, 10).ok_or_else(malformed)?;
+            let kind = u16::from_be_bytes([header[0], header[1]]);
+            let len = u16::from_be_bytes([header[8], header[9]]) as usize;
+            let mut data = take(&mut input, len).ok_or_else(malformed)?;
+            // Answers may include the CNAME records that led to the TXT ones:
+            if kind != TXT {
+                continue;
+            }
+            let mut record = Vec::new();
+            while let Some((&len, rest)) = data.split_first() {
+                data = rest;
+                record.extend(take(&mut data, len as usize).ok_or_else(malformed)?);
+            }
+            records.push(String::from_utf8_lossy(&record).into_owned());
+        }
+        Ok(records)
+    }
+}
+
+/// Skips over a possibly compressed domain name.
+fn skip_name(input: &mut &[u8]) -> Option<()> {
+    loop {
+        let len = *take(input, 1)?.first()?;
+        match len {
+            0 => return Some(()),
+            // A pointer to a name elsewhere ends this one:
+            0xc0..=0xff => return take(input, 1).map(|_| ()),
+            len => take(input, len as usize)?,
+        };
+    }
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    let (head, tail) = input.split_at_checked(len)?;
+    *input = tail;
+    Some(head)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_response() {
+        let query = Query::new("_dnslink.example.org").unwrap();
+        let mut response = query.bytes.clone();
+        response[2] |= 0x80;
+        response[7] = 2;
+        // A CNAME record, compressed against the question:
+        response.extend([0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 12]);
+        // A TXT record split into two strings:
+        response.extend([0xc0, 12, 0, 16, 0, 1, 0, 0, 0, 60, 0, 14]);
+        response.extend(b"\x08dnslink=\x04/ipf");
+        assert_eq!(query.parse(&response).unwrap(), ["dnslink=/ipf"]);
+
+        response[3] = NXDOMAIN;
+        assert!(query.parse(&response).unwrap().is_empty());
+        response[3] = 2;
+        assert!(query.parse(&response).is_err());
+        response[..2].copy_from_slice(&query.id.wrapping_add(1).to_be_bytes());
+        assert!(query.parse(&response).is_err());
+    }
+}
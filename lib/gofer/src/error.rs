@@ -29,6 +29,112 @@ pub enum Error {
     )]
     UnknownScheme(String),
 
+    #[error("resource exceeds the maximum size of {0} bytes")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::too_large),
+            help("increase the maximum size in the open options, or remove the limit"),
+            url(docsrs),
+        )
+    )]
+    TooLarge(u64),
+
+    #[error("URL scheme does not support seeking: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::not_seekable),
+            help("open the URL for sequential reading instead, or copy it to a local file"),
+            url(docsrs),
+        )
+    )]
+    NotSeekable(String),
+
+    #[cfg(feature = "decompress")]
+    #[error("failed decompression: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_decompression),
+            help("the contents appear to be compressed, but could not be decompressed"),
+            url(docsrs),
+        )
+    )]
+    FailedDecompression(std::io::Error),
+
+    #[cfg(feature = "integrity")]
+    #[error("invalid digest: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::invalid_digest),
+            help("digests are written as an algorithm and a hex value, e.g. `sha256=…`"),
+            url(docsrs),
+        )
+    )]
+    InvalidDigest(String),
+
+    #[cfg(feature = "integrity")]
+    #[error("integrity mismatch: expected {expected}, got {actual}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::integrity_mismatch),
+            help("the contents were corrupted or have changed since the digest was taken"),
+            url(docsrs),
+        )
+    )]
+    IntegrityMismatch { expected: String, actual: String },
+
+    #[cfg(feature = "archive")]
+    #[error("invalid archive URL: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::invalid_archive_url),
+            help("use `zip:<url>!/<member>` or `zip+<url>#<member>` (or `tar`)"),
+            url(docsrs),
+        )
+    )]
+    InvalidArchiveUrl(String),
+
+    #[cfg(feature = "archive")]
+    #[error("archive member not found: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::missing_archive_member),
+            help("check the path of the member within the archive"),
+            url(docsrs),
+        )
+    )]
+    MissingArchiveMember(String),
+
+    #[cfg(feature = "archive")]
+    #[error("failed to read archive: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_archive_read),
+            help("the archive may be corrupt or use an unsupported feature"),
+            url(docsrs),
+        )
+    )]
+    FailedArchiveRead(std::io::Error),
+
+    #[cfg(feature = "cache")]
+    #[error("failed cache I/O: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_cache_io),
+            help("check the permissions and free space of the cache directory"),
+            url(docsrs),
+        )
+    )]
+    FailedCacheIo(std::io::Error),
+
     #[cfg(feature = "data")]
     #[error("invalid data URL: {0}")]
     #[cfg_attr(
@@ -101,6 +207,126 @@ pub enum Error {
     )]
     FailedFtpRequest(#[from] suppaftp::FtpError),
 
+    #[cfg(feature = "gemini")]
+    #[error("invalid Gemini URL: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::invalid_gemini_url),
+            help("it seems that the URL is malformed in some way"),
+            url(docsrs),
+        )
+    )]
+    InvalidGeminiUrl(String),
+
+    #[cfg(feature = "gemini")]
+    #[error("failed Gemini request: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_gemini_request),
+            help("something went wrong connecting to the server or requesting the resource"),
+            url(docsrs),
+        )
+    )]
+    FailedGeminiRequest(std::io::Error),
+
+    #[cfg(feature = "gemini")]
+    #[error("invalid Gemini response: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::invalid_gemini_response),
+            help("the server sent a response header that is malformed or can't be followed"),
+            url(docsrs),
+        )
+    )]
+    InvalidGeminiResponse(String),
+
+    #[cfg(feature = "gemini")]
+    #[error("Gemini input required for {url}: {prompt}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::gemini_input_required),
+            help("append the input to the URL as a query string"),
+            url(docsrs),
+        )
+    )]
+    GeminiInputRequired { url: String, prompt: String },
+
+    #[cfg(feature = "gemini")]
+    #[error("Gemini temporary failure {status} for {url}: {meta}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::gemini_temporary_failure),
+            help("the server is unable to respond right now, so the request may succeed later"),
+            url(docsrs),
+        )
+    )]
+    GeminiTemporaryFailure {
+        status: u8,
+        url: String,
+        meta: String,
+    },
+
+    #[cfg(feature = "gemini")]
+    #[error("Gemini permanent failure {status} for {url}: {meta}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::gemini_permanent_failure),
+            help("the server responded with an error status instead of the resource"),
+            url(docsrs),
+        )
+    )]
+    GeminiPermanentFailure {
+        status: u8,
+        url: String,
+        meta: String,
+    },
+
+    #[cfg(feature = "gemini")]
+    #[error("Gemini client certificate required for {url}: {meta}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::gemini_certificate_required),
+            help("the resource requires a client certificate, which is not supported"),
+            url(docsrs),
+        )
+    )]
+    GeminiCertificateRequired {
+        status: u8,
+        url: String,
+        meta: String,
+    },
+
+    #[cfg(feature = "gemini")]
+    #[error("too many Gemini redirects: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::too_many_gemini_redirects),
+            help("increase the maximum number of redirects in the open options"),
+            url(docsrs),
+        )
+    )]
+    TooManyGeminiRedirects(String),
+
+    #[cfg(feature = "gemini")]
+    #[error("untrusted Gemini certificate: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::untrusted_gemini_certificate),
+            help("the server's certificate differs from the one seen before; remove its line from the known hosts file after verifying it"),
+            url(docsrs),
+        )
+    )]
+    UntrustedGeminiCertificate(String),
+
     #[cfg(any(feature = "http", feature = "https"))]
     #[error("failed HTTP request: {0}")]
     #[cfg_attr(
@@ -113,6 +339,47 @@ pub enum Error {
     )]
     FailedHttpRequest(#[from] reqwest::Error),
 
+    #[cfg(any(feature = "http", feature = "https"))]
+    #[error("HTTP status {status} for {url}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::http_status),
+            help("the server responded with an error status instead of the file"),
+            url(docsrs),
+        )
+    )]
+    HttpStatus {
+        status: u16,
+        url: String,
+        body_excerpt: String,
+        retry_after: Option<std::time::Duration>,
+    },
+
+    #[cfg(any(feature = "http", feature = "https"))]
+    #[error("HTTP server does not support range requests for {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::range_not_supported),
+            help("the server did not send `Accept-Ranges: bytes`, so the resource can only be read sequentially"),
+            url(docsrs),
+        )
+    )]
+    RangeNotSupported(String),
+
+    #[cfg(any(feature = "http", feature = "https"))]
+    #[error("resource changed while being read: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::resource_changed),
+            help("the resource was modified on the server, so reading it again from the start may succeed"),
+            url(docsrs),
+        )
+    )]
+    ResourceChanged(String),
+
     #[cfg(feature = "git")]
     #[error("invalid Git URL: {0}")]
     #[cfg_attr(
@@ -125,6 +392,66 @@ pub enum Error {
     )]
     InvalidGitUrl(String),
 
+    #[cfg(feature = "git")]
+    #[error("failed Git request: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_git_request),
+            help("something went wrong fetching from the git server or reading the repository"),
+            url(docsrs),
+        )
+    )]
+    FailedGitRequest(std::io::Error),
+
+    #[cfg(feature = "git")]
+    #[error("invalid Git data: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::invalid_git_data),
+            help("the git server sent a malformed response or a corrupt object"),
+            url(docsrs),
+        )
+    )]
+    InvalidGitData(String),
+
+    #[cfg(feature = "git")]
+    #[error("Git object not found: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::git_object_not_found),
+            help("the repository has no such branch, tag, commit, or file"),
+            url(docsrs),
+        )
+    )]
+    GitObjectNotFound(String),
+
+    #[cfg(feature = "gopher")]
+    #[error("invalid Gopher URL: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::invalid_gopher_url),
+            help("it seems that the URL is malformed in some way"),
+            url(docsrs),
+        )
+    )]
+    InvalidGopherUrl(String),
+
+    #[cfg(feature = "gopher")]
+    #[error("failed Gopher request: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_gopher_request),
+            help("something went wrong connecting to the server or requesting the item"),
+            url(docsrs),
+        )
+    )]
+    FailedGopherRequest(std::io::Error),
+
     #[cfg(feature = "ipfs")]
     #[error("invalid IPFS URL: {0}")]
     #[cfg_attr(
@@ -136,6 +463,271 @@ pub enum Error {
         )
     )]
     InvalidIpfsUrl(String),
+
+    #[cfg(feature = "ipfs")]
+    #[error("failed IPFS verification: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::ipfs_verification_failed),
+            help("the gateway returned content that does not match the requested CID"),
+            url(docsrs),
+        )
+    )]
+    IpfsVerificationFailed(String),
+
+    #[cfg(feature = "ipfs")]
+    #[error("unsupported IPFS content: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::unsupported_ipfs_content),
+            help("the content uses a format that can't be verified, so try a trusted Kubo node instead"),
+            url(docsrs),
+        )
+    )]
+    UnsupportedIpfsContent(String),
+
+    #[cfg(feature = "ipfs")]
+    #[error("failed IPNS resolution: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::ipns_resolution_failed),
+            help("the name has no valid DNSLink or IPNS record, so check that it was published"),
+            url(docsrs),
+        )
+    )]
+    IpnsResolutionFailed(String),
+
+    #[cfg(feature = "ipfs")]
+    #[error("failed DNS query: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_dns_query),
+            help("something went wrong querying the DNS server, so check the network and `/etc/resolv.conf`"),
+            url(docsrs),
+        )
+    )]
+    FailedDnsQuery(std::io::Error),
+
+    #[cfg(feature = "scp")]
+    #[error("invalid SCP URL: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::invalid_scp_url),
+            help("it seems that the URL is malformed in some way"),
+            url(docsrs),
+        )
+    )]
+    InvalidScpUrl(String),
+
+    #[cfg(any(feature = "scp", feature = "sftp"))]
+    #[error("failed SSH connection: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_ssh_connection),
+            help("something went wrong connecting to the server"),
+            url(docsrs),
+        )
+    )]
+    FailedSshConnection(std::io::Error),
+
+    #[cfg(any(feature = "scp", feature = "sftp"))]
+    #[error("failed SSH request: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_ssh_request),
+            help("something went wrong talking to the server or requesting the file"),
+            url(docsrs),
+        )
+    )]
+    FailedSshRequest(#[from] ssh2::Error),
+
+    #[cfg(any(feature = "scp", feature = "sftp"))]
+    #[error("failed SSH authentication: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_ssh_auth),
+            help("check the SSH agent, your identity files, or the password in the URL"),
+            url(docsrs),
+        )
+    )]
+    FailedSshAuth(String),
+
+    #[cfg(any(feature = "scp", feature = "sftp"))]
+    #[error("untrusted SSH host key: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::untrusted_host_key),
+            help("add the server's host key to your known hosts file after verifying it"),
+            url(docsrs),
+        )
+    )]
+    UntrustedHostKey(String),
+
+    #[cfg(feature = "sftp")]
+    #[error("invalid SFTP URL: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::invalid_sftp_url),
+            help("it seems that the URL is malformed in some way"),
+            url(docsrs),
+        )
+    )]
+    InvalidSftpUrl(String),
+
+    #[cfg(feature = "sftp")]
+    #[error("failed SFTP request: {0}")]
+    #[cfg_attr(
+        feature = "miette",
+        diagnostic(
+            code(gofer::failed_sftp_request),
+            help("something went wrong opening, reading, or listing the remote path"),
+            url(docsrs),
+        )
+    )]
+    FailedSftpRequest(ssh2::Error),
+}
+
+impl Error {
+    /// Returns whether the error is likely to be transient, such that the
+    /// same request may succeed if retried later.
+    ///
+    /// This is the case for failures to connect (including DNS failures),
+    /// dropped connections, and timeouts, as well as for HTTP 408, 429,
+    /// 502, 503, and 504 statuses and FTP 4xx replies.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            #[cfg(feature = "file")]
+            Error::FailedFileIo(e) => is_transient_io(e),
+
+            #[cfg(any(feature = "ftp", feature = "ftps"))]
+            Error::FailedFtpRequest(e) => is_transient_ftp(e),
+
+            #[cfg(any(feature = "http", feature = "https"))]
+            Error::FailedHttpRequest(e) => {
+                e.is_connect() || e.is_timeout() || is_transient_source(e)
+            }
+
+            #[cfg(feature = "gemini")]
+            Error::FailedGeminiRequest(e) => is_transient_io(e),
+
+            #[cfg(feature = "gemini")]
+            Error::GeminiTemporaryFailure { .. } => true,
+
+            #[cfg(feature = "git")]
+            Error::FailedGitRequest(e) => is_transient_io(e),
+
+            #[cfg(feature = "gopher")]
+            Error::FailedGopherRequest(e) => is_transient_io(e),
+
+            #[cfg(feature = "ipfs")]
+            Error::FailedDnsQuery(e) => is_transient_io(e),
+
+            #[cfg(any(feature = "http", feature = "https"))]
+            Error::HttpStatus { status, .. } => matches!(status, 408 | 429 | 502 | 503 | 504),
+
+            #[cfg(any(feature = "scp", feature = "sftp"))]
+            Error::FailedSshConnection(e) => is_transient_io(e),
+
+            _ => false,
+        }
+    }
+
+    /// Returns the delay before retrying that the server asked for with an
+    /// HTTP `Retry-After` header, if any.
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            #[cfg(any(feature = "http", feature = "https"))]
+            Error::HttpStatus { retry_after, .. } => *retry_after,
+
+            // See: https://geminiprotocol.net/docs/protocol-specification.gmi#status-44-slow-down
+            #[cfg(feature = "gemini")]
+            Error::GeminiTemporaryFailure {
+                status: 44, meta, ..
+            } => meta.trim().parse().ok().map(std::time::Duration::from_secs),
+
+            _ => None,
+        }
+    }
+}
+
+/// Returns whether an I/O error, or any error it wraps, is transient.
+#[cfg(any(
+    feature = "file",
+    feature = "ftp",
+    feature = "gemini",
+    feature = "gopher",
+    feature = "http",
+    feature = "scp",
+    feature = "sftp"
+))]
+pub(crate) fn is_transient_io(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind::*;
+    match error.kind() {
+        BrokenPipe | ConnectionAborted | ConnectionRefused | ConnectionReset | NotConnected
+        | TimedOut | UnexpectedEof => true,
+        // The source of an `io::Error` skips over the error it wraps:
+        _ => error
+            .get_ref()
+            .is_some_and(|inner| match inner.downcast_ref::<Error>() {
+                Some(inner) => inner.is_transient(),
+                None => is_transient_source(inner),
+            }),
+    }
+}
+
+/// Returns whether the error, or any error in its chain of sources, is
+/// transient.
+#[cfg(any(
+    feature = "file",
+    feature = "ftp",
+    feature = "gemini",
+    feature = "gopher",
+    feature = "http",
+    feature = "scp",
+    feature = "sftp"
+))]
+fn is_transient_source(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut next = Some(error);
+    while let Some(error) = next {
+        if let Some(error) = error.downcast_ref::<std::io::Error>() {
+            return is_transient_io(error);
+        }
+        #[cfg(any(feature = "http", feature = "https"))]
+        if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+            if error.is_timeout() {
+                return true;
+            }
+        }
+        next = error.source();
+    }
+    false
+}
+
+/// Returns whether an FTP error is transient: by definition, 4xx replies
+/// are transient negative completions.
+///
+/// See: https://www.rfc-editor.org/rfc/rfc959.html#section-4.2
+#[cfg(any(feature = "ftp", feature = "ftps"))]
+fn is_transient_ftp(error: &suppaftp::FtpError) -> bool {
+    use suppaftp::{FtpError, Status};
+    match error {
+        FtpError::ConnectionError(e) => is_transient_io(e),
+        FtpError::UnexpectedResponse(response) => {
+            (400..500).contains(&response.status.code())
+                && response.status != Status::InvalidCredentials
+        }
+        _ => false,
+    }
 }
 
 #[cfg(feature = "std")]
@@ -145,6 +737,29 @@ impl From<Error> for std::io::Error {
         match value {
             Error::InvalidUrl(e) => std::io::Error::new(ErrorKind::InvalidInput, e),
             Error::UnknownScheme(s) => std::io::Error::new(ErrorKind::InvalidInput, s),
+            e @ Error::TooLarge(_) => std::io::Error::other(e),
+            e @ Error::NotSeekable(_) => std::io::Error::new(ErrorKind::Unsupported, e),
+
+            #[cfg(feature = "decompress")]
+            Error::FailedDecompression(e) => e,
+
+            #[cfg(feature = "integrity")]
+            Error::InvalidDigest(s) => std::io::Error::new(ErrorKind::InvalidInput, s),
+
+            #[cfg(feature = "integrity")]
+            e @ Error::IntegrityMismatch { .. } => std::io::Error::new(ErrorKind::InvalidData, e),
+
+            #[cfg(feature = "archive")]
+            Error::InvalidArchiveUrl(u) => std::io::Error::new(ErrorKind::InvalidInput, u),
+
+            #[cfg(feature = "archive")]
+            Error::MissingArchiveMember(m) => std::io::Error::new(ErrorKind::NotFound, m),
+
+            #[cfg(feature = "archive")]
+            Error::FailedArchiveRead(e) => e,
+
+            #[cfg(feature = "cache")]
+            Error::FailedCacheIo(e) => e,
 
             #[cfg(feature = "data")]
             Error::InvalidDataUrl(e) => std::io::Error::new(ErrorKind::InvalidInput, e),
@@ -162,20 +777,180 @@ impl From<Error> for std::io::Error {
             Error::InvalidFtpUrl(u) => std::io::Error::new(ErrorKind::InvalidInput, u.as_str()),
 
             #[cfg(any(feature = "ftp", feature = "ftps"))]
-            Error::FailedFtpRequest(_e) => std::io::Error::from(ErrorKind::Other), // FIXME
+            Error::FailedFtpRequest(suppaftp::FtpError::ConnectionError(e)) => e,
+
+            #[cfg(any(feature = "ftp", feature = "ftps"))]
+            Error::FailedFtpRequest(e) => std::io::Error::new(ftp_error_kind(&e), e),
+
+            #[cfg(feature = "gemini")]
+            Error::InvalidGeminiUrl(u) => std::io::Error::new(ErrorKind::InvalidInput, u.as_str()),
+
+            #[cfg(feature = "gemini")]
+            Error::FailedGeminiRequest(e) => e,
+
+            #[cfg(feature = "gemini")]
+            e @ Error::InvalidGeminiResponse(_) => std::io::Error::new(ErrorKind::InvalidData, e),
+
+            #[cfg(feature = "gemini")]
+            e @ Error::GeminiInputRequired { .. } => {
+                std::io::Error::new(ErrorKind::InvalidInput, e)
+            }
+
+            #[cfg(feature = "gemini")]
+            e @ (Error::GeminiTemporaryFailure { status, .. }
+            | Error::GeminiPermanentFailure { status, .. }
+            | Error::GeminiCertificateRequired { status, .. }) => {
+                std::io::Error::new(gemini_status_kind(status), e)
+            }
+
+            #[cfg(feature = "gemini")]
+            e @ Error::TooManyGeminiRedirects(_) => std::io::Error::other(e),
+
+            #[cfg(feature = "gemini")]
+            e @ Error::UntrustedGeminiCertificate(_) => {
+                std::io::Error::new(ErrorKind::PermissionDenied, e)
+            }
+
+            #[cfg(any(feature = "http", feature = "https"))]
+            Error::FailedHttpRequest(e) => std::io::Error::new(http_error_kind(&e), e),
+
+            #[cfg(any(feature = "http", feature = "https"))]
+            e @ Error::HttpStatus { status, .. } => {
+                std::io::Error::new(http_status_kind(status), e)
+            }
+
+            #[cfg(any(feature = "http", feature = "https"))]
+            e @ Error::RangeNotSupported(_) => std::io::Error::new(ErrorKind::Unsupported, e),
 
             #[cfg(any(feature = "http", feature = "https"))]
-            Error::FailedHttpRequest(_e) => std::io::Error::from(ErrorKind::Other), // FIXME
+            e @ Error::ResourceChanged(_) => std::io::Error::new(ErrorKind::InvalidData, e),
 
             #[cfg(feature = "git")]
             Error::InvalidGitUrl(u) => std::io::Error::new(ErrorKind::InvalidInput, u.as_str()),
 
+            #[cfg(feature = "git")]
+            Error::FailedGitRequest(e) => e,
+
+            #[cfg(feature = "git")]
+            e @ Error::InvalidGitData(_) => std::io::Error::new(ErrorKind::InvalidData, e),
+
+            #[cfg(feature = "git")]
+            e @ Error::GitObjectNotFound(_) => std::io::Error::new(ErrorKind::NotFound, e),
+
+            #[cfg(feature = "gopher")]
+            Error::InvalidGopherUrl(u) => std::io::Error::new(ErrorKind::InvalidInput, u.as_str()),
+
+            #[cfg(feature = "gopher")]
+            Error::FailedGopherRequest(e) => e,
+
             #[cfg(feature = "ipfs")]
             Error::InvalidIpfsUrl(u) => std::io::Error::new(ErrorKind::InvalidInput, u.as_str()),
+
+            #[cfg(feature = "ipfs")]
+            e @ Error::IpfsVerificationFailed(_) => std::io::Error::new(ErrorKind::InvalidData, e),
+
+            #[cfg(feature = "ipfs")]
+            e @ Error::UnsupportedIpfsContent(_) => std::io::Error::new(ErrorKind::Unsupported, e),
+
+            #[cfg(feature = "ipfs")]
+            e @ Error::IpnsResolutionFailed(_) => std::io::Error::new(ErrorKind::NotFound, e),
+
+            #[cfg(feature = "ipfs")]
+            Error::FailedDnsQuery(e) => e,
+
+            #[cfg(feature = "scp")]
+            Error::InvalidScpUrl(u) => std::io::Error::new(ErrorKind::InvalidInput, u.as_str()),
+
+            #[cfg(any(feature = "scp", feature = "sftp"))]
+            Error::FailedSshConnection(e) => e,
+
+            #[cfg(any(feature = "scp", feature = "sftp"))]
+            Error::FailedSshRequest(e) => e.into(),
+
+            #[cfg(any(feature = "scp", feature = "sftp"))]
+            e @ Error::FailedSshAuth(_) => std::io::Error::new(ErrorKind::PermissionDenied, e),
+
+            #[cfg(any(feature = "scp", feature = "sftp"))]
+            e @ Error::UntrustedHostKey(_) => std::io::Error::new(ErrorKind::PermissionDenied, e),
+
+            #[cfg(feature = "sftp")]
+            Error::InvalidSftpUrl(u) => std::io::Error::new(ErrorKind::InvalidInput, u.as_str()),
+
+            #[cfg(feature = "sftp")]
+            Error::FailedSftpRequest(e) => e.into(),
         }
     }
 }
 
+#[cfg(any(feature = "scp", feature = "sftp"))]
+impl TryInto<ssh2::Error> for Error {
+    type Error = Error;
+
+    fn try_into(self) -> Result<ssh2::Error> {
+        match self {
+            Error::FailedSshRequest(e) => Ok(e),
+            #[cfg(feature = "sftp")]
+            Error::FailedSftpRequest(e) => Ok(e),
+            _ => Err(self),
+        }
+    }
+}
+
+#[cfg(all(feature = "std", any(feature = "ftp", feature = "ftps")))]
+fn ftp_error_kind(error: &suppaftp::FtpError) -> std::io::ErrorKind {
+    use std::io::ErrorKind;
+    use suppaftp::{FtpError, Status};
+    match error {
+        FtpError::ConnectionError(e) => e.kind(),
+        FtpError::UnexpectedResponse(response) => match response.status {
+            Status::FileUnavailable => ErrorKind::NotFound,
+            Status::NotLoggedIn | Status::InvalidCredentials => ErrorKind::PermissionDenied,
+            Status::NotAvailable | Status::HostUnavailable => ErrorKind::ConnectionRefused,
+            Status::TransferAborted | Status::ActionAborted => ErrorKind::ConnectionAborted,
+            _ => ErrorKind::Other,
+        },
+        FtpError::InvalidAddress(_) => ErrorKind::InvalidInput,
+        _ => ErrorKind::InvalidData,
+    }
+}
+
+#[cfg(all(feature = "std", any(feature = "http", feature = "https")))]
+fn http_error_kind(error: &reqwest::Error) -> std::io::ErrorKind {
+    use std::io::ErrorKind;
+    match error.status() {
+        Some(status) => http_status_kind(status.as_u16()),
+        None if error.is_timeout() => ErrorKind::TimedOut,
+        None if error.is_connect() => ErrorKind::ConnectionRefused,
+        None if error.is_builder() => ErrorKind::InvalidInput,
+        None if error.is_decode() => ErrorKind::InvalidData,
+        None => ErrorKind::Other,
+    }
+}
+
+#[cfg(all(feature = "std", any(feature = "http", feature = "https")))]
+fn http_status_kind(status: u16) -> std::io::ErrorKind {
+    use std::io::ErrorKind;
+    match status {
+        401 | 403 | 407 => ErrorKind::PermissionDenied,
+        404 | 410 => ErrorKind::NotFound,
+        408 | 504 => ErrorKind::TimedOut,
+        400 | 405 | 414 | 422 => ErrorKind::InvalidInput,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// See: https://geminiprotocol.net/docs/protocol-specification.gmi#status-codes
+#[cfg(all(feature = "std", feature = "gemini"))]
+fn gemini_status_kind(status: u8) -> std::io::ErrorKind {
+    use std::io::ErrorKind;
+    match status {
+        51 | 52 => ErrorKind::NotFound,
+        53 | 60..=69 => ErrorKind::PermissionDenied,
+        59 => ErrorKind::InvalidInput,
+        _ => ErrorKind::Other,
+    }
+}
+
 #[cfg(feature = "data")]
 impl TryInto<data_url::DataUrlError> for Error {
     type Error = Error;
@@ -223,3 +998,24 @@ impl TryInto<reqwest::Error> for Error {
         }
     }
 }
+
+#[cfg(all(test, feature = "std", any(feature = "http", feature = "https")))]
+mod test {
+    use super::*;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn http_status_to_io_error() {
+        let error = |status| Error::HttpStatus {
+            status,
+            url: "https://example.org/".into(),
+            body_excerpt: String::new(),
+            retry_after: None,
+        };
+        let kind = |status| std::io::Error::from(error(status)).kind();
+        assert_eq!(kind(404), ErrorKind::NotFound);
+        assert_eq!(kind(403), ErrorKind::PermissionDenied);
+        assert_eq!(kind(504), ErrorKind::TimedOut);
+        assert_eq!(kind(500), ErrorKind::Other);
+    }
+}
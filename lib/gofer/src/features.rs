@@ -2,22 +2,40 @@
 
 /// The set of features that are enabled in this build of the crate.
 pub static FEATURES: &[&str] = &[
+    #[cfg(feature = "archive")]
+    "archive",
+    #[cfg(feature = "async")]
+    "async",
+    #[cfg(feature = "cache")]
+    "cache",
     #[cfg(feature = "data")]
     "data",
+    #[cfg(feature = "decompress")]
+    "decompress",
     #[cfg(feature = "file")]
     "file",
     #[cfg(feature = "ftp")]
     "ftp",
     #[cfg(feature = "ftps")]
     "ftps",
+    #[cfg(feature = "gemini")]
+    "gemini",
     #[cfg(feature = "git")]
     "git",
+    #[cfg(feature = "gopher")]
+    "gopher",
     #[cfg(feature = "http")]
     "http",
     #[cfg(feature = "https")]
     "https",
+    #[cfg(feature = "integrity")]
+    "integrity",
+    #[cfg(feature = "ipfs")]
+    "ipfs",
     #[cfg(feature = "scp")]
     "scp",
+    #[cfg(feature = "sftp")]
+    "sftp",
     #[cfg(feature = "stdin")]
     "stdin",
     #[cfg(feature = "unstable")]
@@ -0,0 +1,61 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{OpenOptions, ReadSeek, Resource, Result, Url};
+
+/// A protocol handler capable of opening URLs of a given scheme.
+///
+/// Handlers are registered with a [`Registry`](crate::Registry) under one or
+/// more scheme names. Any function or closure with a matching signature
+/// implements this trait automatically, as long as it returns either a
+/// [`Resource`] or a bare `Box<dyn Read>`. Handlers should honor whichever
+/// [`OpenOptions`] are meaningful for their scheme and ignore the rest.
+///
+/// # Examples
+///
+/// ```edition2021
+/// # use gofer::*;
+/// let mut registry = Registry::default();
+/// registry.register("hello", |_url: &Url, _options: &OpenOptions| -> Result<Box<dyn Read>> {
+///     Ok(Box::new(Cursor::new(b"Hello, world!\n".to_vec())))
+/// });
+/// let mut output = String::new();
+/// registry.open("hello:")?.read_to_string(&mut output)?;
+/// assert_eq!(output, "Hello, world!\n");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub trait SchemeHandler: Send + Sync {
+    /// Opens the given URL for reading.
+    fn open(&self, url: &Url, options: &OpenOptions) -> Result<Resource>;
+}
+
+impl<F, R> SchemeHandler for F
+where
+    F: Fn(&Url, &OpenOptions) -> Result<R> + Send + Sync,
+    R: Into<Resource>,
+{
+    fn open(&self, url: &Url, options: &OpenOptions) -> Result<Resource> {
+        self(url, options).map(Into::into)
+    }
+}
+
+/// A protocol handler capable of opening URLs of a given scheme for random
+/// access.
+///
+/// Seekable handlers are registered with a [`Registry`](crate::Registry)
+/// alongside the sequential ones, using
+/// [`register_seekable`](crate::Registry::register_seekable). Any function or
+/// closure returning a `Box<dyn ReadSeek>` implements this trait
+/// automatically.
+pub trait SeekableHandler: Send + Sync {
+    /// Opens the given URL for random access.
+    fn open_seekable(&self, url: &Url, options: &OpenOptions) -> Result<Box<dyn ReadSeek>>;
+}
+
+impl<F> SeekableHandler for F
+where
+    F: Fn(&Url, &OpenOptions) -> Result<Box<dyn ReadSeek>> + Send + Sync,
+{
+    fn open_seekable(&self, url: &Url, options: &OpenOptions) -> Result<Box<dyn ReadSeek>> {
+        self(url, options)
+    }
+}
@@ -0,0 +1,470 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Resolution of IPNS names, which are mutable pointers to content paths.
+//!
+//! A name is either a domain name, resolved through its DNSLink record, or
+//! a public key, resolved through an IPNS record signed by that key.
+//!
+//! See: https://specs.ipfs.tech/ipns/ipns-record/
+//! See: https://dnslink.dev/
+
+use crate::{
+    car::{self, Cid, Field, ProtobufFields},
+    Error, Result,
+};
+use sha2::{Digest as _, Sha256};
+use std::time::{Duration, SystemTime};
+
+/// The maximum number of names to follow before giving up, as recommended
+/// by the DNSLink specification.
+const MAX_DEPTH: usize = 32;
+
+/// The maximum size of an IPNS record, as set by the specification.
+pub(crate) const MAX_RECORD_LEN: u64 = 10 << 10;
+
+/// The multicodec of a CID that names a public key.
+const LIBP2P_KEY: u64 = 0x72;
+
+/// The libp2p key type of an Ed25519 public key.
+const ED25519: u64 = 1;
+
+/// An IPNS name that has yet to be resolved.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Name {
+    /// A domain name with a DNSLink record.
+    Domain(String),
+    /// The hash of a public key, as a multihash.
+    Key(Key),
+}
+
+impl Name {
+    /// Parses a domain name, a CIDv1 with the `libp2p-key` codec (e.g.,
+    /// `k51…`), or a base58btc peer ID (e.g., `12D3KooW…` or `Qm…`).
+    pub(crate) fn parse(input: &str) -> Option<Self> {
+        if input.contains('.') {
+            let valid = input.split('.').all(|label| {
+                !label.is_empty()
+                    && label.len() <= 63
+                    && label
+                        .bytes()
+                        .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_')
+            });
+            return valid.then(|| Self::Domain(input.to_ascii_lowercase()));
+        }
+        let (hash, digest) = match Cid::parse(input) {
+            // A `Qm…` peer ID looks just like a CIDv0:
+            Some(cid) if cid.codec == LIBP2P_KEY || input.starts_with("Qm") => {
+                (cid.hash, cid.digest)
+            }
+            Some(_) => return None,
+            None => {
+                let multihash = car::base58_decode(input)?;
+                let mut multihash = multihash.as_slice();
+                let hash = car::read_varint(&mut multihash)?;
+                let len = usize::try_from(car::read_varint(&mut multihash)?).ok()?;
+                (multihash.len() == len).then_some((hash, multihash.to_vec()))?
+            }
+        };
+        Some(Self::Key(Key {
+            name: input.to_string(),
+            hash,
+            digest,
+        }))
+    }
+}
+
+/// The public key named by an IPNS name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Key {
+    /// The name as given, which is how it is requested from gateways.
+    pub(crate) name: String,
+    hash: u64,
+    digest: Vec<u8>,
+}
+
+impl Key {
+    /// Checks the given IPNS record against this key, returning the content
+    /// path that it points to.
+    ///
+    /// Only V2 signatures by Ed25519 keys are supported, as produced by
+    /// current IPFS implementations.
+    pub(crate) fn verify(&self, record: &[u8], now: SystemTime) -> Result<String> {
+        let (mut public_key, mut signature, mut data) = (None, None, None);
+        for field in ProtobufFields(record) {
+            match field.ok_or_else(|| invalid(format!("malformed record for {}", self.name)))? {
+                (7, Field::Bytes(bytes)) => public_key = Some(bytes),
+                (8, Field::Bytes(bytes)) => signature = Some(bytes),
+                (9, Field::Bytes(bytes)) => data = Some(bytes),
+                _ => {}
+            }
+        }
+        let (Some(signature), Some(data)) = (signature, data) else {
+            return Err(unsupported(format!(
+                "record for {} without V2 signature",
+                self.name
+            )));
+        };
+
+        // Small keys are inlined in the name, larger ones in the record:
+        let public_key = match (self.hash, public_key) {
+            (car::IDENTITY, _) => self.digest.as_slice(),
+            (car::SHA2_256, Some(key)) if Sha256::digest(key)[..] == self.digest[..] => key,
+            _ => {
+                return Err(invalid(format!(
+                    "record for {} has the wrong key",
+                    self.name
+                )))
+            }
+        };
+        let (mut key_type, mut key_data) = (None, &[][..]);
+        for field in ProtobufFields(public_key) {
+            match field.ok_or_else(|| invalid(format!("malformed key for {}", self.name)))? {
+                (1, Field::Varint(value)) => key_type = Some(value),
+                (2, Field::Bytes(bytes)) => key_data = bytes,
+                _ => {}
+            }
+        }
+        if key_type != Some(ED25519) {
+            return Err(unsupported(format!("non-Ed25519 key for {}", self.name)));
+        }
+
+        // See: https://specs.ipfs.tech/ipns/ipns-record/#record-verification
+        let mut message = b"ipns-signature:".to_vec();
+        message.extend_from_slice(data);
+        ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key_data)
+            .verify(&message, signature)
+            .map_err(|_| invalid(format!("bad signature on record for {}", self.name)))?;
+
+        let (mut value, mut validity, mut validity_type) = (None, None, None);
+        let mut input = data;
+        let malformed = || invalid(format!("malformed record data for {}", self.name));
+        let entries = match cbor::read_head(&mut input).ok_or_else(malformed)? {
+            (cbor::MAP, len) => len,
+            _ => return Err(malformed()),
+        };
+        for _ in 0..entries {
+            let key = cbor::read_string(&mut input).ok_or_else(malformed)?;
+            let item = cbor::read_item(&mut input).ok_or_else(malformed)?;
+            match (key, item) {
+                (b"Value", cbor::Item::Bytes(bytes)) => value = Some(bytes),
+                (b"Validity", cbor::Item::Bytes(bytes)) => validity = Some(bytes),
+                (b"ValidityType", cbor::Item::Int(int)) => validity_type = Some(int),
+                _ => {}
+            }
+        }
+
+        // Only expiry dates are defined as a type of validity:
+        let expires = match (validity_type, validity) {
+            (Some(0), Some(validity)) => std::str::from_utf8(validity)
+                .ok()
+                .and_then(parse_rfc3339)
+                .ok_or_else(malformed)?,
+            _ => return Err(malformed()),
+        };
+        if expires < now {
+            return Err(invalid(format!("expired record for {}", self.name)));
+        }
+        value
+            .and_then(|value| String::from_utf8(value.to_vec()).ok())
+            .ok_or_else(malformed)
+    }
+}
+
+/// Follows a chain of content paths through IPNS names to the immutable
+/// content path that the last of them points to.
+///
+/// The caller passes each path to [`step`](Self::step), starting with the
+/// one in the URL, and looks up the value of each [`name`](Self::name) in
+/// turn, until a step yields an immutable path.
+#[derive(Debug, Default)]
+pub(crate) struct Resolver {
+    name: String,
+    /// The rest of the path, below the content path of the current name.
+    path: String,
+    depth: usize,
+}
+
+impl Resolver {
+    /// Returns the name to be resolved next.
+    pub(crate) fn name(&self) -> Result<Name> {
+        if self.depth > MAX_DEPTH {
+            return Err(Error::IpnsResolutionFailed(format!(
+                "more than {} names to follow from {}",
+                MAX_DEPTH, self.name
+            )));
+        }
+        Name::parse(&self.name)
+            .ok_or_else(|| Error::IpnsResolutionFailed(format!("invalid name {}", self.name)))
+    }
+
+    /// Continues with the given content path, such as the value that the
+    /// current name points to (e.g., `/ipfs/<cid>/<path>` or
+    /// `/ipns/<name>/<path>`), returning the final `<cid>/<path>` once
+    /// there are no more names to follow.
+    pub(crate) fn step(&mut self, value: &str) -> Result<Option<String>> {
+        let invalid = || Error::IpnsResolutionFailed(format!("invalid path {}", value));
+        let value = value.trim_end_matches('/');
+        let (namespace, rest) = value
+            .strip_prefix('/')
+            .and_then(|value| value.split_once('/'))
+            .ok_or_else(invalid)?;
+        let (name, path) = rest.split_once('/').unwrap_or((rest, ""));
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        if !path.is_empty() {
+            self.path = format!("/{}{}", path, self.path);
+        }
+        match namespace {
+            "ipfs" => Ok(Some(format!("{}{}", name, self.path))),
+            "ipns" => {
+                self.name = name.to_string();
+                self.depth += 1;
+                Ok(None)
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Returns the value of the DNSLink record among the given TXT records of
+/// `_dnslink.<domain>`.
+///
+/// Where there are several, the first in lexicographic order is used.
+///
+/// See: https://dnslink.dev/#multiple-records
+pub(crate) fn dnslink(domain: &str, records: &[String]) -> Result<String> {
+    records
+        .iter()
+        .filter_map(|record| record.strip_prefix("dnslink="))
+        .filter(|value| value.starts_with("/ipfs/") || value.starts_with("/ipns/"))
+        .min()
+        .map(String::from)
+        .ok_or_else(|| Error::IpnsResolutionFailed(format!("no DNSLink record for {}", domain)))
+}
+
+/// Returns the content path in the JSON response of the Kubo RPC API's
+/// `name/resolve` command, such as `{"Path":"/ipfs/<cid>"}`.
+pub(crate) fn kubo_path(name: &str, response: &str) -> Result<String> {
+    let (_, rest) = response.split_once("\"Path\"").unwrap_or_default();
+    rest.trim_start()
+        .strip_prefix(':')
+        .map(str::trim_start)
+        .and_then(|rest| rest.strip_prefix('"'))
+        .and_then(|rest| rest.split_once('"'))
+        .map(|(path, _)| path.to_string())
+        .ok_or_else(|| Error::IpnsResolutionFailed(format!("no path for {}", name)))
+}
+
+/// Parses an RFC 3339 timestamp (e.g., `2025-01-01T00:00:00.000000000Z`).
+fn parse_rfc3339(input: &str) -> Option<SystemTime> {
+    let number = |s: &str| {
+        s.bytes()
+            .all(|c| c.is_ascii_digit())
+            .then(|| s.parse::<i64>().ok())?
+    };
+    let (date, time) = input.split_once(['T', 't'])?;
+    let mut date = date.splitn(3, '-').map(number);
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+
+    let (time, offset) = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => (time, 0),
+        None => {
+            let (time, offset) = time.split_at_checked(time.len().checked_sub(6)?)?;
+            let sign = match offset.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let (hours, minutes) = offset[1..].split_once(':')?;
+            (time, sign * (number(hours)? * 3600 + number(minutes)? * 60))
+        }
+    };
+    let (time, _fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':').map(number);
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    // The year is chosen by whoever signed the record, so the arithmetic is
+    // checked rather than allowed to overflow.
+    // See: https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era.checked_mul(146097)?.checked_add(day_of_era - 719468)?;
+
+    let seconds = days
+        .checked_mul(86400)?
+        .checked_add(hour * 3600 + minute * 60 + second)?
+        .checked_sub(offset)?;
+    match u64::try_from(seconds) {
+        Ok(seconds) => SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds)),
+        Err(_) => Some(SystemTime::UNIX_EPOCH),
+    }
+}
+
+/// Just enough DAG-CBOR to read the signed data of an IPNS record.
+///
+/// See: https://ipld.io/specs/codecs/dag-cbor/spec/
+mod cbor {
+    pub(super) const MAP: u8 = 5;
+
+    pub(super) enum Item<'a> {
+        Int(u64),
+        Bytes(&'a [u8]),
+    }
+
+    /// Reads the major type and argument of a data item.
+    pub(super) fn read_head(input: &mut &[u8]) -> Option<(u8, u64)> {
+        let (&initial, rest) = input.split_first()?;
+        *input = rest;
+        let len = match initial & 0x1f {
+            info @ 0..=23 => return Some((initial >> 5, u64::from(info))),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return None,
+        };
+        let bytes = super::car::take(input, len)?;
+        let value = bytes
+            .iter()
+            .fold(0, |value, byte| value << 8 | u64::from(*byte));
+        Some((initial >> 5, value))
+    }
+
+    /// Reads a text string, as used for map keys.
+    pub(super) fn read_string<'a>(input: &mut &'a [u8]) -> Option<&'a [u8]> {
+        match read_head(input)? {
+            (3, len) => super::car::take(input, usize::try_from(len).ok()?),
+            _ => None,
+        }
+    }
+
+    /// Reads an unsigned integer, or a byte or text string.
+    pub(super) fn read_item<'a>(input: &mut &'a [u8]) -> Option<Item<'a>> {
+        match read_head(input)? {
+            (0, value) => Some(Item::Int(value)),
+            (2 | 3, len) => super::car::take(input, usize::try_from(len).ok()?).map(Item::Bytes),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::IpfsVerificationFailed(message.into())
+}
+
+fn unsupported(message: impl Into<String>) -> Error {
+    Error::UnsupportedIpfsContent(message.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_name() {
+        assert_eq!(
+            Name::parse("Docs.IPFS.tech"),
+            Some(Name::Domain("docs.ipfs.tech".into()))
+        );
+        let Some(Name::Key(key)) =
+            Name::parse("k51qzi5uqu5dlvj2baxnqndepeb86cbk3ng7n3i46uzyxzyqj2xjonzllnv0v8")
+        else {
+            panic!("expected a key");
+        };
+        assert_eq!(key.hash, car::IDENTITY);
+        assert_eq!(key.digest.len(), 36);
+        let Some(Name::Key(key)) =
+            Name::parse("12D3KooWD3eckifWpRn9wQpMG9R9hX3sD158z7EqHWmweQAJU5SA")
+        else {
+            panic!("expected a peer ID");
+        };
+        assert_eq!(key.hash, car::IDENTITY);
+        assert_eq!(&key.digest[..4], &[0x08, 0x01, 0x12, 0x20]);
+        assert_eq!(Name::parse("example..org"), None);
+        assert_eq!(
+            Name::parse("bafybeihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"),
+            None
+        );
+    }
+
+    #[test]
+    fn follow_names() {
+        let mut resolver = Resolver::default();
+        assert_eq!(
+            resolver.step("/ipns/example.org/docs/readme").unwrap(),
+            None
+        );
+        assert!(matches!(resolver.name(), Ok(Name::Domain(_))));
+        assert_eq!(resolver.step("/ipns/example.net/v1").unwrap(), None);
+        assert_eq!(resolver.name().unwrap(), Name::Domain("example.net".into()));
+        assert_eq!(
+            resolver.step("/ipfs/bafkqaaa/").unwrap().as_deref(),
+            Some("bafkqaaa/v1/docs/readme")
+        );
+        assert!(resolver.step("/ipfs").is_err());
+        assert!(resolver.step("/ipns/").is_err());
+        assert!(resolver.step("/ipld/bafkqaaa").is_err());
+
+        let mut resolver = Resolver::default();
+        for _ in 0..MAX_DEPTH {
+            resolver.step("/ipns/example.org").unwrap();
+            resolver.name().unwrap();
+        }
+        resolver.step("/ipns/example.org").unwrap();
+        assert!(resolver.name().is_err());
+    }
+
+    #[test]
+    fn select_dnslink() {
+        let records = [
+            "v=spf1 -all".to_string(),
+            "dnslink=/ipns/example.net".to_string(),
+            "dnslink=/ipfs/bafkqaaa".to_string(),
+            "dnslink=/ipld/bafkqaaa".to_string(),
+        ];
+        assert_eq!(dnslink("example.org", &records).unwrap(), "/ipfs/bafkqaaa");
+        assert!(dnslink("example.org", &records[..1]).is_err());
+    }
+
+    #[test]
+    fn parse_kubo_response() {
+        let response = "{\"Path\": \"/ipfs/bafkqaaa\"}\n";
+        assert_eq!(kubo_path("k51", response).unwrap(), "/ipfs/bafkqaaa");
+        assert!(kubo_path("k51", "{\"Message\":\"not found\"}").is_err());
+    }
+
+    #[test]
+    fn parse_timestamps() {
+        let time = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(parse_rfc3339("1970-01-01T00:00:00Z"), Some(time(0)));
+        assert_eq!(
+            parse_rfc3339("2025-03-01T12:30:15.123456789Z"),
+            Some(time(1740832215))
+        );
+        assert_eq!(
+            parse_rfc3339("2025-03-01T14:30:15+02:00"),
+            Some(time(1740832215))
+        );
+        assert_eq!(parse_rfc3339("2025-13-01T00:00:00Z"), None);
+        assert_eq!(parse_rfc3339("yesterday"), None);
+        assert_eq!(parse_rfc3339("2025-03-01T12:30:61Z"), None);
+        assert_eq!(parse_rfc3339("9223372036854775807-03-01T00:00:00Z"), None);
+        assert_eq!(parse_rfc3339("25000000000000000-03-01T00:00:00Z"), None);
+    }
+}
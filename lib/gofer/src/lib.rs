@@ -19,18 +19,100 @@ pub use dogma::Uri as Url;
 pub use dogma::UriError as UrlError;
 pub use dogma::UriScheme as UrlScheme;
 
+#[cfg(feature = "async")]
+pub mod r#async;
+
 mod features;
 pub use features::*;
 
+#[cfg(feature = "cache")]
+mod cache;
+#[cfg(feature = "cache")]
+pub use cache::*;
+
+#[cfg(feature = "ipfs")]
+mod car;
+
+mod client;
+pub use client::*;
+
+#[cfg(feature = "ipfs")]
+mod dns;
+
+#[cfg(feature = "decompress")]
+mod compression;
+#[cfg(feature = "decompress")]
+pub use compression::*;
+
+#[cfg(feature = "integrity")]
+mod digest;
+#[cfg(feature = "integrity")]
+pub use digest::*;
+
 mod error;
 pub use error::*;
 
+mod handler;
+pub use handler::*;
+
+#[cfg(feature = "ipfs")]
+mod ipns;
+
+#[cfg(any(
+    feature = "ftp",
+    feature = "gemini",
+    feature = "gopher",
+    feature = "scp",
+    feature = "sftp"
+))]
+mod net;
+
 mod open;
 pub use open::*;
 
+mod options;
+pub use options::*;
+
+#[cfg(feature = "git")]
+mod pack;
+
+mod readers;
+
+mod registry;
+pub use registry::*;
+
+#[cfg(feature = "git")]
+mod repository;
+
+mod resource;
+pub use resource::*;
+
+mod retry;
+pub use retry::*;
+
 mod schemes;
+#[cfg(any(
+    feature = "archive",
+    feature = "data",
+    feature = "file",
+    feature = "ftp",
+    feature = "gemini",
+    feature = "git",
+    feature = "gopher",
+    feature = "http",
+    feature = "ipfs",
+    feature = "scp",
+    feature = "sftp",
+    feature = "stdin"
+))]
 pub use schemes::*;
 
+mod seek;
+pub use seek::*;
+
+#[cfg(feature = "git")]
+mod upload_pack;
+
 #[doc = include_str!("../../../README.md")]
 #[cfg(doctest)]
 pub struct ReadmeDoctests;
@@ -0,0 +1,21 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::OpenOptions;
+use std::net::{TcpStream, ToSocketAddrs};
+
+/// Connects to the first of the host's addresses that accepts, within the
+/// connect timeout for each, if any.
+pub(crate) fn connect(host: &str, port: u16, options: &OpenOptions) -> std::io::Result<TcpStream> {
+    let mut last_error = None;
+    for address in (host, port).to_socket_addrs()? {
+        let stream = match options.get_connect_timeout() {
+            Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+            None => TcpStream::connect(address),
+        };
+        match stream {
+            Ok(stream) => return Ok(stream),
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| std::io::ErrorKind::NotFound.into()))
+}
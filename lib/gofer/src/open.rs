@@ -1,48 +1,72 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{Error, Read, Result, Url, UrlScheme};
+use crate::{
+    default_registry, readers::SizeLimit, Client, Error, OpenOptions, Read, Resource, Result,
+    SchemeHandler, Url,
+};
 
+/// Opens the given URL for reading, using the [`global`](Client::global)
+/// client.
 pub fn open(url: impl AsRef<str>) -> Result<Box<dyn Read>> {
-    let url = url.as_ref().parse::<Url>()?;
-
-    // TODO: look up the scheme in a protocol handler registry
-
-    match url.scheme() {
-        #[cfg(feature = "data")]
-        UrlScheme::Data => crate::schemes::data::open(&url),
-
-        #[cfg(feature = "file")]
-        UrlScheme::File => crate::schemes::file::open(&url),
-
-        #[cfg(feature = "ftp")]
-        UrlScheme::Ftp => crate::schemes::ftp::open(&url, false),
+    Client::global().open(url)
+}
 
-        #[cfg(feature = "ftps")]
-        UrlScheme::Ftps => crate::schemes::ftp::open(&url, true),
+/// Opens the given URL, returning the reader together with the metadata
+/// reported by the protocol handler.
+pub fn open_with_metadata(url: impl AsRef<str>) -> Result<Resource> {
+    Client::global().open_with_metadata(url)
+}
 
-        #[cfg(feature = "git")]
-        UrlScheme::Git => crate::schemes::git::open(&url),
+#[cfg(feature = "std")]
+pub fn open_buffered(url: impl AsRef<str>) -> Result<std::io::BufReader<Box<dyn Read>>> {
+    Ok(std::io::BufReader::new(open(url)?))
+}
 
-        #[cfg(feature = "http")]
-        UrlScheme::Http => crate::schemes::http::open(&url, false),
+pub(crate) fn open_url_with(url: &Url, options: &OpenOptions) -> Result<Resource> {
+    // The registry lock is released before opening, as handlers may block:
+    let handler = default_registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(url.scheme_str())
+        .ok_or_else(|| Error::UnknownScheme(url.scheme_str().to_string()))?;
 
-        #[cfg(feature = "https")]
-        UrlScheme::Https => crate::schemes::http::open(&url, true),
+    open_url_using(handler.as_ref(), url, options)
+}
 
-        #[cfg(feature = "ipfs")]
-        UrlScheme::Ipfs => crate::schemes::ipfs::open(&url),
+pub(crate) fn open_url_using(
+    handler: &dyn SchemeHandler,
+    url: &Url,
+    options: &OpenOptions,
+) -> Result<Resource> {
+    #[cfg(feature = "integrity")]
+    let expected_digest = match options.get_expected_digest() {
+        Some(digest) => Some(digest.clone()),
+        None => url
+            .fragment_str()
+            .and_then(crate::Digest::from_fragment)
+            .transpose()?,
+    };
 
-        #[cfg(feature = "scp")]
-        UrlScheme::Scp => crate::schemes::scp::open(&url),
+    let resource = handler.open(url, options)?;
 
-        #[cfg(feature = "stdin")]
-        UrlScheme::Stdin => crate::schemes::stdin::open(&url),
+    #[cfg(feature = "integrity")]
+    let resource = match expected_digest {
+        Some(digest) => {
+            resource.map_reader(|reader| Box::new(crate::readers::DigestCheck::new(reader, digest)))
+        }
+        None => resource,
+    };
 
-        _ => Err(Error::UnknownScheme(url.scheme_str().to_string())),
-    }
-}
+    // Decompression follows the integrity check, since digests are taken of
+    // the raw bytes, and precedes the size limit, so as to bound the output:
+    #[cfg(feature = "decompress")]
+    let resource = match options.get_decompress() {
+        true => crate::compression::decompress(resource, url)?,
+        false => resource,
+    };
 
-#[cfg(feature = "std")]
-pub fn open_buffered(url: impl AsRef<str>) -> Result<std::io::BufReader<Box<dyn Read>>> {
-    Ok(std::io::BufReader::new(open(url)?))
+    Ok(match options.get_max_size() {
+        Some(limit) => resource.map_reader(|reader| Box::new(SizeLimit::new(reader, limit))),
+        None => resource,
+    })
 }
@@ -0,0 +1,606 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{
+    open_seekable_url_with, open_url_with, ClientState, Read, ReadSeek, Resource, Result,
+    RetryPolicy, Url,
+};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// The default `User-Agent` sent by network protocol handlers.
+pub static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// The public IPFS gateway used to resolve `ipfs:` URLs by default.
+pub static IPFS_GATEWAY: &str = "https://ipfs.io";
+
+/// The usual address of the RPC API of a local Kubo node.
+///
+/// Its content is trusted rather than verified, so it is only used when
+/// configured as a gateway, such as with `IPFS_GATEWAY=http://127.0.0.1:5001/api/v0`.
+pub static IPFS_API: &str = "http://127.0.0.1:5001/api/v0";
+
+/// The default maximum number of redirects to follow.
+pub const MAX_REDIRECTS: usize = 10;
+
+/// The default maximum number of times to resume an interrupted download.
+pub const MAX_RESUME_ATTEMPTS: u32 = 3;
+
+/// Options and flags which can be used to configure how a URL is opened.
+///
+/// Modelled on [`std::fs::OpenOptions`], this builder is passed through to
+/// every protocol handler. Handlers ignore any options that have no
+/// meaning for their scheme.
+///
+/// Opening URLs directly from options does not reuse connections; to do
+/// so, create a [`Client`](crate::Client) with the options instead.
+///
+/// # Examples
+///
+/// ```edition2021,no_run
+/// # use gofer::*;
+/// # use std::time::Duration;
+/// let result = OpenOptions::new()
+///     .user_agent("my-app/1.0")
+///     .connect_timeout(Duration::from_secs(5))
+///     .max_redirects(3)
+///     .header("Accept", "text/plain")
+///     .open("https://www.google.com/robots.txt");
+/// ```
+#[derive(Clone, Debug)]
+pub struct OpenOptions {
+    user_agent: String,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    max_redirects: usize,
+    max_resume_attempts: u32,
+    retry: RetryPolicy,
+    headers: Vec<(String, String)>,
+    max_size: Option<u64>,
+    error_for_status: bool,
+    ipfs_gateways: Vec<String>,
+    ipfs_trust_gateway: bool,
+    dns_server: Option<SocketAddr>,
+    ftp_passive: bool,
+    ftp_resume_offset: u64,
+    root_certificates: Vec<Vec<u8>>,
+    tls_built_in_root_certs: bool,
+    ssh_identity: Option<PathBuf>,
+    ssh_known_hosts: Option<PathBuf>,
+    gemini_known_hosts: Option<PathBuf>,
+    #[cfg(feature = "git")]
+    git_forges: Vec<(String, crate::schemes::git::GitForge)>,
+    #[cfg(feature = "git")]
+    git_tokens: Vec<(String, Redacted)>,
+    #[cfg(feature = "git")]
+    git_credential_helper: bool,
+    #[cfg(feature = "git")]
+    git_config: Vec<(String, String)>,
+    #[cfg(feature = "git")]
+    same_origin_redirects: bool,
+    #[cfg(feature = "cache")]
+    cache: Option<crate::Cache>,
+    #[cfg(feature = "decompress")]
+    decompress: bool,
+    #[cfg(feature = "integrity")]
+    expected_digest: Option<crate::Digest>,
+    pub(crate) state: Option<Arc<ClientState>>,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self {
+            user_agent: USER_AGENT.into(),
+            connect_timeout: None,
+            read_timeout: None,
+            max_redirects: MAX_REDIRECTS,
+            max_resume_attempts: MAX_RESUME_ATTEMPTS,
+            retry: RetryPolicy::default(),
+            headers: Vec::new(),
+            max_size: None,
+            error_for_status: true,
+            ipfs_gateways: Vec::new(),
+            ipfs_trust_gateway: false,
+            dns_server: None,
+            ftp_passive: true,
+            ftp_resume_offset: 0,
+            root_certificates: Vec::new(),
+            tls_built_in_root_certs: true,
+            ssh_identity: None,
+            ssh_known_hosts: None,
+            gemini_known_hosts: None,
+            #[cfg(feature = "git")]
+            git_forges: Vec::new(),
+            #[cfg(feature = "git")]
+            git_tokens: Vec::new(),
+            #[cfg(feature = "git")]
+            git_credential_helper: false,
+            #[cfg(feature = "git")]
+            git_config: Vec::new(),
+            #[cfg(feature = "git")]
+            same_origin_redirects: false,
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "decompress")]
+            decompress: true,
+            #[cfg(feature = "integrity")]
+            expected_digest: None,
+            state: None,
+        }
+    }
+}
+
+impl OpenOptions {
+    /// Creates a blank new set of options ready for configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the `User-Agent` sent by network protocol handlers.
+    pub fn user_agent(&mut self, user_agent: impl Into<String>) -> &mut Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Sets the timeout for establishing a connection.
+    pub fn connect_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.connect_timeout = timeout.into();
+        self
+    }
+
+    /// Sets the timeout for each read operation on an open connection.
+    pub fn read_timeout(&mut self, timeout: impl Into<Option<Duration>>) -> &mut Self {
+        self.read_timeout = timeout.into();
+        self
+    }
+
+    /// Sets the maximum number of redirects to follow, or zero to follow
+    /// none.
+    pub fn max_redirects(&mut self, max_redirects: usize) -> &mut Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Sets the maximum number of times an HTTP or FTP download is resumed
+    /// from where it left off after the connection drops, or zero to fail
+    /// on the first connection error.
+    ///
+    /// HTTP downloads can only be resumed if the server sent an `ETag` or
+    /// `Last-Modified` header, so that the rest of the same version of the
+    /// resource can be requested with `Range` and `If-Range`. FTP downloads
+    /// are resumed with `REST`.
+    pub fn max_resume_attempts(&mut self, attempts: u32) -> &mut Self {
+        self.max_resume_attempts = attempts;
+        self
+    }
+
+    /// Sets the policy for retrying requests that fail with a
+    /// [transient](crate::Error::is_transient) error.
+    ///
+    /// Defaults to [`RetryPolicy::default`]. Use [`RetryPolicy::none`] to
+    /// fail on the first error.
+    pub fn retry(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Adds an extra header to send with requests.
+    pub fn header(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the maximum number of bytes that may be read from a resource.
+    ///
+    /// Reading past the limit fails with [`Error::TooLarge`](crate::Error::TooLarge).
+    /// This also limits the packs received from git servers for `git:`
+    /// URLs, and the objects unpacked from them into memory, which are
+    /// otherwise limited to 1 GiB.
+    pub fn max_size(&mut self, max_size: impl Into<Option<u64>>) -> &mut Self {
+        self.max_size = max_size.into();
+        self
+    }
+
+    /// Sets whether HTTP error statuses (4xx and 5xx) fail with
+    /// [`Error::HttpStatus`](crate::Error::HttpStatus).
+    ///
+    /// This is enabled by default. When disabled, the body of the error
+    /// response is returned as if the request had succeeded.
+    pub fn error_for_status(&mut self, error_for_status: bool) -> &mut Self {
+        self.error_for_status = error_for_status;
+        self
+    }
+
+    /// Sets the gateway used to resolve `ipfs:` and `ipns:` URLs, replacing
+    /// any others.
+    ///
+    /// See [`ipfs_gateways`](Self::ipfs_gateways) for details.
+    pub fn ipfs_gateway(&mut self, gateway: impl Into<String>) -> &mut Self {
+        self.ipfs_gateways = vec![gateway.into()];
+        self
+    }
+
+    /// Sets the gateways used to resolve `ipfs:` and `ipns:` URLs, which are
+    /// tried in order until one of them succeeds.
+    ///
+    /// A gateway URL ending in `/api/v0` denotes the RPC API of a Kubo node,
+    /// such as [`IPFS_API`], whose content is trusted, and which is therefore
+    /// only used when configured. Any other gateway is asked for a CAR,
+    /// whose blocks are verified against the requested CID.
+    ///
+    /// Defaults to the whitespace- or comma-separated list in
+    /// `$IPFS_GATEWAY` if set, otherwise to [`IPFS_GATEWAY`].
+    pub fn ipfs_gateways(
+        &mut self,
+        gateways: impl IntoIterator<Item = impl Into<String>>,
+    ) -> &mut Self {
+        self.ipfs_gateways = gateways.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether `ipfs:` and `ipns:` URLs may be opened for random access,
+    /// trusting the gateways to return the requested content.
+    ///
+    /// This is disabled by default, since the ranges of a file can't be
+    /// verified against its CID, and [`open_seekable`](Self::open_seekable)
+    /// then fails with [`Error::NotSeekable`](crate::Error::NotSeekable).
+    pub fn ipfs_trust_gateway(&mut self, trust: bool) -> &mut Self {
+        self.ipfs_trust_gateway = trust;
+        self
+    }
+
+    /// Sets the DNS server used to look up the DNSLink records of `ipns:`
+    /// URLs with domain names.
+    ///
+    /// Defaults to the nameservers listed in `/etc/resolv.conf`.
+    pub fn dns_server(&mut self, server: impl Into<Option<SocketAddr>>) -> &mut Self {
+        self.dns_server = server.into();
+        self
+    }
+
+    /// Sets whether FTP data connections use passive mode.
+    ///
+    /// This is enabled by default. When disabled, active mode is used, in
+    /// which the server connects back to the client.
+    pub fn ftp_passive(&mut self, passive: bool) -> &mut Self {
+        self.ftp_passive = passive;
+        self
+    }
+
+    /// Sets the byte offset at which to resume FTP downloads, using the
+    /// `REST` command.
+    pub fn ftp_resume_offset(&mut self, offset: u64) -> &mut Self {
+        self.ftp_resume_offset = offset;
+        self
+    }
+
+    /// Adds PEM-encoded root certificates to trust for TLS connections.
+    ///
+    /// This applies to HTTPS as well as FTPS, and is useful for internal
+    /// servers with certificates issued by a private CA.
+    pub fn add_root_certificate(&mut self, pem: impl Into<Vec<u8>>) -> &mut Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Sets whether to trust the platform's native root certificates.
+    ///
+    /// This is enabled by default. Disable it to pin TLS connections to
+    /// only the root certificates added with
+    /// [`add_root_certificate`](Self::add_root_certificate).
+    pub fn tls_built_in_root_certs(&mut self, enabled: bool) -> &mut Self {
+        self.tls_built_in_root_certs = enabled;
+        self
+    }
+
+    /// Sets the private key file used for SSH public key authentication.
+    ///
+    /// By default, the SSH agent is tried first, followed by the standard
+    /// identity files in `~/.ssh`.
+    pub fn ssh_identity(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.ssh_identity = Some(path.into());
+        self
+    }
+
+    /// Sets the known hosts file used to verify SSH host keys.
+    ///
+    /// Defaults to `~/.ssh/known_hosts`.
+    pub fn ssh_known_hosts(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.ssh_known_hosts = Some(path.into());
+        self
+    }
+
+    /// Sets the known hosts file used to pin the certificates of Gemini
+    /// servers on first use.
+    ///
+    /// Defaults to `gofer/gemini_known_hosts` in the user's data directory
+    /// (e.g., `~/.local/share/gofer/gemini_known_hosts`).
+    pub fn gemini_known_hosts(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.gemini_known_hosts = Some(path.into());
+        self
+    }
+
+    /// Sets the forge hosting the repositories on the given host, so that
+    /// `git:` URLs for a self-hosted forge can be mapped to raw file URLs.
+    ///
+    /// The forge is either one of the known kinds, such as
+    /// [`GitForge::GitLab`](crate::git::GitForge::GitLab), a raw file URL
+    /// template (see [`GitForge::Custom`](crate::git::GitForge::Custom)), or
+    /// a git server (see [`GitForge::Remote`](crate::git::GitForge::Remote)).
+    /// This takes precedence over the forges known for public hosts, such
+    /// as `github.com`.
+    #[cfg(feature = "git")]
+    pub fn git_forge(
+        &mut self,
+        host: impl Into<String>,
+        forge: impl Into<crate::schemes::git::GitForge>,
+    ) -> &mut Self {
+        let host = host.into().to_ascii_lowercase();
+        self.git_forges.retain(|(known, _)| *known != host);
+        self.git_forges.push((host, forge.into()));
+        self
+    }
+
+    /// Sets the token used to access private repositories on the given
+    /// host in `git:` URLs.
+    ///
+    /// The token is sent in the header that the host's forge expects, such
+    /// as `PRIVATE-TOKEN` for GitLab. This takes precedence over
+    /// `$GITHUB_TOKEN`, `$GITLAB_TOKEN`, and the git credential helper.
+    #[cfg(feature = "git")]
+    pub fn git_token(&mut self, host: impl Into<String>, token: impl Into<String>) -> &mut Self {
+        let host = host.into().to_ascii_lowercase();
+        self.git_tokens.retain(|(known, _)| *known != host);
+        self.git_tokens.push((host, Redacted(token.into())));
+        self
+    }
+
+    /// Sets whether the git credential helper is asked for the credentials
+    /// of hosts in `git:` URLs that have no configured token.
+    ///
+    /// This is disabled by default. The helper is run as `git credential
+    /// fill`, never prompts for credentials, and is only asked for hosts
+    /// reached over HTTPS.
+    #[cfg(feature = "git")]
+    pub fn git_credential_helper(&mut self, enabled: bool) -> &mut Self {
+        self.git_credential_helper = enabled;
+        self
+    }
+
+    /// Adds a configuration value for the `git` commands run for `git:`
+    /// URLs, as if given with `git -c key=value`.
+    ///
+    /// Values are added in order, so an empty `credential.helper` followed
+    /// by another one replaces the helpers otherwise configured.
+    #[cfg(feature = "git")]
+    pub fn git_config(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.git_config.push((key.into(), value.into()));
+        self
+    }
+
+    /// Sets whether redirects are only followed within the origin of the
+    /// request, as for requests carrying credentials in custom headers
+    /// that would otherwise be sent on to other origins.
+    #[cfg(feature = "git")]
+    pub(crate) fn same_origin_redirects(&mut self, enabled: bool) -> &mut Self {
+        self.same_origin_redirects = enabled;
+        self
+    }
+
+    /// Sets the cache used for HTTP responses, or `None` to bypass it.
+    ///
+    /// Defaults to `None`. Use [`Cache::default`](crate::Cache::default) for
+    /// a cache in [`Cache::default_dir`](crate::Cache::default_dir).
+    #[cfg(feature = "cache")]
+    pub fn cache(&mut self, cache: impl Into<Option<crate::Cache>>) -> &mut Self {
+        self.cache = cache.into();
+        self
+    }
+
+    /// Sets whether compressed resources are transparently decompressed.
+    ///
+    /// This is enabled by default. Disable it to read the raw bytes. See
+    /// [`Compression`](crate::Compression) for how compression is detected.
+    #[cfg(feature = "decompress")]
+    pub fn decompress(&mut self, decompress: bool) -> &mut Self {
+        self.decompress = decompress;
+        self
+    }
+
+    /// Sets the digest that the contents are expected to have.
+    ///
+    /// Reaching EOF fails with
+    /// [`Error::IntegrityMismatch`](crate::Error::IntegrityMismatch) if the
+    /// digest differs. This takes precedence over any digest given in the
+    /// URL fragment (e.g., `#sha256=…`).
+    #[cfg(feature = "integrity")]
+    pub fn expect_digest(&mut self, digest: impl Into<Option<crate::Digest>>) -> &mut Self {
+        self.expected_digest = digest.into();
+        self
+    }
+
+    /// Opens the given URL with the options specified by `self`, using the
+    /// [`default_registry`](crate::default_registry).
+    pub fn open(&self, url: impl AsRef<str>) -> Result<Box<dyn Read>> {
+        self.open_with_metadata(url).map(Resource::into_reader)
+    }
+
+    /// Opens the given URL with the options specified by `self`, returning
+    /// the reader together with the metadata reported by the handler.
+    pub fn open_with_metadata(&self, url: impl AsRef<str>) -> Result<Resource> {
+        let url = url.as_ref().parse::<Url>()?;
+        open_url_with(&url, self)
+    }
+
+    /// Opens the given URL for random access with the options specified by
+    /// `self`.
+    ///
+    /// Contents are read as stored, so neither decompression, integrity
+    /// checks, nor the maximum size apply.
+    pub fn open_seekable(&self, url: impl AsRef<str>) -> Result<Box<dyn ReadSeek>> {
+        let url = url.as_ref().parse::<Url>()?;
+        open_seekable_url_with(&url, self)
+    }
+
+    pub fn get_user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    pub fn get_connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
+
+    pub fn get_read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    pub fn get_max_redirects(&self) -> usize {
+        self.max_redirects
+    }
+
+    pub fn get_max_resume_attempts(&self) -> u32 {
+        self.max_resume_attempts
+    }
+
+    pub fn get_retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    pub fn get_headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn get_max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+
+    pub fn get_error_for_status(&self) -> bool {
+        self.error_for_status
+    }
+
+    /// Returns the configured IPFS gateways, or the defaults if none were
+    /// configured.
+    pub fn get_ipfs_gateways(&self) -> Vec<String> {
+        if !self.ipfs_gateways.is_empty() {
+            return self.ipfs_gateways.clone();
+        }
+        let from_env: Vec<String> = std::env::var("IPFS_GATEWAY")
+            .unwrap_or_default()
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|gateway| !gateway.is_empty())
+            .map(String::from)
+            .collect();
+        match from_env.is_empty() {
+            true => vec![IPFS_GATEWAY.into()],
+            false => from_env,
+        }
+    }
+
+    pub fn get_ipfs_trust_gateway(&self) -> bool {
+        self.ipfs_trust_gateway
+    }
+
+    pub fn get_dns_server(&self) -> Option<SocketAddr> {
+        self.dns_server
+    }
+
+    pub fn get_ftp_passive(&self) -> bool {
+        self.ftp_passive
+    }
+
+    pub fn get_ftp_resume_offset(&self) -> u64 {
+        self.ftp_resume_offset
+    }
+
+    pub fn get_root_certificates(&self) -> impl Iterator<Item = &[u8]> {
+        self.root_certificates.iter().map(Vec::as_slice)
+    }
+
+    pub fn get_tls_built_in_root_certs(&self) -> bool {
+        self.tls_built_in_root_certs
+    }
+
+    pub fn get_ssh_identity(&self) -> Option<&Path> {
+        self.ssh_identity.as_deref()
+    }
+
+    pub fn get_ssh_known_hosts(&self) -> Option<&Path> {
+        self.ssh_known_hosts.as_deref()
+    }
+
+    pub fn get_gemini_known_hosts(&self) -> Option<&Path> {
+        self.gemini_known_hosts.as_deref()
+    }
+
+    /// Returns the forge configured for the given host, if any.
+    #[cfg(feature = "git")]
+    pub fn get_git_forge(&self, host: &str) -> Option<crate::schemes::git::GitForge> {
+        self.git_forges
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(host))
+            .map(|(_, forge)| forge.clone())
+    }
+
+    /// Returns the token configured for the given host, if any.
+    #[cfg(feature = "git")]
+    pub fn get_git_token(&self, host: &str) -> Option<&str> {
+        self.git_tokens
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(host))
+            .map(|(_, token)| token.0.as_str())
+    }
+
+    #[cfg(feature = "git")]
+    pub fn get_git_credential_helper(&self) -> bool {
+        self.git_credential_helper
+    }
+
+    #[cfg(feature = "git")]
+    pub fn get_git_config(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.git_config
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    #[cfg(feature = "git")]
+    pub(crate) fn get_same_origin_redirects(&self) -> bool {
+        self.same_origin_redirects
+    }
+
+    #[cfg(feature = "cache")]
+    pub fn get_cache(&self) -> Option<&crate::Cache> {
+        self.cache.as_ref()
+    }
+
+    #[cfg(feature = "decompress")]
+    pub fn get_decompress(&self) -> bool {
+        self.decompress
+    }
+
+    #[cfg(feature = "integrity")]
+    pub fn get_expected_digest(&self) -> Option<&crate::Digest> {
+        self.expected_digest.as_ref()
+    }
+
+    /// Returns the state of the [`Client`](crate::Client) these options
+    /// belong to, if any.
+    #[cfg(feature = "http")]
+    pub(crate) fn get_state(&self) -> Option<&ClientState> {
+        self.state.as_deref()
+    }
+}
+
+/// A secret, such as a token, which is kept out of debug output.
+#[cfg(feature = "git")]
+#[derive(Clone)]
+struct Redacted(String);
+
+#[cfg(feature = "git")]
+impl core::fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
@@ -0,0 +1,660 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Git objects, and the packfiles that carry them.
+//!
+//! See: https://git-scm.com/book/en/v2/Git-Internals-Git-Objects
+//! See: https://git-scm.com/docs/gitformat-pack
+
+use crate::{Error, Result};
+use flate2::{Decompress, FlushDecompress, Status};
+use std::collections::HashMap;
+
+/// The most memory reserved up front for an object, whatever size its
+/// header claims.
+const MAX_PREALLOC: usize = 1 << 20;
+
+/// The deepest chain of annotated tags followed to reach a commit.
+const MAX_TAG_DEPTH: usize = 16;
+
+const OFS_DELTA: u8 = 6;
+const REF_DELTA: u8 = 7;
+
+/// A SHA-1 object ID.
+pub(crate) type Oid = [u8; 20];
+
+/// Parses a hexadecimal object ID.
+pub(crate) fn parse_oid(hex: &str) -> Option<Oid> {
+    if hex.len() != 40 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut oid = [0; 20];
+    for (byte, pair) in oid.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(oid)
+}
+
+/// Formats an object ID as hexadecimal.
+pub(crate) fn to_hex(oid: &Oid) -> String {
+    oid.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Kind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl Kind {
+    fn from_type(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Kind::Commit),
+            2 => Some(Kind::Tree),
+            3 => Some(Kind::Blob),
+            4 => Some(Kind::Tag),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"commit" => Some(Kind::Commit),
+            b"tree" => Some(Kind::Tree),
+            b"blob" => Some(Kind::Blob),
+            b"tag" => Some(Kind::Tag),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Commit => "commit",
+            Kind::Tree => "tree",
+            Kind::Blob => "blob",
+            Kind::Tag => "tag",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Object {
+    pub(crate) kind: Kind,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Object {
+    /// Returns the ID of this object, the SHA-1 digest of its header and
+    /// data.
+    pub(crate) fn oid(&self) -> Oid {
+        let mut context = ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY);
+        context.update(format!("{} {}\0", self.kind.name(), self.data.len()).as_bytes());
+        context.update(&self.data);
+        context
+            .finish()
+            .as_ref()
+            .try_into()
+            .expect("SHA-1 digests are 20 bytes")
+    }
+
+    /// Returns the object ID in the given header field of a commit or tag.
+    fn field(&self, name: &str) -> Option<Oid> {
+        let header = self.data.split(|&byte| byte == b'\n');
+        header.take_while(|line| !line.is_empty()).find_map(|line| {
+            let value = line.strip_prefix(name.as_bytes())?.strip_prefix(b" ")?;
+            parse_oid(std::str::from_utf8(value).ok()?)
+        })
+    }
+
+    /// Returns the entry with the given name in a tree, as its mode and
+    /// object ID.
+    fn entry(&self, name: &str) -> Option<(&[u8], Oid)> {
+        let mut input = &self.data[..];
+        while !input.is_empty() {
+            let space = input.iter().position(|&byte| byte == b' ')?;
+            let (mode, rest) = input.split_at(space);
+            let nul = rest.iter().position(|&byte| byte == 0)?;
+            let (entry, rest) = rest[1..].split_at(nul - 1);
+            let (oid, rest) = rest[1..].split_at_checked(20)?;
+            if entry == name.as_bytes() {
+                return Some((mode, oid.try_into().ok()?));
+            }
+            input = rest;
+        }
+        None
+    }
+}
+
+/// Finds the blob at the given path in the tree of a commit, or of the
+/// commit an annotated tag points to, looking up each object on the way.
+///
+/// Returns the ID of the blob, which is not looked up itself.
+pub(crate) fn find_blob(
+    start: &Oid,
+    path: &str,
+    mut lookup: impl FnMut(&Oid) -> Result<Object>,
+) -> Result<Oid> {
+    let not_found = || Error::GitObjectNotFound(path.to_string());
+    let invalid = |message: &str| Error::InvalidGitData(message.to_string());
+
+    let mut object = lookup(start)?;
+    for _ in 0..MAX_TAG_DEPTH {
+        if object.kind != Kind::Tag {
+            break;
+        }
+        let target = object
+            .field("object")
+            .ok_or_else(|| invalid("tag has no object"))?;
+        object = lookup(&target)?;
+    }
+    if object.kind != Kind::Commit {
+        return Err(Error::GitObjectNotFound(format!(
+            "{} is not a commit",
+            to_hex(start)
+        )));
+    }
+    let mut oid = object
+        .field("tree")
+        .ok_or_else(|| invalid("commit has no tree"))?;
+
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    let mut segment = segments.next().ok_or_else(not_found)?;
+    loop {
+        let tree = lookup(&oid)?;
+        if tree.kind != Kind::Tree {
+            return Err(not_found());
+        }
+        let (mode, entry) = tree.entry(segment).ok_or_else(not_found)?;
+        oid = entry;
+        match (segments.next(), mode) {
+            // Directories have the mode `40000`:
+            (Some(next), b"40000") => segment = next,
+            (Some(_), _) | (None, b"40000") => return Err(not_found()),
+            // Submodules are commits in another repository:
+            (None, b"160000") => return Err(not_found()),
+            (None, _) => return Ok(oid),
+        }
+    }
+}
+
+/// Parses a packfile as it arrives, keeping the objects it holds by their
+/// IDs, so that reading it can stop as soon as the wanted objects are in.
+///
+/// Deltas are resolved against the objects in the same packfile, so thin
+/// packs are not supported. As every object is held inflated in memory,
+/// their total size is limited, along with the size of each one.
+pub(crate) struct Unpacker {
+    /// The data not yet parsed, which starts at an entry.
+    buffer: Vec<u8>,
+    /// The offset in the packfile of the start of the buffer.
+    offset: usize,
+    /// The length the buffer must reach before parsing is tried again, so
+    /// that an entry that is still arriving isn't inflated over and over.
+    wanted: usize,
+    /// The number of entries left, once the header is parsed.
+    remaining: Option<u32>,
+    /// The most object data held, whatever its compressed size.
+    max_len: usize,
+    /// The size of the objects and deltas held so far.
+    len: usize,
+    digest: ring::digest::Context,
+    objects: HashMap<Oid, Object>,
+    offsets: HashMap<usize, Oid>,
+    pending: Vec<(usize, Oid, Vec<u8>)>,
+}
+
+impl Unpacker {
+    pub(crate) fn new(max_len: usize) -> Self {
+        Self {
+            buffer: Vec::new(),
+            offset: 0,
+            wanted: 0,
+            remaining: None,
+            max_len,
+            len: 0,
+            digest: ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY),
+            objects: HashMap::new(),
+            offsets: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns the objects parsed so far.
+    pub(crate) fn objects(&self) -> &HashMap<Oid, Object> {
+        &self.objects
+    }
+
+    /// Adds the next part of the packfile, parsing the entries it completes.
+    pub(crate) fn push(&mut self, data: &[u8]) -> Result<()> {
+        let invalid = |message: &str| Error::InvalidGitData(message.to_string());
+
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() < self.wanted {
+            return Ok(());
+        }
+        let mut input = &self.buffer[..];
+        let remaining = match self.remaining {
+            Some(remaining) => remaining,
+            None if input.len() < 12 => return Ok(()),
+            None => {
+                if !input.starts_with(b"PACK") {
+                    return Err(invalid("malformed packfile"));
+                }
+                let version = u32::from_be_bytes(input[4..8].try_into().unwrap());
+                if !matches!(version, 2 | 3) {
+                    return Err(invalid("unsupported packfile version"));
+                }
+                let count = u32::from_be_bytes(input[8..12].try_into().unwrap());
+                input = &input[12..];
+                count
+            }
+        };
+
+        // Entries cut short are left for when more of them has arrived:
+        let mut entries = Vec::new();
+        while entries.len() < remaining as usize {
+            let offset = self.offset + self.buffer.len() - input.len();
+            let mut entry = input;
+            let Some((kind, size)) = read_header(&mut entry, offset) else {
+                break;
+            };
+            if size > self.max_len - self.len {
+                return Err(Error::TooLarge(self.max_len as u64));
+            }
+            let Some(data) = inflate(&mut entry, size) else {
+                break;
+            };
+            entries.push((offset, kind, data));
+            input = entry;
+        }
+
+        let consumed = self.buffer.len() - input.len();
+        let remaining = remaining - entries.len() as u32;
+        self.remaining = Some(remaining);
+        self.digest.update(&self.buffer[..consumed]);
+        self.buffer.drain(..consumed);
+        self.offset += consumed;
+        self.wanted = match entries.is_empty() && remaining > 0 {
+            true => self.buffer.len() * 2,
+            false => 0,
+        };
+
+        for (offset, kind, data) in entries {
+            self.add(offset, kind, data)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the object held by an entry, once its delta base is known.
+    fn add(&mut self, offset: usize, kind: EntryKind, data: Vec<u8>) -> Result<()> {
+        let invalid = |message: &str| Error::InvalidGitData(message.to_string());
+
+        let object = match kind {
+            EntryKind::Object(kind) => Object { kind, data },
+            EntryKind::OffsetDelta(base) => {
+                let base = self
+                    .offsets
+                    .get(&base)
+                    .and_then(|oid| self.objects.get(oid))
+                    .ok_or_else(|| invalid("missing delta base"))?;
+                apply_delta(base, &data).ok_or_else(|| invalid("malformed delta"))?
+            }
+            EntryKind::RefDelta(base) => match self.objects.get(&base) {
+                Some(base) => apply_delta(base, &data).ok_or_else(|| invalid("malformed delta"))?,
+                None => {
+                    // Deltas against objects later in the packfile wait:
+                    self.hold(data.len())?;
+                    self.pending.push((offset, base, data));
+                    return Ok(());
+                }
+            },
+        };
+        self.hold(object.data.len())?;
+        let oid = object.oid();
+        self.offsets.insert(offset, oid);
+        self.objects.insert(oid, object);
+
+        while let Some(index) = self.pending.iter().position(|(_, base, _)| *base == oid) {
+            let (offset, base, delta) = self.pending.swap_remove(index);
+            self.add(offset, EntryKind::RefDelta(base), delta)?;
+        }
+        Ok(())
+    }
+
+    /// Counts data about to be held against the limit.
+    fn hold(&mut self, len: usize) -> Result<()> {
+        if len > self.max_len - self.len {
+            return Err(Error::TooLarge(self.max_len as u64));
+        }
+        self.len += len;
+        Ok(())
+    }
+
+    /// Returns the objects parsed so far, without checking the rest of the
+    /// packfile, for when it is no longer needed.
+    pub(crate) fn into_objects(self) -> HashMap<Oid, Object> {
+        self.objects
+    }
+
+    /// Checks that the whole packfile has arrived, returning its objects.
+    pub(crate) fn finish(self) -> Result<HashMap<Oid, Object>> {
+        let invalid = |message: &str| Error::InvalidGitData(message.to_string());
+
+        if self.remaining != Some(0) || self.buffer.len() < 20 {
+            return Err(invalid("malformed packfile"));
+        }
+        if self.buffer.len() > 20 {
+            return Err(invalid("trailing data in packfile"));
+        }
+        if self.digest.finish().as_ref() != self.buffer {
+            return Err(invalid("packfile checksum mismatch"));
+        }
+        if !self.pending.is_empty() {
+            return Err(invalid("missing delta base"));
+        }
+        Ok(self.objects)
+    }
+}
+
+/// The kind of a packfile entry, which holds either an object or a delta.
+pub(crate) enum EntryKind {
+    Object(Kind),
+    /// A delta against the entry at the given offset.
+    OffsetDelta(usize),
+    /// A delta against the object with the given ID.
+    RefDelta(Oid),
+}
+
+/// Reads the header of the packfile entry at the given offset, returning
+/// its kind and the size of its data, which follows as a zlib stream.
+pub(crate) fn read_header(input: &mut &[u8], offset: usize) -> Option<(EntryKind, usize)> {
+    let mut byte = next(input)?;
+    let code = (byte >> 4) & 0x07;
+    let mut size = (byte & 0x0f) as usize;
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+        byte = next(input)?;
+        size |= ((byte & 0x7f) as usize).checked_shl(shift)?;
+        shift += 7;
+    }
+
+    match code {
+        OFS_DELTA => {
+            // A big-endian number with an offset added to each extra byte:
+            let mut byte = next(input)?;
+            let mut distance = (byte & 0x7f) as usize;
+            while byte & 0x80 != 0 {
+                byte = next(input)?;
+                distance = distance.checked_add(1)?.checked_mul(128)? | (byte & 0x7f) as usize;
+            }
+            let base = offset.checked_sub(distance)?;
+            Some((EntryKind::OffsetDelta(base), size))
+        }
+        REF_DELTA => {
+            let (base, rest) = input.split_at_checked(20)?;
+            *input = rest;
+            Some((EntryKind::RefDelta(base.try_into().ok()?), size))
+        }
+        code => Some((EntryKind::Object(Kind::from_type(code)?), size)),
+    }
+}
+
+/// Inflates the zlib stream at the start of the input, which must inflate
+/// to the given size, and skips past it.
+pub(crate) fn inflate(input: &mut &[u8], size: usize) -> Option<Vec<u8>> {
+    let mut inflater = Decompress::new(true);
+    let mut output = Vec::with_capacity(size.min(MAX_PREALLOC) + 1);
+    loop {
+        if output.len() == output.capacity() {
+            output.reserve(output.len());
+        }
+        let (read, written) = (inflater.total_in(), inflater.total_out());
+        let status = inflater
+            .decompress_vec(
+                &input[read as usize..],
+                &mut output,
+                FlushDecompress::Finish,
+            )
+            .ok()?;
+        if output.len() > size {
+            return None;
+        }
+        match status {
+            Status::StreamEnd => break,
+            _ if inflater.total_in() == read && inflater.total_out() == written => return None,
+            _ => {}
+        }
+    }
+    *input = &input[inflater.total_in() as usize..];
+    (output.len() == size).then_some(output)
+}
+
+/// Applies a delta to its base object.
+///
+/// See: https://git-scm.com/docs/gitformat-pack#_deltified_representation
+pub(crate) fn apply_delta(base: &Object, mut delta: &[u8]) -> Option<Object> {
+    let source_len = read_size(&mut delta)?;
+    let target_len = read_size(&mut delta)?;
+    if source_len != base.data.len() {
+        return None;
+    }
+    let mut data = Vec::with_capacity(target_len.min(MAX_PREALLOC));
+    while let Some(op) = next(&mut delta) {
+        if op & 0x80 != 0 {
+            // Copy from the base, with the offset and size bytes present
+            // according to the low bits:
+            let mut offset = 0;
+            let mut len = 0;
+            for i in 0..4 {
+                if op & (1 << i) != 0 {
+                    offset |= (next(&mut delta)? as usize) << (8 * i);
+                }
+            }
+            for i in 0..3 {
+                if op & (0x10 << i) != 0 {
+                    len |= (next(&mut delta)? as usize) << (8 * i);
+                }
+            }
+            if len == 0 {
+                len = 0x10000;
+            }
+            data.extend(base.data.get(offset..offset.checked_add(len)?)?);
+        } else if op != 0 {
+            // Insert the following bytes:
+            let (insert, rest) = delta.split_at_checked(op as usize)?;
+            data.extend(insert);
+            delta = rest;
+        } else {
+            return None;
+        }
+        if data.len() > target_len {
+            return None;
+        }
+    }
+    (data.len() == target_len).then_some(Object {
+        kind: base.kind,
+        data,
+    })
+}
+
+/// Reads a little-endian base-128 size.
+fn read_size(input: &mut &[u8]) -> Option<usize> {
+    let mut size = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = next(input)?;
+        size |= ((byte & 0x7f) as usize).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(size);
+        }
+        shift += 7;
+    }
+}
+
+fn next(input: &mut &[u8]) -> Option<u8> {
+    let (&byte, rest) = input.split_first()?;
+    *input = rest;
+    Some(byte)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn blob(data: &[u8]) -> Object {
+        Object {
+            kind: Kind::Blob,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn object_ids() {
+        // As given by `git hash-object`:
+        assert_eq!(
+            to_hex(&blob(b"hello\n").oid()),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+        let oid = parse_oid("ce013625030ba8dba906f756967f9e9ca394464a").unwrap();
+        assert_eq!(oid, blob(b"hello\n").oid());
+        assert!(parse_oid("ce013625").is_none());
+        assert!(parse_oid("ce013625030ba8dba906f756967f9e9ca394464g").is_none());
+    }
+
+    #[test]
+    fn apply_deltas() {
+        let base = blob(b"Hello, world!\n");
+        // Copy "Hello, ", insert "git", then copy "!\n":
+        let mut delta = vec![14, 12, 0x90, 7, 3];
+        delta.extend(b"git");
+        delta.extend([0x91, 12, 2]);
+        assert_eq!(apply_delta(&base, &delta).unwrap().data, b"Hello, git!\n");
+
+        // The base must have the size that the delta expects:
+        delta[0] = 13;
+        assert!(apply_delta(&base, &delta).is_none());
+        delta[0] = 14;
+        // Copies must stay within the base:
+        delta[9] = 13;
+        assert!(apply_delta(&base, &delta).is_none());
+    }
+
+    /// Returns a packfile holding the given objects, without deltas.
+    fn pack(objects: &[&Object]) -> Vec<u8> {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let mut pack = b"PACK\0\0\0\x02".to_vec();
+        pack.extend((objects.len() as u32).to_be_bytes());
+        for object in objects {
+            let code = match object.kind {
+                Kind::Commit => 1,
+                Kind::Tree => 2,
+                Kind::Blob => 3,
+                Kind::Tag => 4,
+            };
+            let mut size = object.data.len();
+            let mut byte = (code << 4) | (size & 0x0f) as u8;
+            size >>= 4;
+            while size > 0 {
+                pack.push(byte | 0x80);
+                byte = (size & 0x7f) as u8;
+                size >>= 7;
+            }
+            pack.push(byte);
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&object.data).unwrap();
+            pack.extend(encoder.finish().unwrap());
+        }
+        let checksum = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, &pack);
+        pack.extend(checksum.as_ref());
+        pack
+    }
+
+    #[test]
+    fn unpack_in_parts() {
+        let small = blob(b"hello\n");
+        let large = blob(&b"0123456789".repeat(1000));
+        let pack = pack(&[&small, &large]);
+
+        let mut unpacker = Unpacker::new(usize::MAX);
+        for byte in &pack[..pack.len() - 21] {
+            unpacker.push(&[*byte]).unwrap();
+        }
+        // Every entry is parsed as soon as it is complete:
+        assert!(unpacker.objects().contains_key(&small.oid()));
+        assert!(!unpacker.objects().contains_key(&large.oid()));
+        for byte in &pack[pack.len() - 21..] {
+            unpacker.push(&[*byte]).unwrap();
+        }
+        let objects = unpacker.finish().unwrap();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[&large.oid()].data, large.data);
+
+        // The packfile must be whole and unchanged:
+        let mut unpacker = Unpacker::new(usize::MAX);
+        unpacker.push(&pack[..pack.len() - 1]).unwrap();
+        assert!(unpacker.finish().is_err());
+        let mut corrupt = pack.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        let mut unpacker = Unpacker::new(usize::MAX);
+        unpacker.push(&corrupt).unwrap();
+        assert!(unpacker.finish().is_err());
+
+        // Objects over the limit are refused as soon as their size is known:
+        let mut unpacker = Unpacker::new(100);
+        assert!(matches!(unpacker.push(&pack), Err(Error::TooLarge(100))));
+
+        // And so are objects that together are over the limit:
+        let limit = large.data.len() + 1;
+        let mut unpacker = Unpacker::new(limit);
+        assert!(matches!(unpacker.push(&pack), Err(Error::TooLarge(n)) if n == limit as u64));
+    }
+
+    #[test]
+    fn walk_trees() {
+        let file = blob(b"hello\n");
+        let mut dir = b"100644 a.txt\0".to_vec();
+        dir.extend(file.oid());
+        let dir = Object {
+            kind: Kind::Tree,
+            data: dir,
+        };
+        let mut root = b"40000 docs\0".to_vec();
+        root.extend(dir.oid());
+        let root = Object {
+            kind: Kind::Tree,
+            data: root,
+        };
+        let commit = Object {
+            kind: Kind::Commit,
+            data: format!("tree {}\nauthor a\n\nmessage tree 0\n", to_hex(&root.oid())).into(),
+        };
+        let tag = Object {
+            kind: Kind::Tag,
+            data: format!("object {}\ntype commit\n", to_hex(&commit.oid())).into(),
+        };
+        let objects: HashMap<Oid, Object> = [dir, root, commit, tag.clone()]
+            .into_iter()
+            .map(|object| (object.oid(), object))
+            .collect();
+        let lookup = |oid: &Oid| {
+            objects
+                .get(oid)
+                .cloned()
+                .ok_or_else(|| Error::GitObjectNotFound(to_hex(oid)))
+        };
+
+        assert_eq!(
+            find_blob(&tag.oid(), "docs/a.txt", lookup).unwrap(),
+            file.oid()
+        );
+        for path in ["docs", "docs/b.txt", "docs/a.txt/c", ""] {
+            assert!(matches!(
+                find_blob(&tag.oid(), path, lookup),
+                Err(Error::GitObjectNotFound(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,340 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{Error, Read};
+use std::io::{Seek, SeekFrom};
+
+#[cfg(any(feature = "ftp", feature = "http"))]
+use crate::error::is_transient_io;
+
+/// A reader that fails once more than `limit` bytes have been read.
+pub(crate) struct SizeLimit<R> {
+    inner: R,
+    limit: u64,
+    count: u64,
+}
+
+impl<R: Read> SizeLimit<R> {
+    pub(crate) fn new(inner: R, limit: u64) -> Self {
+        Self {
+            inner,
+            limit,
+            count: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for SizeLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Once over the limit, keep failing rather than read any further:
+        if self.count > self.limit {
+            return Err(Error::TooLarge(self.limit).into());
+        }
+        // Read up to one byte past the limit, so as to detect overflow:
+        let remaining = (self.limit - self.count).saturating_add(1);
+        let len = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..len])?;
+        self.count += n as u64;
+        if self.count > self.limit {
+            return Err(Error::TooLarge(self.limit).into());
+        }
+        Ok(n)
+    }
+}
+
+/// Seeking moves the count along, so that no byte past the limit is read
+/// whatever the order of the reads.
+impl<R: Seek> Seek for SizeLimit<R> {
+    fn seek(&mut self, position: SeekFrom) -> std::io::Result<u64> {
+        self.count = self.inner.seek(position)?;
+        Ok(self.count)
+    }
+}
+
+/// A reader that reopens its source at the current offset after a
+/// transient error, such as a connection reset, up to a number of attempts.
+///
+/// Errors from reopening that are not themselves transient mean that
+/// resumption is impossible, and are returned straight away.
+#[cfg(any(feature = "ftp", feature = "http"))]
+pub(crate) struct Resume<F> {
+    inner: Box<dyn Read>,
+    reopen: F,
+    position: u64,
+    attempts: u32,
+}
+
+#[cfg(any(feature = "ftp", feature = "http"))]
+impl<F: FnMut(u64) -> crate::Result<Box<dyn Read>>> Resume<F> {
+    pub(crate) fn new(inner: Box<dyn Read>, attempts: u32, reopen: F) -> Self {
+        Self {
+            inner,
+            reopen,
+            position: 0,
+            attempts,
+        }
+    }
+
+    fn resume(&mut self, mut error: std::io::Error) -> std::io::Result<Box<dyn Read>> {
+        while self.attempts > 0 {
+            self.attempts -= 1;
+            match (self.reopen)(self.position) {
+                Ok(reader) => return Ok(reader),
+                Err(e) => error = e.into(),
+            }
+            if !is_transient_io(&error) {
+                break;
+            }
+        }
+        Err(error)
+    }
+}
+
+#[cfg(any(feature = "ftp", feature = "http"))]
+impl<F: FnMut(u64) -> crate::Result<Box<dyn Read>>> Read for Resume<F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            match self.inner.read(buf) {
+                Ok(n) => {
+                    self.position += n as u64;
+                    return Ok(n);
+                }
+                Err(error) if is_transient_io(&error) => self.inner = self.resume(error)?,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+/// A reader that hashes its contents, failing at EOF if the digest differs
+/// from the expected one.
+#[cfg(feature = "integrity")]
+pub(crate) struct DigestCheck<R> {
+    inner: R,
+    expected: crate::Digest,
+    hasher: Option<crate::digest::Hasher>,
+    /// The digest actually read, once it turned out to differ.
+    mismatch: Option<crate::Digest>,
+}
+
+#[cfg(feature = "integrity")]
+impl<R: Read> DigestCheck<R> {
+    pub(crate) fn new(inner: R, expected: crate::Digest) -> Self {
+        let hasher = Some(crate::digest::Hasher::new(&expected));
+        Self {
+            inner,
+            expected,
+            hasher,
+            mismatch: None,
+        }
+    }
+
+    fn mismatch(&self, actual: &crate::Digest) -> std::io::Error {
+        Error::IntegrityMismatch {
+            expected: self.expected.to_string(),
+            actual: actual.to_string(),
+        }
+        .into()
+    }
+}
+
+#[cfg(feature = "integrity")]
+impl<R: Read> Read for DigestCheck<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Once the digest turned out to differ, keep failing rather than
+        // report a clean EOF:
+        if let Some(actual) = &self.mismatch {
+            return Err(self.mismatch(actual));
+        }
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            if let Some(hasher) = self.hasher.as_mut() {
+                hasher.update(&buf[..n]);
+            }
+        } else if !buf.is_empty() {
+            if let Some(hasher) = self.hasher.take() {
+                let actual = hasher.finalize();
+                if actual != self.expected {
+                    let error = self.mismatch(&actual);
+                    self.mismatch = Some(actual);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Wraps a stream so that it fails once more than `limit` bytes have been
+/// yielded.
+#[cfg(feature = "async")]
+pub(crate) fn limit_stream(
+    stream: crate::r#async::ByteStream,
+    limit: u64,
+) -> crate::r#async::ByteStream {
+    use futures_util::StreamExt;
+
+    let mut count: u64 = 0;
+    Box::pin(stream.map(move |chunk| {
+        let chunk = chunk?;
+        count += chunk.len() as u64;
+        if count > limit {
+            return Err(Error::TooLarge(limit).into());
+        }
+        Ok(chunk)
+    }))
+}
+
+/// Wraps a stream so that it hashes its contents, failing at its end if the
+/// digest differs from the expected one.
+#[cfg(all(feature = "async", feature = "integrity"))]
+pub(crate) fn check_stream_digest(
+    stream: crate::r#async::ByteStream,
+    expected: crate::Digest,
+) -> crate::r#async::ByteStream {
+    use futures_util::StreamExt;
+
+    // The state is the stream being read, or else the mismatched digest,
+    // which keeps failing any further reads, if any:
+    let hasher = crate::digest::Hasher::new(&expected);
+    let stream = futures_util::stream::unfold(Ok((stream, hasher)), move |state| {
+        let expected = expected.clone();
+        async move {
+            let mismatch = |actual: &crate::Digest| -> std::io::Error {
+                Error::IntegrityMismatch {
+                    expected: expected.to_string(),
+                    actual: actual.to_string(),
+                }
+                .into()
+            };
+            let (mut stream, mut hasher) = match state {
+                Ok(reading) => reading,
+                Err(Some(actual)) => return Some((Err(mismatch(&actual)), Err(Some(actual)))),
+                Err(None) => return None,
+            };
+            match stream.next().await {
+                Some(Ok(chunk)) => {
+                    hasher.update(&chunk);
+                    Some((Ok(chunk), Ok((stream, hasher))))
+                }
+                Some(Err(error)) => Some((Err(error), Err(None))),
+                None => {
+                    let actual = hasher.finalize();
+                    (actual != expected).then(|| (Err(mismatch(&actual)), Err(Some(actual))))
+                }
+            }
+        }
+    });
+    // Readers may poll again after the end:
+    Box::pin(stream.fuse())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Cursor;
+
+    #[test]
+    fn size_limit() {
+        let mut output = Vec::new();
+        let mut reader = SizeLimit::new(Cursor::new(b"hello"), 5);
+        assert_eq!(reader.read_to_end(&mut output).unwrap(), 5);
+
+        let mut reader = SizeLimit::new(Cursor::new(b"hello"), 4);
+        assert!(reader.read_to_end(&mut output).is_err());
+
+        // Reading again after the error fails the same way:
+        let mut buf = [0; 8];
+        let error = reader.read(&mut buf).unwrap_err();
+        assert!(matches!(
+            error.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+            Some(Error::TooLarge(4))
+        ));
+    }
+
+    #[test]
+    fn seekable_size_limit() {
+        let mut output = Vec::new();
+        let mut reader = SizeLimit::new(Cursor::new(b"hello world"), 5);
+        reader.seek(SeekFrom::Start(2)).unwrap();
+        reader.by_ref().take(3).read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"llo");
+
+        // Seeking past the limit doesn't get around it:
+        reader.seek(SeekFrom::End(-5)).unwrap();
+        assert!(reader.read_to_end(&mut output).is_err());
+
+        // Seeking back within it does:
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        reader.by_ref().take(5).read_to_end(&mut output).unwrap();
+        assert_eq!(output, b"llohello");
+    }
+
+    #[cfg(feature = "integrity")]
+    #[test]
+    fn digest_check() {
+        let digest: crate::Digest =
+            "sha256=9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+                .parse()
+                .unwrap();
+        let mut output = Vec::new();
+        let mut reader = DigestCheck::new(Cursor::new(b"test"), digest.clone());
+        assert_eq!(reader.read_to_end(&mut output).unwrap(), 4);
+
+        let mut reader = DigestCheck::new(Cursor::new(b"tent"), digest);
+        let error = reader.read_to_end(&mut output).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // Reading again after the error fails the same way, twice over:
+        let mut buf = [0; 8];
+        for _ in 0..2 {
+            let error = reader.read(&mut buf).unwrap_err();
+            assert!(matches!(
+                error.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+                Some(Error::IntegrityMismatch { .. })
+            ));
+        }
+    }
+
+    #[cfg(any(feature = "ftp", feature = "http"))]
+    #[test]
+    fn resume() {
+        use std::io::{Error, ErrorKind};
+
+        // Yields the given bytes, then fails as if the connection dropped:
+        let dropping = |bytes: &'static [u8]| -> Box<dyn Read> {
+            let reset = std::iter::once(Err(Error::from(ErrorKind::ConnectionReset)));
+            Box::new(Cursor::new(bytes).chain(ReadErrors(Box::new(reset))))
+        };
+
+        let mut offsets = Vec::new();
+        let mut output = String::new();
+        let mut reader = Resume::new(dropping(b"hel"), 2, |offset| {
+            offsets.push(offset);
+            Ok(match offset {
+                3 => dropping(b"lo "),
+                _ => Box::new(Cursor::new(&b"hello world"[offset as usize..])),
+            })
+        });
+        reader.read_to_string(&mut output).unwrap();
+        drop(reader);
+        assert_eq!(output, "hello world");
+        assert_eq!(offsets, [3, 6]);
+
+        let mut reader = Resume::new(dropping(b"hel"), 1, |_| Ok(dropping(b"lo")));
+        let error = reader.read_to_string(&mut output).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ConnectionReset);
+    }
+
+    /// A reader that returns the given results, and then EOF.
+    #[cfg(any(feature = "ftp", feature = "http"))]
+    struct ReadErrors(Box<dyn Iterator<Item = std::io::Result<usize>>>);
+
+    #[cfg(any(feature = "ftp", feature = "http"))]
+    impl Read for ReadErrors {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.next().unwrap_or(Ok(0))
+        }
+    }
+}
@@ -0,0 +1,408 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{
+    open_url_using, Error, OpenOptions, Read, Resource, Result, SchemeHandler, SeekableHandler, Url,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, LazyLock, RwLock},
+};
+
+static DEFAULT_REGISTRY: LazyLock<RwLock<Registry>> =
+    LazyLock::new(|| RwLock::new(Registry::default()));
+
+/// Returns the process-wide registry consulted by [`open`](crate::open).
+///
+/// Applications can register, override, or remove protocol handlers at
+/// runtime by acquiring a write lock on the returned registry.
+pub fn default_registry() -> &'static RwLock<Registry> {
+    &DEFAULT_REGISTRY
+}
+
+/// A table of protocol handlers keyed by URL scheme.
+///
+/// Scheme names are case-insensitive. The [`Default`] registry comes
+/// pre-populated with the built-in handlers for all enabled features,
+/// whereas [`Registry::empty`] starts out with no handlers at all.
+#[derive(Clone)]
+pub struct Registry {
+    handlers: BTreeMap<String, Arc<dyn SchemeHandler>>,
+    seekable: BTreeMap<String, Arc<dyn SeekableHandler>>,
+    /// The schemes whose handlers also open the compound schemes that nest
+    /// another URL in them.
+    nested: BTreeSet<String>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        // Builds without any protocols register no handlers:
+        #[cfg_attr(
+            not(any(
+                feature = "archive",
+                feature = "data",
+                feature = "file",
+                feature = "ftp",
+                feature = "gemini",
+                feature = "git",
+                feature = "gopher",
+                feature = "http",
+                feature = "ipfs",
+                feature = "scp",
+                feature = "sftp",
+                feature = "stdin"
+            )),
+            allow(unused_mut)
+        )]
+        let mut registry = Self::empty();
+
+        #[cfg(feature = "archive")]
+        registry.register_nested("tar", |url: &Url, options: &OpenOptions| {
+            crate::schemes::archive::open(url, crate::schemes::archive::ArchiveFormat::Tar, options)
+        });
+
+        #[cfg(feature = "archive")]
+        registry.register_nested("zip", |url: &Url, options: &OpenOptions| {
+            crate::schemes::archive::open(url, crate::schemes::archive::ArchiveFormat::Zip, options)
+        });
+
+        #[cfg(feature = "data")]
+        registry.register("data", |url: &Url, options: &OpenOptions| {
+            crate::schemes::data::open(url, options)
+        });
+
+        #[cfg(feature = "file")]
+        registry.register("file", |url: &Url, options: &OpenOptions| {
+            crate::schemes::file::open(url, options)
+        });
+
+        #[cfg(feature = "ftp")]
+        registry.register("ftp", |url: &Url, options: &OpenOptions| {
+            crate::schemes::ftp::open(url, crate::schemes::ftp::FtpSecurity::None, options)
+        });
+
+        #[cfg(feature = "ftps")]
+        registry.register("ftps", |url: &Url, options: &OpenOptions| {
+            crate::schemes::ftp::open(url, crate::schemes::ftp::FtpSecurity::Implicit, options)
+        });
+
+        #[cfg(feature = "ftps")]
+        registry.register("ftpes", |url: &Url, options: &OpenOptions| {
+            crate::schemes::ftp::open(url, crate::schemes::ftp::FtpSecurity::Explicit, options)
+        });
+
+        #[cfg(feature = "gemini")]
+        registry.register("gemini", |url: &Url, options: &OpenOptions| {
+            crate::schemes::gemini::open(url, options)
+        });
+
+        #[cfg(feature = "git")]
+        registry.register("git", |url: &Url, options: &OpenOptions| {
+            crate::schemes::git::open(url, options)
+        });
+
+        #[cfg(feature = "git")]
+        registry.register("git+file", |url: &Url, options: &OpenOptions| {
+            crate::schemes::git::open(url, options)
+        });
+
+        #[cfg(feature = "gopher")]
+        registry.register("gopher", |url: &Url, options: &OpenOptions| {
+            crate::schemes::gopher::open(url, options)
+        });
+
+        #[cfg(feature = "http")]
+        registry.register("http", |url: &Url, options: &OpenOptions| {
+            crate::schemes::http::open(url, false, options)
+        });
+
+        #[cfg(feature = "https")]
+        registry.register("https", |url: &Url, options: &OpenOptions| {
+            crate::schemes::http::open(url, true, options)
+        });
+
+        #[cfg(feature = "ipfs")]
+        registry.register("ipfs", |url: &Url, options: &OpenOptions| {
+            crate::schemes::ipfs::open(url, options)
+        });
+
+        #[cfg(feature = "ipfs")]
+        registry.register("ipns", |url: &Url, options: &OpenOptions| {
+            crate::schemes::ipfs::open(url, options)
+        });
+
+        #[cfg(feature = "scp")]
+        registry.register("scp", |url: &Url, options: &OpenOptions| {
+            crate::schemes::scp::open(url, options)
+        });
+
+        #[cfg(feature = "sftp")]
+        registry.register("sftp", |url: &Url, options: &OpenOptions| {
+            crate::schemes::sftp::open(url, options)
+        });
+
+        #[cfg(feature = "stdin")]
+        registry.register("stdin", |url: &Url, options: &OpenOptions| {
+            crate::schemes::stdin::open(url, options)
+        });
+
+        // Seekable handlers go last, since registering a handler above
+        // would remove the seekable one for its scheme:
+
+        #[cfg(feature = "data")]
+        registry.register_seekable("data", |url: &Url, options: &OpenOptions| {
+            crate::schemes::data::open_seekable(url, options)
+        });
+
+        #[cfg(feature = "file")]
+        registry.register_seekable("file", |url: &Url, options: &OpenOptions| {
+            crate::schemes::file::open_seekable(url, options)
+        });
+
+        #[cfg(feature = "ftp")]
+        registry.register_seekable("ftp", |url: &Url, options: &OpenOptions| {
+            crate::schemes::ftp::open_seekable(url, crate::schemes::ftp::FtpSecurity::None, options)
+        });
+
+        #[cfg(feature = "ftps")]
+        registry.register_seekable("ftps", |url: &Url, options: &OpenOptions| {
+            let security = crate::schemes::ftp::FtpSecurity::Implicit;
+            crate::schemes::ftp::open_seekable(url, security, options)
+        });
+
+        #[cfg(feature = "ftps")]
+        registry.register_seekable("ftpes", |url: &Url, options: &OpenOptions| {
+            let security = crate::schemes::ftp::FtpSecurity::Explicit;
+            crate::schemes::ftp::open_seekable(url, security, options)
+        });
+
+        #[cfg(feature = "git")]
+        registry.register_seekable("git", |url: &Url, options: &OpenOptions| {
+            crate::schemes::git::open_seekable(url, options)
+        });
+
+        #[cfg(feature = "git")]
+        registry.register_seekable("git+file", |url: &Url, options: &OpenOptions| {
+            crate::schemes::git::open_seekable(url, options)
+        });
+
+        #[cfg(feature = "http")]
+        registry.register_seekable("http", |url: &Url, options: &OpenOptions| {
+            crate::schemes::http::open_seekable(url, false, options)
+        });
+
+        #[cfg(feature = "https")]
+        registry.register_seekable("https", |url: &Url, options: &OpenOptions| {
+            crate::schemes::http::open_seekable(url, true, options)
+        });
+
+        #[cfg(feature = "ipfs")]
+        registry.register_seekable("ipfs", |url: &Url, options: &OpenOptions| {
+            crate::schemes::ipfs::open_seekable(url, options)
+        });
+
+        #[cfg(feature = "ipfs")]
+        registry.register_seekable("ipns", |url: &Url, options: &OpenOptions| {
+            crate::schemes::ipfs::open_seekable(url, options)
+        });
+
+        registry
+    }
+}
+
+impl core::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_set().entries(self.schemes()).finish()
+    }
+}
+
+impl Registry {
+    /// Creates a registry with the built-in handlers for all enabled features.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry without any handlers.
+    pub fn empty() -> Self {
+        Self {
+            handlers: BTreeMap::new(),
+            seekable: BTreeMap::new(),
+            nested: BTreeSet::new(),
+        }
+    }
+
+    /// Registers a handler for the given scheme, returning the handler it
+    /// replaced, if any.
+    ///
+    /// This also removes any seekable handler for the scheme, so that random
+    /// access doesn't bypass the new handler; register one afterwards with
+    /// [`register_seekable`](Self::register_seekable) if needed.
+    pub fn register(
+        &mut self,
+        scheme: impl AsRef<str>,
+        handler: impl SchemeHandler + 'static,
+    ) -> Option<Arc<dyn SchemeHandler>> {
+        let scheme = scheme.as_ref().to_ascii_lowercase();
+        self.seekable.remove(&scheme);
+        self.nested.remove(&scheme);
+        self.handlers.insert(scheme, Arc::new(handler))
+    }
+
+    /// Registers a handler for the given scheme that also opens the URLs
+    /// nested in it, such as `tar+https:` URLs for `tar`, returning the
+    /// handler it replaced, if any.
+    pub fn register_nested(
+        &mut self,
+        scheme: impl AsRef<str>,
+        handler: impl SchemeHandler + 'static,
+    ) -> Option<Arc<dyn SchemeHandler>> {
+        let previous = self.register(scheme.as_ref(), handler);
+        self.nested.insert(scheme.as_ref().to_ascii_lowercase());
+        previous
+    }
+
+    /// Registers a handler for opening URLs of the given scheme for random
+    /// access, returning the seekable handler it replaced, if any.
+    pub fn register_seekable(
+        &mut self,
+        scheme: impl AsRef<str>,
+        handler: impl SeekableHandler + 'static,
+    ) -> Option<Arc<dyn SeekableHandler>> {
+        self.seekable
+            .insert(scheme.as_ref().to_ascii_lowercase(), Arc::new(handler))
+    }
+
+    /// Removes the handlers for the given scheme, returning the sequential
+    /// one if it existed.
+    pub fn unregister(&mut self, scheme: impl AsRef<str>) -> Option<Arc<dyn SchemeHandler>> {
+        let scheme = scheme.as_ref().to_ascii_lowercase();
+        self.seekable.remove(&scheme);
+        self.nested.remove(&scheme);
+        self.handlers.remove(&scheme)
+    }
+
+    /// Returns the handler for the given scheme, if any.
+    ///
+    /// A compound scheme such as `tar+https` falls back to the handler for
+    /// its first component (`tar`) if it has no handler of its own, as long
+    /// as that handler was registered with
+    /// [`register_nested`](Self::register_nested). Other compound schemes,
+    /// such as `git+ssh`, are unknown unless registered themselves.
+    pub fn get(&self, scheme: impl AsRef<str>) -> Option<Arc<dyn SchemeHandler>> {
+        let scheme = scheme.as_ref().to_ascii_lowercase();
+        self.handlers
+            .get(&scheme)
+            .or_else(|| {
+                let (outer, _) = scheme.split_once('+')?;
+                self.nested
+                    .contains(outer)
+                    .then(|| self.handlers.get(outer))?
+            })
+            .cloned()
+    }
+
+    /// Returns the seekable handler for the given scheme, if any.
+    pub fn get_seekable(&self, scheme: impl AsRef<str>) -> Option<Arc<dyn SeekableHandler>> {
+        self.seekable
+            .get(&scheme.as_ref().to_ascii_lowercase())
+            .cloned()
+    }
+
+    /// Checks whether a handler is registered for the given scheme.
+    pub fn contains(&self, scheme: impl AsRef<str>) -> bool {
+        self.get(scheme).is_some()
+    }
+
+    /// Returns the registered scheme names in sorted order.
+    pub fn schemes(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+
+    /// Opens the given URL using the handler registered for its scheme.
+    pub fn open(&self, url: impl AsRef<str>) -> Result<Box<dyn Read>> {
+        self.open_with(url, &OpenOptions::default())
+    }
+
+    /// Opens the given URL with the given options, using the handler
+    /// registered for its scheme.
+    pub fn open_with(&self, url: impl AsRef<str>, options: &OpenOptions) -> Result<Box<dyn Read>> {
+        let url = url.as_ref().parse::<Url>()?;
+        self.open_url(&url, options).map(Resource::into_reader)
+    }
+
+    /// Opens an already-parsed URL with the given options, using the
+    /// handler registered for its scheme.
+    pub fn open_url(&self, url: &Url, options: &OpenOptions) -> Result<Resource> {
+        let handler = self
+            .get(url.scheme_str())
+            .ok_or_else(|| Error::UnknownScheme(url.scheme_str().to_string()))?;
+        open_url_using(handler.as_ref(), url, options)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Cursor;
+
+    fn hello(_url: &Url, _options: &OpenOptions) -> Result<Box<dyn Read>> {
+        Ok(Box::new(Cursor::new(b"hello".to_vec())))
+    }
+
+    #[test]
+    fn register_and_unregister() {
+        let mut registry = Registry::empty();
+        assert!(matches!(
+            registry.open("hello:"),
+            Err(Error::UnknownScheme(s)) if s == "hello"
+        ));
+
+        assert!(registry.register("Hello", hello).is_none());
+        assert!(registry.contains("hello"));
+        assert!(registry.contains("HELLO"));
+        assert_eq!(registry.schemes().collect::<Vec<_>>(), vec!["hello"]);
+
+        let mut output = String::new();
+        registry
+            .open("hello:")
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "hello");
+
+        assert!(registry.register("hello", hello).is_some());
+        assert!(registry.unregister("hello").is_some());
+        assert!(!registry.contains("hello"));
+    }
+
+    #[test]
+    fn compound_schemes() {
+        let mut registry = Registry::empty();
+        registry.register("hello", hello);
+        registry.register_nested("nested", hello);
+        assert!(registry.contains("nested+https"));
+        assert!(!registry.contains("hello+https"));
+
+        // Overriding the handler doesn't leave it open to nested URLs:
+        registry.register("nested", hello);
+        assert!(!registry.contains("nested+https"));
+    }
+
+    #[test]
+    fn override_seekable() {
+        let seekable = |_url: &Url, _options: &OpenOptions| -> Result<Box<dyn crate::ReadSeek>> {
+            Ok(Box::new(Cursor::new(b"hello".to_vec())))
+        };
+        let mut registry = Registry::empty();
+        registry.register("hello", hello);
+        registry.register_seekable("hello", seekable);
+        assert!(registry.get_seekable("HELLO").is_some());
+
+        // Overriding the handler doesn't leave the old seekable one behind:
+        registry.register("hello", hello);
+        assert!(registry.get_seekable("hello").is_none());
+
+        registry.register_seekable("hello", seekable);
+        registry.unregister("hello");
+        assert!(registry.get_seekable("hello").is_none());
+    }
+}
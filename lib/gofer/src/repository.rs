@@ -0,0 +1,634 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Local git repositories, whose refs and objects are read from disk
+//! without running git.
+//!
+//! See: https://git-scm.com/docs/gitrepository-layout
+//! See: https://git-scm.com/docs/gitformat-pack
+
+use crate::{
+    pack::{self, EntryKind, Kind, Object, Oid},
+    Error, Result,
+};
+use flate2::bufread::ZlibDecoder;
+use std::{
+    cmp::Ordering,
+    fs::{self, File},
+    io::{BufRead, BufReader, Cursor, ErrorKind, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// The most memory reserved up front for an object, whatever size its
+/// header claims.
+const MAX_PREALLOC: usize = 1 << 20;
+
+/// The deepest chain of symbolic refs followed, as in git.
+const MAX_SYMREF_DEPTH: usize = 5;
+
+/// The longest chain of deltas followed to reconstruct an object.
+const MAX_DELTA_DEPTH: usize = 4096;
+
+/// The most object directories searched, including alternates.
+const MAX_OBJECT_DIRS: usize = 16;
+
+/// The longest header of a loose object, such as `commit 1234\0`.
+const MAX_LOOSE_HEADER_LEN: usize = 32;
+
+/// A repository on the local filesystem, bare or with a working tree.
+pub(crate) struct Repository {
+    /// The git directory, such as `.git` in a working tree.
+    git_dir: PathBuf,
+    /// The git directory shared by linked working trees, which holds the
+    /// refs and objects.
+    common_dir: PathBuf,
+    /// The object directories, the repository's own and its alternates.
+    object_dirs: Vec<PathBuf>,
+    /// The pack indexes, each next to the packfile it indexes.
+    indexes: Vec<PathBuf>,
+}
+
+impl Repository {
+    /// Opens the repository at the given path, which is either a working
+    /// tree or a git directory.
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let git_dir = find_git_dir(path)?;
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(dir) => git_dir.join(dir.trim_end()),
+            Err(_) => git_dir.clone(),
+        };
+        if common_dir.join("reftable").is_dir() {
+            return Err(Error::FailedGitRequest(std::io::Error::new(
+                ErrorKind::Unsupported,
+                "repositories with reftable refs are not supported",
+            )));
+        }
+        let config = fs::read_to_string(common_dir.join("config")).unwrap_or_default();
+        match config_value(&config, "extensions", "objectformat") {
+            None => {}
+            Some(format) if format.eq_ignore_ascii_case("sha1") => {}
+            Some(format) => {
+                return Err(Error::FailedGitRequest(std::io::Error::new(
+                    ErrorKind::Unsupported,
+                    format!("repositories with {} object IDs are not supported", format),
+                )))
+            }
+        }
+
+        // Objects may also be borrowed from other repositories:
+        // See: https://git-scm.com/docs/gitrepository-layout#Documentation/gitrepository-layout.txt-objectsinfoalternates
+        let mut object_dirs = vec![common_dir.join("objects")];
+        let mut i = 0;
+        while i < object_dirs.len() && object_dirs.len() < MAX_OBJECT_DIRS {
+            let alternates =
+                fs::read_to_string(object_dirs[i].join("info/alternates")).unwrap_or_default();
+            for line in alternates.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let dir = object_dirs[i].join(line);
+                if !object_dirs.contains(&dir) {
+                    object_dirs.push(dir);
+                }
+            }
+            i += 1;
+        }
+        object_dirs.truncate(MAX_OBJECT_DIRS);
+
+        let mut indexes = Vec::new();
+        for dir in &object_dirs {
+            let Ok(entries) = fs::read_dir(dir.join("pack")) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|extension| extension == "idx") {
+                    indexes.push(path);
+                }
+            }
+        }
+        indexes.sort();
+
+        Ok(Self {
+            git_dir,
+            common_dir,
+            object_dirs,
+            indexes,
+        })
+    }
+
+    /// Resolves a ref, such as a branch or tag, or a full or abbreviated
+    /// object ID, to an object ID, in the order `git rev-parse` does.
+    ///
+    /// See: https://git-scm.com/docs/gitrevisions
+    pub(crate) fn resolve(&self, revision: &str) -> Result<Oid> {
+        if let Some(oid) = pack::parse_oid(&revision.to_ascii_lowercase()) {
+            return Ok(oid);
+        }
+        if is_ref_name(revision) {
+            // Only refs under `refs/` and pseudorefs such as `HEAD` are
+            // looked up as given, and not other files in the git directory:
+            let pseudoref = revision
+                .bytes()
+                .all(|byte| byte.is_ascii_uppercase() || byte == b'_');
+            let candidates = [
+                (revision.starts_with("refs/") || pseudoref).then(|| revision.to_string()),
+                Some(format!("refs/{}", revision)),
+                Some(format!("refs/tags/{}", revision)),
+                Some(format!("refs/heads/{}", revision)),
+                Some(format!("refs/remotes/{}", revision)),
+                Some(format!("refs/remotes/{}/HEAD", revision)),
+            ];
+            for name in candidates.iter().flatten() {
+                if let Some(oid) = self.read_ref(name)? {
+                    return Ok(oid);
+                }
+            }
+        }
+        if (4..40).contains(&revision.len()) && revision.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return self.expand(&revision.to_ascii_lowercase());
+        }
+        Err(Error::GitObjectNotFound(revision.to_string()))
+    }
+
+    /// Reads a ref, following symbolic refs, or returns `None` if there is
+    /// no such ref.
+    fn read_ref(&self, name: &str) -> Result<Option<Oid>> {
+        let mut name = name.to_string();
+        for _ in 0..MAX_SYMREF_DEPTH {
+            let Some(value) = self.read_loose_ref(&name)? else {
+                return self.read_packed_ref(&name);
+            };
+            match value.strip_prefix("ref: ") {
+                Some(target) if is_ref_name(target) => name = target.to_string(),
+                Some(_) => return Err(invalid("malformed symbolic ref")),
+                None => {
+                    return pack::parse_oid(&value)
+                        .map(Some)
+                        .ok_or_else(|| invalid("malformed ref"))
+                }
+            }
+        }
+        Err(invalid("too many levels of symbolic refs"))
+    }
+
+    /// Reads the file of a loose ref, which is either in the git directory
+    /// of the working tree, as for `HEAD`, or in the common one.
+    fn read_loose_ref(&self, name: &str) -> Result<Option<String>> {
+        for dir in [&self.git_dir, &self.common_dir] {
+            let path = dir.join(name);
+            if !path.is_file() {
+                continue;
+            }
+            let value = fs::read_to_string(path).map_err(Error::FailedGitRequest)?;
+            return Ok(Some(value.trim_end().to_string()));
+        }
+        Ok(None)
+    }
+
+    /// Looks up a ref in `packed-refs`.
+    ///
+    /// See: https://git-scm.com/docs/git-pack-refs
+    fn read_packed_ref(&self, name: &str) -> Result<Option<Oid>> {
+        let refs = match fs::read_to_string(self.common_dir.join("packed-refs")) {
+            Ok(refs) => refs,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::FailedGitRequest(e)),
+        };
+        Ok(find_packed_ref(&refs, name))
+    }
+
+    /// Finds the one object whose ID starts with the given lowercase
+    /// hexadecimal prefix.
+    fn expand(&self, prefix: &str) -> Result<Oid> {
+        let mut found: Vec<Oid> = Vec::new();
+        let (directory, rest) = prefix.split_at(2);
+        for dir in &self.object_dirs {
+            let Ok(entries) = fs::read_dir(dir.join(directory)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                match pack::parse_oid(&format!("{}{}", directory, name)) {
+                    Some(oid) if name.starts_with(rest) && !found.contains(&oid) => found.push(oid),
+                    _ => {}
+                }
+            }
+        }
+
+        // The lowest object ID with the prefix, to search the indexes for:
+        let mut lowest = [0; 20];
+        for (i, digit) in prefix.bytes().enumerate() {
+            let nibble = (digit as char).to_digit(16).unwrap_or_default() as u8;
+            lowest[i / 2] |= nibble << if i % 2 == 0 { 4 } else { 0 };
+        }
+        for path in &self.indexes {
+            let mut index = PackIndex::open(path)?;
+            let (mut low, end) = index.range(lowest[0])?;
+            let mut high = end;
+            while low < high {
+                let middle = low + (high - low) / 2;
+                match index.oid(middle)? < lowest {
+                    true => low = middle + 1,
+                    false => high = middle,
+                }
+            }
+            for i in low..end {
+                let oid = index.oid(i)?;
+                if !pack::to_hex(&oid).starts_with(prefix) {
+                    break;
+                }
+                if !found.contains(&oid) {
+                    found.push(oid);
+                }
+            }
+        }
+
+        match found[..] {
+            [oid] => Ok(oid),
+            [] => Err(Error::GitObjectNotFound(prefix.to_string())),
+            _ => Err(Error::InvalidGitUrl(format!(
+                "ambiguous object ID prefix: {}",
+                prefix
+            ))),
+        }
+    }
+
+    /// Reads an object in full.
+    pub(crate) fn read(&self, oid: &Oid) -> Result<Object> {
+        self.read_at_depth(oid, 0)
+    }
+
+    fn read_at_depth(&self, oid: &Oid, depth: usize) -> Result<Object> {
+        match self.find(oid, depth)? {
+            Found::Stream(kind, size, reader) => Ok(Object {
+                kind,
+                data: read_exactly(reader, size)?,
+            }),
+            Found::Object(object) => Ok(object),
+        }
+    }
+
+    /// Opens a blob, returning its size and a reader of its data, which is
+    /// streamed from disk unless the blob is stored as a delta.
+    pub(crate) fn open_blob(&self, oid: &Oid) -> Result<(u64, Box<dyn Read + Send>)> {
+        let (kind, size, reader): (_, _, Box<dyn Read + Send>) = match self.find(oid, 0)? {
+            Found::Stream(kind, size, reader) => (kind, size, reader),
+            Found::Object(object) => {
+                let size = object.data.len() as u64;
+                (object.kind, size, Box::new(Cursor::new(object.data)))
+            }
+        };
+        if kind != Kind::Blob {
+            return Err(Error::GitObjectNotFound(format!(
+                "{} is not a file",
+                pack::to_hex(oid)
+            )));
+        }
+        Ok((size, reader))
+    }
+
+    /// Finds an object among the loose objects and then the packfiles.
+    fn find(&self, oid: &Oid, depth: usize) -> Result<Found> {
+        let hex = pack::to_hex(oid);
+        for dir in &self.object_dirs {
+            match File::open(dir.join(&hex[..2]).join(&hex[2..])) {
+                Ok(file) => return open_loose(file),
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(Error::FailedGitRequest(e)),
+            }
+        }
+        for path in &self.indexes {
+            if let Some(offset) = PackIndex::open(path)?.find(oid)? {
+                return self.open_packed(&path.with_extension("pack"), offset, depth);
+            }
+        }
+        Err(Error::GitObjectNotFound(hex))
+    }
+
+    /// Opens the packfile entry at the given offset, streaming it if it is
+    /// a whole object, or else reconstructing it from its chain of deltas.
+    fn open_packed(&self, path: &Path, mut offset: u64, depth: usize) -> Result<Found> {
+        let mut deltas = Vec::new();
+        let base = loop {
+            if depth + deltas.len() > MAX_DELTA_DEPTH {
+                return Err(invalid("delta chain too long"));
+            }
+            let (kind, size, reader) = open_entry(path, offset)?;
+            match kind {
+                EntryKind::Object(kind) if deltas.is_empty() => {
+                    return Ok(Found::Stream(kind, size, reader))
+                }
+                EntryKind::Object(kind) => {
+                    break Object {
+                        kind,
+                        data: read_exactly(reader, size)?,
+                    }
+                }
+                EntryKind::OffsetDelta(base) => {
+                    deltas.push(read_exactly(reader, size)?);
+                    offset = base as u64;
+                }
+                EntryKind::RefDelta(base) => {
+                    deltas.push(read_exactly(reader, size)?);
+                    break self.read_at_depth(&base, depth + deltas.len())?;
+                }
+            }
+        };
+        let mut object = base;
+        for delta in deltas.iter().rev() {
+            object = pack::apply_delta(&object, delta).ok_or_else(|| invalid("malformed delta"))?;
+        }
+        Ok(Found::Object(object))
+    }
+}
+
+/// An object found in a repository, either to be streamed, or already
+/// reconstructed from deltas.
+enum Found {
+    Stream(Kind, u64, Box<dyn Read + Send>),
+    Object(Object),
+}
+
+/// Finds the git directory of a working tree, which may be a `.git` file
+/// pointing to it, as for linked working trees and submodules, or else
+/// checks that the path is itself a git directory.
+fn find_git_dir(path: &Path) -> Result<PathBuf> {
+    let dot_git = path.join(".git");
+    if dot_git.is_dir() {
+        return Ok(dot_git);
+    }
+    if let Ok(link) = fs::read_to_string(&dot_git) {
+        if let Some(dir) = link.trim_end().strip_prefix("gitdir: ") {
+            return Ok(path.join(dir));
+        }
+    }
+    if path.join("HEAD").is_file() && path.join("objects").is_dir() {
+        return Ok(path.to_path_buf());
+    }
+    Err(Error::FailedGitRequest(std::io::Error::new(
+        ErrorKind::NotFound,
+        format!("not a git repository: {}", path.display()),
+    )))
+}
+
+/// Checks that a ref name is well-formed, which also keeps it from naming
+/// any file outside the git directory.
+///
+/// See: https://git-scm.com/docs/git-check-ref-format
+fn is_ref_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains("@{")
+        && name.split('/').all(|component| {
+            !component.is_empty() && !component.starts_with('.') && !component.ends_with(".lock")
+        })
+        && !name.contains("..")
+        && !name.ends_with('.')
+        && !name
+            .chars()
+            .any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c))
+}
+
+/// Finds the last value of a key in a section of a git config file, with
+/// the section and key given in lowercase, as they are case-insensitive.
+///
+/// See: https://git-scm.com/docs/git-config#_configuration_file
+fn config_value<'a>(config: &'a str, section: &str, key: &str) -> Option<&'a str> {
+    let mut in_section = false;
+    let mut value = None;
+    for line in config.lines().map(str::trim) {
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header.split([']', ' ', '"']).next().unwrap_or_default();
+            in_section = name.eq_ignore_ascii_case(section);
+        } else if let (true, Some((name, found))) = (in_section, line.split_once('=')) {
+            if name.trim().eq_ignore_ascii_case(key) {
+                value = found.split(['#', ';']).next().map(str::trim);
+            }
+        }
+    }
+    value
+}
+
+/// Finds a ref in the contents of `packed-refs`, whose lines are of the
+/// form `<oid> <name>`, each possibly followed by a `^<oid>` line with the
+/// commit an annotated tag points to.
+fn find_packed_ref(refs: &str, name: &str) -> Option<Oid> {
+    refs.lines().find_map(|line| {
+        let (oid, found) = line.split_once(' ')?;
+        (found == name).then(|| pack::parse_oid(oid))?
+    })
+}
+
+/// Opens a loose object, a zlib stream of a `<kind> <size>\0` header
+/// followed by the data.
+fn open_loose(file: File) -> Result<Found> {
+    let mut reader = ZlibDecoder::new(BufReader::new(file));
+    let mut header = Vec::new();
+    loop {
+        let mut byte = [0];
+        reader
+            .read_exact(&mut byte)
+            .map_err(|_| invalid("malformed loose object"))?;
+        match byte[0] {
+            0 => break,
+            _ if header.len() == MAX_LOOSE_HEADER_LEN => {
+                return Err(invalid("malformed loose object"))
+            }
+            byte => header.push(byte),
+        }
+    }
+    let (kind, size) = std::str::from_utf8(&header)
+        .ok()
+        .and_then(|header| header.split_once(' '))
+        .and_then(|(kind, size)| Some((Kind::from_name(kind.as_bytes())?, size.parse().ok()?)))
+        .ok_or_else(|| invalid("malformed loose object"))?;
+    Ok(Found::Stream(kind, size, Box::new(reader.take(size))))
+}
+
+/// Opens the entry at the given offset in a packfile, returning its kind,
+/// the size of its data, and a reader that inflates the data.
+fn open_entry(path: &Path, offset: u64) -> Result<(EntryKind, u64, Box<dyn Read + Send>)> {
+    let mut file = File::open(path).map_err(Error::FailedGitRequest)?;
+    file.seek(SeekFrom::Start(offset))
+        .map_err(Error::FailedGitRequest)?;
+    let mut reader = BufReader::new(file);
+    let buffer = reader.fill_buf().map_err(Error::FailedGitRequest)?;
+    let mut input = buffer;
+    let offset = usize::try_from(offset).map_err(|_| invalid("packfile too large"))?;
+    let (kind, size) =
+        pack::read_header(&mut input, offset).ok_or_else(|| invalid("malformed packfile"))?;
+    let header_len = buffer.len() - input.len();
+    reader.consume(header_len);
+    let size = size as u64;
+    Ok((kind, size, Box::new(ZlibDecoder::new(reader).take(size))))
+}
+
+/// Reads an object's data, which must be of the given size.
+fn read_exactly(mut reader: impl Read, size: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity((size as usize).min(MAX_PREALLOC));
+    reader
+        .read_to_end(&mut data)
+        .map_err(|_| invalid("malformed object"))?;
+    match data.len() as u64 == size {
+        true => Ok(data),
+        false => Err(invalid("truncated object")),
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidGitData(message.to_string())
+}
+
+/// A version 2 pack index, which lists the objects in a packfile in order
+/// of their IDs, with their offsets.
+///
+/// See: https://git-scm.com/docs/gitformat-pack#_version_2_pack_idx_files_support_packs_larger_than_4_gib_and
+struct PackIndex {
+    file: File,
+    count: u32,
+}
+
+impl PackIndex {
+    /// The size of the header and the fanout table.
+    const TABLES_START: u64 = 8 + 256 * 4;
+
+    fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(Error::FailedGitRequest)?;
+        let mut index = Self { file, count: 0 };
+        let mut header = [0; 8];
+        index.read_at(0, &mut header)?;
+        if header != *b"\xfftOc\0\0\0\x02" {
+            return Err(invalid("unsupported pack index version"));
+        }
+        index.count = index.fanout(255)?;
+        Ok(index)
+    }
+
+    /// Looks up the offset of an object in the packfile.
+    fn find(&mut self, oid: &Oid) -> Result<Option<u64>> {
+        let (mut low, mut high) = self.range(oid[0])?;
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.oid(middle)?.cmp(oid) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => return self.offset(middle).map(Some),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the range of entries whose object IDs start with the given
+    /// byte.
+    fn range(&mut self, first: u8) -> Result<(u32, u32)> {
+        let start = match first {
+            0 => 0,
+            _ => self.fanout(first - 1)?,
+        };
+        let end = self.fanout(first)?.min(self.count);
+        Ok((start.min(end), end))
+    }
+
+    fn fanout(&mut self, byte: u8) -> Result<u32> {
+        let mut count = [0; 4];
+        self.read_at(8 + byte as u64 * 4, &mut count)?;
+        Ok(u32::from_be_bytes(count))
+    }
+
+    fn oid(&mut self, i: u32) -> Result<Oid> {
+        let mut oid = [0; 20];
+        self.read_at(Self::TABLES_START + i as u64 * 20, &mut oid)?;
+        Ok(oid)
+    }
+
+    /// Returns the offset of the given entry, which is in the table of
+    /// 64-bit offsets if the most significant bit of its 32-bit one is set.
+    fn offset(&mut self, i: u32) -> Result<u64> {
+        // The object IDs are followed by their CRC-32s, and then offsets:
+        let offsets = Self::TABLES_START + self.count as u64 * 24;
+        let mut offset = [0; 4];
+        self.read_at(offsets + i as u64 * 4, &mut offset)?;
+        let offset = u32::from_be_bytes(offset);
+        if offset & 0x8000_0000 == 0 {
+            return Ok(offset as u64);
+        }
+        let large_offsets = offsets + self.count as u64 * 4;
+        let mut large_offset = [0; 8];
+        self.read_at(
+            large_offsets + (offset & 0x7fff_ffff) as u64 * 8,
+            &mut large_offset,
+        )?;
+        Ok(u64::from_be_bytes(large_offset))
+    }
+
+    fn read_at(&mut self, position: u64, buffer: &mut [u8]) -> Result<()> {
+        self.file
+            .seek(SeekFrom::Start(position))
+            .and_then(|_| self.file.read_exact(buffer))
+            .map_err(|e| match e.kind() {
+                ErrorKind::UnexpectedEof => invalid("truncated pack index"),
+                _ => Error::FailedGitRequest(e),
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ref_names() {
+        for name in [
+            "HEAD",
+            "main",
+            "v1.0",
+            "refs/heads/feature/x",
+            "origin/main",
+        ] {
+            assert!(is_ref_name(name), "{}", name);
+        }
+        for name in [
+            "",
+            "../config",
+            "refs/../../etc/passwd",
+            "/etc/passwd",
+            "refs/heads/",
+            "refs//heads",
+            ".hidden",
+            "main.lock",
+            "main@{1}",
+            "main~1",
+            "main^",
+            "a b",
+            "a:b",
+            "a\\b",
+            "main.",
+        ] {
+            assert!(!is_ref_name(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn config_values() {
+        let config = "[core]\n\tbare = true\n\
+            [extensions]\n\tobjectFormat = sha256 ; a comment\n\
+            [remote \"origin\"]\n\tobjectformat = sha1\n";
+        assert_eq!(
+            config_value(config, "extensions", "objectformat"),
+            Some("sha256")
+        );
+        assert_eq!(config_value(config, "core", "bare"), Some("true"));
+        assert_eq!(config_value(config, "core", "objectformat"), None);
+    }
+
+    #[test]
+    fn packed_refs() {
+        let refs = "# pack-refs with: peeled fully-peeled sorted \n\
+            1111111111111111111111111111111111111111 refs/heads/main\n\
+            2222222222222222222222222222222222222222 refs/tags/v1.0\n\
+            ^3333333333333333333333333333333333333333\n";
+        assert_eq!(find_packed_ref(refs, "refs/heads/main"), Some([0x11; 20]));
+        assert_eq!(find_packed_ref(refs, "refs/tags/v1.0"), Some([0x22; 20]));
+        assert_eq!(find_packed_ref(refs, "refs/tags/v2.0"), None);
+    }
+}
@@ -0,0 +1,169 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::Read;
+use std::time::SystemTime;
+
+/// An opened resource: a reader together with whatever metadata the
+/// protocol handler was able to learn about it.
+///
+/// All metadata is optional, since not every scheme can provide it.
+#[non_exhaustive]
+pub struct Resource {
+    reader: Box<dyn Read>,
+
+    /// The media type (e.g., `text/plain`), without any parameters.
+    pub content_type: Option<String>,
+
+    /// The character encoding given by the `charset` media type parameter.
+    pub charset: Option<String>,
+
+    /// The content coding applied to the bytes read (e.g., `gzip`).
+    pub content_encoding: Option<String>,
+
+    /// The length of the content in bytes, if known in advance.
+    pub content_length: Option<u64>,
+
+    /// The time at which the resource was last modified.
+    pub last_modified: Option<SystemTime>,
+
+    /// The entity tag identifying this version of the resource.
+    pub etag: Option<String>,
+
+    /// The URL the resource was ultimately fetched from, after redirects.
+    pub final_url: Option<String>,
+
+    /// The IPFS content identifier that the URL resolved to, after following
+    /// any IPNS names. Any path in the URL is resolved relative to it.
+    pub cid: Option<String>,
+}
+
+impl core::fmt::Debug for Resource {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Resource")
+            .field("content_type", &self.content_type)
+            .field("charset", &self.charset)
+            .field("content_encoding", &self.content_encoding)
+            .field("content_length", &self.content_length)
+            .field("last_modified", &self.last_modified)
+            .field("etag", &self.etag)
+            .field("final_url", &self.final_url)
+            .field("cid", &self.cid)
+            .finish_non_exhaustive()
+    }
+}
+
+impl From<Box<dyn Read>> for Resource {
+    fn from(reader: Box<dyn Read>) -> Self {
+        Self::new(reader)
+    }
+}
+
+impl Read for Resource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl Resource {
+    /// Creates a resource without any metadata.
+    pub fn new(reader: impl Read + 'static) -> Self {
+        Self::from_boxed(Box::new(reader))
+    }
+
+    fn from_boxed(reader: Box<dyn Read>) -> Self {
+        Self {
+            reader,
+            content_type: None,
+            charset: None,
+            content_encoding: None,
+            content_length: None,
+            last_modified: None,
+            etag: None,
+            final_url: None,
+            cid: None,
+        }
+    }
+
+    /// Sets the media type and charset from a `Content-Type` value such as
+    /// `text/html; charset=utf-8`.
+    pub fn with_content_type(mut self, content_type: impl AsRef<str>) -> Self {
+        let mut params = content_type.as_ref().split(';');
+        self.content_type = params
+            .next()
+            .map(|essence| essence.trim().to_ascii_lowercase())
+            .filter(|essence| !essence.is_empty());
+        self.charset = params.find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            name.trim()
+                .eq_ignore_ascii_case("charset")
+                .then(|| value.trim().trim_matches('"').to_string())
+        });
+        self
+    }
+
+    pub fn with_content_encoding(mut self, content_encoding: impl Into<Option<String>>) -> Self {
+        self.content_encoding = content_encoding.into();
+        self
+    }
+
+    pub fn with_content_length(mut self, content_length: impl Into<Option<u64>>) -> Self {
+        self.content_length = content_length.into();
+        self
+    }
+
+    pub fn with_last_modified(mut self, last_modified: impl Into<Option<SystemTime>>) -> Self {
+        self.last_modified = last_modified.into();
+        self
+    }
+
+    pub fn with_etag(mut self, etag: impl Into<Option<String>>) -> Self {
+        self.etag = etag.into();
+        self
+    }
+
+    pub fn with_final_url(mut self, final_url: impl Into<Option<String>>) -> Self {
+        self.final_url = final_url.into();
+        self
+    }
+
+    pub fn with_cid(mut self, cid: impl Into<Option<String>>) -> Self {
+        self.cid = cid.into();
+        self
+    }
+
+    /// Returns the reader, discarding the metadata.
+    pub fn into_reader(self) -> Box<dyn Read> {
+        self.reader
+    }
+
+    /// Replaces the reader with a wrapper around it, keeping the metadata.
+    pub(crate) fn map_reader(mut self, f: impl FnOnce(Box<dyn Read>) -> Box<dyn Read>) -> Self {
+        self.reader = f(self.reader);
+        self
+    }
+
+    /// Replaces the reader with a fallible wrapper around it, keeping the
+    /// metadata.
+    #[cfg(feature = "decompress")]
+    pub(crate) fn try_map_reader(
+        mut self,
+        f: impl FnOnce(Box<dyn Read>) -> crate::Result<Box<dyn Read>>,
+    ) -> crate::Result<Self> {
+        self.reader = f(self.reader)?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Cursor;
+
+    #[test]
+    fn content_type() {
+        let resource = Resource::new(Cursor::new(b""));
+        let resource = resource.with_content_type("Text/HTML; Charset=\"UTF-8\"");
+        assert_eq!(resource.content_type.as_deref(), Some("text/html"));
+        assert_eq!(resource.charset.as_deref(), Some("UTF-8"));
+    }
+}
@@ -0,0 +1,227 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::Error;
+use std::time::Duration;
+
+/// The default maximum number of attempts made to open a URL.
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// A policy for retrying requests that fail with a
+/// [transient](Error::is_transient) error.
+///
+/// Between attempts, the handlers wait for an exponentially increasing
+/// delay, starting at the initial backoff and doubling up to the maximum
+/// backoff. With jitter enabled, each delay is randomized to between half
+/// and all of that, so that many clients don't retry in lockstep. A delay
+/// requested by the server with `Retry-After` takes precedence, unless it
+/// exceeds the maximum backoff, in which case the error is returned.
+///
+/// # Examples
+///
+/// ```edition2021,no_run
+/// # use gofer::*;
+/// # use std::time::Duration;
+/// let result = OpenOptions::new()
+///     .retry(
+///         RetryPolicy::new()
+///             .with_max_attempts(5)
+///             .with_initial_backoff(Duration::from_secs(1)),
+///     )
+///     .open("https://www.google.com/robots.txt");
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryPolicy {
+    /// Creates the default policy, making up to [`MAX_ATTEMPTS`] attempts.
+    pub fn new() -> Self {
+        Self {
+            max_attempts: MAX_ATTEMPTS,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+
+    /// Creates a policy that never retries.
+    pub fn none() -> Self {
+        Self::new().with_max_attempts(1)
+    }
+
+    /// Sets the maximum number of attempts, including the first one.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the delay before the first retry.
+    pub fn with_initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the maximum delay between attempts.
+    pub fn with_max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Sets whether delays are randomized.
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub fn initial_backoff(&self) -> Duration {
+        self.initial_backoff
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    pub fn jitter(&self) -> bool {
+        self.jitter
+    }
+
+    /// Returns the delay before the given retry, counting from one, or
+    /// `None` if no further attempts should be made after the error.
+    pub fn delay(&self, retry: u32, error: &Error) -> Option<Duration> {
+        if retry >= self.max_attempts || !error.is_transient() {
+            return None;
+        }
+
+        if let Some(retry_after) = error.retry_after() {
+            return (retry_after <= self.max_backoff).then_some(retry_after);
+        }
+
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff);
+        Some(match self.jitter {
+            true => backoff.mul_f64(0.5 + random_fraction() / 2.0),
+            false => backoff,
+        })
+    }
+
+    /// Runs the given operation until it succeeds, fails with an error that
+    /// is not transient, or the attempts run out.
+    #[cfg(any(
+        feature = "ftp",
+        feature = "gemini",
+        feature = "gopher",
+        feature = "http",
+        feature = "scp",
+        feature = "sftp"
+    ))]
+    pub(crate) fn run<T>(
+        &self,
+        mut operation: impl FnMut() -> crate::Result<T>,
+    ) -> crate::Result<T> {
+        let mut retry = 1;
+        loop {
+            match operation() {
+                Err(error) => match self.delay(retry, &error) {
+                    Some(delay) => std::thread::sleep(delay),
+                    None => return Err(error),
+                },
+                result => return result,
+            }
+            retry += 1;
+        }
+    }
+
+    /// Runs the given operation asynchronously until it succeeds, fails
+    /// with an error that is not transient, or the attempts run out.
+    #[cfg(all(feature = "async", feature = "http"))]
+    pub(crate) async fn run_async<T, F: core::future::Future<Output = crate::Result<T>>>(
+        &self,
+        mut operation: impl FnMut() -> F,
+    ) -> crate::Result<T> {
+        let mut retry = 1;
+        loop {
+            match operation().await {
+                Err(error) => match self.delay(retry, &error) {
+                    Some(delay) => tokio::time::sleep(delay).await,
+                    None => return Err(error),
+                },
+                result => return result,
+            }
+            retry += 1;
+        }
+    }
+}
+
+/// Returns a pseudorandom number in `[0, 1)`, which is good enough for
+/// jitter without depending on a random number generator.
+fn random_fraction() -> f64 {
+    use std::hash::{BuildHasher, RandomState};
+    let bits = RandomState::new().hash_one(std::time::SystemTime::now());
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(all(test, feature = "http"))]
+mod test {
+    use super::*;
+
+    fn status(status: u16, retry_after: Option<Duration>) -> Error {
+        Error::HttpStatus {
+            status,
+            url: "https://example.org/".into(),
+            body_excerpt: String::new(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = RetryPolicy::new()
+            .with_max_attempts(4)
+            .with_max_backoff(Duration::from_millis(1500))
+            .with_jitter(false);
+        let error = status(503, None);
+        let delays: Vec<_> = (1..=4).map(|retry| policy.delay(retry, &error)).collect();
+        assert_eq!(
+            delays,
+            [
+                Some(Duration::from_millis(500)),
+                Some(Duration::from_millis(1000)),
+                Some(Duration::from_millis(1500)),
+                None,
+            ]
+        );
+
+        let policy = policy.with_jitter(true);
+        let delay = policy.delay(2, &error).unwrap();
+        assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1000));
+
+        assert_eq!(policy.delay(1, &status(404, None)), None);
+    }
+
+    #[test]
+    fn retry_after() {
+        let policy = RetryPolicy::new().with_max_backoff(Duration::from_secs(10));
+        let error = status(429, Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(1, &error), Some(Duration::from_secs(2)));
+
+        let error = status(429, Some(Duration::from_secs(60)));
+        assert_eq!(policy.delay(1, &error), None);
+    }
+}
@@ -1,5 +1,8 @@
 // This is free and unencumbered software released into the public domain.
 
+#[cfg(feature = "archive")]
+pub mod archive;
+
 #[cfg(feature = "data")]
 pub mod data;
 
@@ -9,9 +12,15 @@ pub mod file;
 #[cfg(any(feature = "ftp", feature = "ftps"))]
 pub mod ftp;
 
+#[cfg(feature = "gemini")]
+pub mod gemini;
+
 #[cfg(feature = "git")]
 pub mod git;
 
+#[cfg(feature = "gopher")]
+pub mod gopher;
+
 #[cfg(any(feature = "http", feature = "https"))]
 pub mod http;
 
@@ -21,5 +30,11 @@ pub mod ipfs;
 #[cfg(feature = "scp")]
 pub mod scp;
 
+#[cfg(feature = "sftp")]
+pub mod sftp;
+
+#[cfg(any(feature = "scp", feature = "sftp"))]
+mod ssh;
+
 #[cfg(feature = "stdin")]
 pub mod stdin;
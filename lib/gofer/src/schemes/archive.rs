@@ -0,0 +1,388 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{
+    open_seekable_url_with, open_url_with, Cursor, Error, OpenOptions, Read, Resource, Result, Url,
+};
+use percent_encoding::percent_decode_str;
+use std::{
+    io::{self, Seek, SeekFrom},
+    time::{Duration, SystemTime},
+};
+use zip::{CompressionMethod, ZipArchive};
+
+/// The supported archive formats.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ArchiveFormat {
+    /// Zip archives (`zip:`).
+    Zip,
+    /// Tar archives, optionally gzip-compressed (`tar:`).
+    Tar,
+}
+
+/// Opens a single member of a remote archive.
+///
+/// The archive is given by an inner URL, which is opened through the
+/// [`default_registry`](crate::default_registry), and the member by a path
+/// within the archive. Both of these forms are supported:
+///
+/// - `zip:https://example.org/archive.zip!/path/in/archive`
+/// - `zip+https://example.org/archive.zip#path/in/archive`
+///
+/// Zip archives are read with random access wherever the inner URL
+/// supports [`open_seekable`](crate::open_seekable), and otherwise buffered
+/// in memory. Tar archives are always streamed.
+///
+/// See: https://en.wikipedia.org/wiki/ZIP_(file_format)
+/// See: https://en.wikipedia.org/wiki/Tar_(computing)
+/// See: https://docs.oracle.com/javase/8/docs/api/java/net/JarURLConnection.html
+pub fn open<'a, 'b>(
+    url: &'a Url<'b>,
+    format: ArchiveFormat,
+    options: &OpenOptions,
+) -> Result<Resource> {
+    let (inner_url, member) = parse_url(url)?;
+
+    // Any size limit and expected digest apply to the member, not the archive:
+    let mut inner_options = options.clone();
+    inner_options.max_size(None);
+    #[cfg(feature = "integrity")]
+    inner_options.expect_digest(None);
+
+    let resource = match format {
+        ArchiveFormat::Zip => match open_seekable_url_with(&inner_url, &inner_options) {
+            Ok(reader) => open_zip_member(reader, &member)?,
+            Err(Error::NotSeekable(_)) => {
+                open_zip_member(buffer(&inner_url, &inner_options)?, &member)?
+            }
+            #[cfg(any(feature = "http", feature = "https"))]
+            Err(Error::RangeNotSupported(_)) => {
+                open_zip_member(buffer(&inner_url, &inner_options)?, &member)?
+            }
+            Err(error) => return Err(error),
+        },
+        ArchiveFormat::Tar => open_tar_member(open_url_with(&inner_url, &inner_options)?, &member)?,
+    };
+
+    // The final URL is given in the `!/` form, so that it ends with the
+    // path of the member:
+    let scheme = match format {
+        ArchiveFormat::Zip => "zip",
+        ArchiveFormat::Tar => "tar",
+    };
+    let final_url = format!("{}:{}!/{}", scheme, inner_url, member);
+    Ok(resource.with_final_url(final_url))
+}
+
+/// Reads the whole of an archive into memory.
+fn buffer(url: &Url, options: &OpenOptions) -> Result<Cursor<Vec<u8>>> {
+    let mut buffer = Vec::new();
+    open_url_with(url, options)?
+        .read_to_end(&mut buffer)
+        .map_err(Error::FailedArchiveRead)?;
+    Ok(Cursor::new(buffer))
+}
+
+/// Splits an archive URL into the inner URL and the member path.
+fn parse_url(url: &Url) -> Result<(Url<'static>, String)> {
+    let invalid = || Error::InvalidArchiveUrl(url.to_string());
+
+    let (scheme, rest) = url.as_str().split_once(':').ok_or_else(invalid)?;
+    let (inner, member) = match scheme.split_once('+') {
+        Some((_, inner_scheme)) => {
+            let member = url.fragment_str().ok_or_else(invalid)?;
+            let rest = rest.split_once('#').map_or(rest, |(rest, _)| rest);
+            (format!("{}:{}", inner_scheme, rest), member)
+        }
+        None => {
+            let (inner, member) = rest.rsplit_once("!/").ok_or_else(invalid)?;
+            (inner.to_string(), member)
+        }
+    };
+
+    let member = percent_decode_str(member).decode_utf8_lossy();
+    let member = normalize(&member).to_string();
+    if member.is_empty() {
+        return Err(invalid());
+    }
+
+    let inner = inner.parse::<Url>().map_err(|_| invalid())?;
+    Ok((inner, member))
+}
+
+/// Strips any leading `./` and `/` from a member path.
+fn normalize(path: &str) -> &str {
+    let mut path = path;
+    loop {
+        match path.strip_prefix("./").or_else(|| path.strip_prefix('/')) {
+            Some(rest) => path = rest,
+            None => return path,
+        }
+    }
+}
+
+fn open_zip_member<R: Read + Seek + 'static>(reader: R, member: &str) -> Result<Resource> {
+    let not_found = || Error::MissingArchiveMember(member.to_string());
+
+    // See: https://docs.rs/zip/latest/zip/read/struct.ZipArchive.html
+    let mut archive = ZipArchive::new(reader).map_err(|e| Error::FailedArchiveRead(e.into()))?;
+    let index = archive.index_for_name(member).ok_or_else(not_found)?;
+
+    // The member is read directly from the underlying reader, so that it
+    // can be streamed without borrowing from the archive:
+    let file = archive
+        .by_index_raw(index)
+        .map_err(|e| Error::FailedArchiveRead(e.into()))?;
+    if file.is_dir() {
+        return Err(not_found());
+    }
+    let unsupported = |what: &str| {
+        Error::FailedArchiveRead(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} zip member: {}", what, member),
+        ))
+    };
+    if file.encrypted() {
+        return Err(unsupported("encrypted"));
+    }
+    let (compression, data_start, compressed_size, size, crc32) = (
+        file.compression(),
+        file.data_start(),
+        file.compressed_size(),
+        file.size(),
+        file.crc32(),
+    );
+    drop(file);
+
+    let mut reader = archive.into_inner();
+    reader
+        .seek(SeekFrom::Start(data_start))
+        .map_err(Error::FailedArchiveRead)?;
+    let data = reader.take(compressed_size);
+
+    let data: Box<dyn Read> = match compression {
+        CompressionMethod::Stored => Box::new(data),
+        // See: https://docs.rs/flate2/latest/flate2/read/struct.DeflateDecoder.html
+        CompressionMethod::Deflated => Box::new(flate2::read::DeflateDecoder::new(data)),
+        _ => return Err(unsupported("unsupported compression method for")),
+    };
+
+    Ok(Resource::new(CrcCheck::new(data, crc32)).with_content_length(size))
+}
+
+/// A reader that checks the CRC-32 of a zip member at EOF.
+struct CrcCheck {
+    inner: Box<dyn Read>,
+    expected: u32,
+    crc: flate2::Crc,
+}
+
+impl CrcCheck {
+    fn new(inner: Box<dyn Read>, expected: u32) -> Self {
+        Self {
+            inner,
+            expected,
+            crc: flate2::Crc::new(),
+        }
+    }
+}
+
+impl Read for CrcCheck {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.crc.update(&buf[..n]);
+        if n == 0 && !buf.is_empty() && self.crc.sum() != self.expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "zip member failed its CRC-32 check",
+            ));
+        }
+        Ok(n)
+    }
+}
+
+/// The size of a tar header or data block.
+const TAR_BLOCK_LEN: u64 = 512;
+
+fn open_tar_member(resource: Resource, member: &str) -> Result<Resource> {
+    let mut reader: Box<dyn Read> = Box::new(resource);
+
+    // Gzip-compressed archives are decompressed, unless this was already
+    // done by the `decompress` feature:
+    let mut magic = Vec::with_capacity(2);
+    reader
+        .by_ref()
+        .take(2)
+        .read_to_end(&mut magic)
+        .map_err(Error::FailedArchiveRead)?;
+    let gzipped = magic == [0x1F, 0x8B];
+    let reader = Cursor::new(magic).chain(reader);
+    let mut reader: Box<dyn Read> = match gzipped {
+        true => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        false => Box::new(reader),
+    };
+
+    // See: https://www.gnu.org/software/tar/manual/html_node/Standard.html
+    // See: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/pax.html
+    let mut long_name: Option<String> = None;
+    loop {
+        let mut header = [0u8; TAR_BLOCK_LEN as usize];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(Error::FailedArchiveRead(e)),
+        }
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let size = parse_octal(&header[124..136]);
+        let typeflag = header[156];
+        let padded_size = size.div_ceil(TAR_BLOCK_LEN) * TAR_BLOCK_LEN;
+
+        match typeflag {
+            // GNU long name, or PAX extended header:
+            b'L' | b'x' => {
+                let mut data = Vec::new();
+                reader
+                    .by_ref()
+                    .take(padded_size)
+                    .read_to_end(&mut data)
+                    .map_err(Error::FailedArchiveRead)?;
+                data.truncate(size as usize);
+                long_name = match typeflag {
+                    b'L' => Some(c_string(&data)),
+                    _ => pax_path(&data).or(long_name),
+                };
+            }
+            _ => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let name = c_string(&header[0..100]);
+                    match &header[257..262] == b"ustar" {
+                        true => match c_string(&header[345..500]) {
+                            prefix if prefix.is_empty() => name,
+                            prefix => format!("{}/{}", prefix, name),
+                        },
+                        false => name,
+                    }
+                });
+
+                if matches!(typeflag, b'0' | b'\0' | b'7') && normalize(&name) == member {
+                    let mtime = parse_octal(&header[136..148]);
+                    return Ok(Resource::new(reader.take(size))
+                        .with_content_length(size)
+                        .with_last_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime)));
+                }
+
+                io::copy(&mut reader.by_ref().take(padded_size), &mut io::sink())
+                    .map_err(Error::FailedArchiveRead)?;
+            }
+        }
+    }
+
+    Err(Error::MissingArchiveMember(member.to_string()))
+}
+
+fn parse_octal(field: &[u8]) -> u64 {
+    field
+        .iter()
+        .skip_while(|&&b| b == b' ')
+        .take_while(|&&b| (b'0'..=b'7').contains(&b))
+        .fold(0, |n, &b| n * 8 + u64::from(b - b'0'))
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Extracts the `path` record from a PAX extended header.
+fn pax_path(data: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter_map(|record| record.split_once(' ')?.1.split_once('='))
+        .find(|(key, _)| *key == "path")
+        .map(|(_, value)| value.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn url_parsing() {
+        let url = "zip:https://example.org/a.zip!/dir/file%20name.txt"
+            .parse::<Url>()
+            .unwrap();
+        let (inner, member) = parse_url(&url).unwrap();
+        assert_eq!(inner.as_str(), "https://example.org/a.zip");
+        assert_eq!(member, "dir/file name.txt");
+
+        let url = "tar+https://example.org/a.tar.gz#./dir/file.txt"
+            .parse::<Url>()
+            .unwrap();
+        let (inner, member) = parse_url(&url).unwrap();
+        assert_eq!(inner.as_str(), "https://example.org/a.tar.gz");
+        assert_eq!(member, "dir/file.txt");
+
+        let url = "zip:https://example.org/a.zip".parse::<Url>().unwrap();
+        assert!(parse_url(&url).is_err());
+    }
+
+    #[test]
+    fn zip_member() {
+        use std::io::Write;
+        use zip::{write::SimpleFileOptions, ZipWriter};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, method) in [
+            ("stored.txt", CompressionMethod::Stored),
+            ("dir/deflated.txt", CompressionMethod::Deflated),
+        ] {
+            let options = SimpleFileOptions::default().compression_method(method);
+            writer.start_file(name, options).unwrap();
+            writer.write_all(b"hello hello hello").unwrap();
+        }
+        let archive = writer.finish().unwrap().into_inner();
+
+        for name in ["stored.txt", "dir/deflated.txt"] {
+            let mut member = open_zip_member(Cursor::new(archive.clone()), name).unwrap();
+            assert_eq!(member.content_length, Some(17));
+            let mut output = String::new();
+            member.read_to_string(&mut output).unwrap();
+            assert_eq!(output, "hello hello hello");
+        }
+
+        assert!(matches!(
+            open_zip_member(Cursor::new(archive), "missing.txt"),
+            Err(Error::MissingArchiveMember(_))
+        ));
+    }
+
+    #[test]
+    fn tar_member() {
+        let mut archive = Vec::new();
+        for (name, contents) in [("a.txt", &b"first"[..]), ("dir/b.txt", &b"second"[..])] {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+            header[156] = b'0';
+            archive.extend_from_slice(&header);
+            archive.extend_from_slice(contents);
+            archive.resize(archive.len().div_ceil(512) * 512, 0);
+        }
+        archive.resize(archive.len() + 1024, 0);
+
+        let resource = Resource::new(Cursor::new(archive.clone()));
+        let mut member = open_tar_member(resource, "dir/b.txt").unwrap();
+        assert_eq!(member.content_length, Some(6));
+        let mut output = String::new();
+        member.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "second");
+
+        let resource = Resource::new(Cursor::new(archive));
+        assert!(matches!(
+            open_tar_member(resource, "missing.txt"),
+            Err(Error::MissingArchiveMember(_))
+        ));
+    }
+}
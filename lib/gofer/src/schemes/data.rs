@@ -1,17 +1,75 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{Cursor, Read, Result, Url};
+use crate::{Cursor, OpenOptions, ReadSeek, Resource, Result, Url};
 use data_url::DataUrl;
 
 /// See: https://en.wikipedia.org/wiki/Data_URI_scheme
 /// See: https://fetch.spec.whatwg.org/#data-urls
 /// See: https://www.rfc-editor.org/rfc/rfc2397.html
-pub fn open<'a, 'b>(url: &'a Url<'b>) -> Result<Box<dyn Read>> {
+pub fn open<'a, 'b>(url: &'a Url<'b>, _options: &OpenOptions) -> Result<Resource> {
     // See: https://docs.rs/data-url/latest/data_url/struct.DataUrl.html#method.process
     let url = DataUrl::process(url.as_str())?;
 
     // See: https://docs.rs/data-url/latest/data_url/struct.DataUrl.html#method.decode_to_vec
     let (body, _) = url.decode_to_vec()?;
 
+    // See: https://docs.rs/data-url/latest/data_url/mime/struct.Mime.html
+    let content_type = url.mime_type().to_string();
+    let content_length = body.len() as u64;
+
+    Ok(Resource::new(Cursor::new(body))
+        .with_content_type(content_type)
+        .with_content_length(content_length))
+}
+
+/// See: https://en.wikipedia.org/wiki/Data_URI_scheme
+pub fn open_seekable<'a, 'b>(
+    url: &'a Url<'b>,
+    _options: &OpenOptions,
+) -> Result<Box<dyn ReadSeek>> {
+    let url = DataUrl::process(url.as_str())?;
+    let (body, _) = url.decode_to_vec()?;
+
     Ok(Box::new(Cursor::new(body)))
 }
+
+/// See: https://en.wikipedia.org/wiki/Data_URI_scheme
+#[cfg(feature = "async")]
+pub async fn open_async<'a, 'b>(
+    url: &'a Url<'b>,
+    _options: &OpenOptions,
+) -> Result<crate::r#async::ByteStream> {
+    let url = DataUrl::process(url.as_str())?;
+    let (body, _) = url.decode_to_vec()?;
+
+    let chunk = bytes::Bytes::from(body);
+    Ok(Box::pin(futures_util::stream::once(async { Ok(chunk) })))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn metadata() {
+        let url = "data:text/plain;charset=US-ASCII,hello"
+            .parse::<Url>()
+            .unwrap();
+        let resource = open(&url, &OpenOptions::new()).unwrap();
+        assert_eq!(resource.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(resource.charset.as_deref(), Some("US-ASCII"));
+        assert_eq!(resource.content_length, Some(5));
+    }
+
+    #[test]
+    fn seek() {
+        use std::io::{Read, SeekFrom};
+        let url = "data:,hello%20world".parse::<Url>().unwrap();
+        let mut reader = open_seekable(&url, &OpenOptions::new()).unwrap();
+        assert_eq!(reader.seek(SeekFrom::End(-5)).unwrap(), 6);
+
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "world");
+    }
+}
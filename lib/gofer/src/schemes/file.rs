@@ -1,11 +1,11 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{Error, Read, Result, Url};
+use crate::{Error, OpenOptions, ReadSeek, Resource, Result, Url};
 use std::fs::File;
 
 /// See: https://en.wikipedia.org/wiki/File_URI_scheme
 /// See: https://www.rfc-editor.org/rfc/rfc8089.html
-pub fn open<'a, 'b>(url: &'a Url<'b>) -> Result<Box<dyn Read>> {
+pub fn open<'a, 'b>(url: &'a Url<'b>, _options: &OpenOptions) -> Result<Resource> {
     // See: https://docs.rs/dogma/latest/dogma/enums/enum.Iri.html#method.to_path
     let path = url
         .to_path()
@@ -15,5 +15,39 @@ pub fn open<'a, 'b>(url: &'a Url<'b>) -> Result<Box<dyn Read>> {
     // See: https://doc.rust-lang.org/stable/std/fs/struct.OpenOptions.html#method.open
     let file = File::open(path)?;
 
-    Ok(Box::new(file))
+    // See: https://doc.rust-lang.org/stable/std/fs/struct.Metadata.html
+    let metadata = file.metadata()?;
+
+    Ok(Resource::new(file)
+        .with_content_length(metadata.is_file().then_some(metadata.len()))
+        .with_last_modified(metadata.modified().ok())
+        .with_final_url(url.to_string()))
+}
+
+/// See: https://en.wikipedia.org/wiki/File_URI_scheme
+pub fn open_seekable<'a, 'b>(
+    url: &'a Url<'b>,
+    _options: &OpenOptions,
+) -> Result<Box<dyn ReadSeek>> {
+    let path = url
+        .to_path()
+        .ok_or_else(|| Error::InvalidFileUrl(url.to_string()))?;
+
+    Ok(Box::new(File::open(path)?))
+}
+
+/// See: https://en.wikipedia.org/wiki/File_URI_scheme
+#[cfg(feature = "async")]
+pub async fn open_async<'a, 'b>(
+    url: &'a Url<'b>,
+    _options: &OpenOptions,
+) -> Result<crate::r#async::ByteStream> {
+    let path = url
+        .to_path()
+        .ok_or_else(|| Error::InvalidFileUrl(url.to_string()))?;
+
+    // See: https://docs.rs/tokio/latest/tokio/fs/struct.File.html#method.open
+    let file = tokio::fs::File::open(path).await?;
+
+    Ok(Box::pin(tokio_util::io::ReaderStream::new(file)))
 }
@@ -1,13 +1,121 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{Error, Read, Result, Url};
+use crate::{readers::Resume, Error, OpenOptions, Read, ReadSeek, Resource, Result, Url};
 use percent_encoding::percent_decode;
-use std::borrow::Cow;
-use suppaftp::FtpStream;
+use std::{
+    borrow::Cow,
+    io::{Seek, SeekFrom},
+    time::{Duration, SystemTime},
+};
+use suppaftp::{
+    types::{FileType, FtpError},
+    Mode,
+};
+
+#[cfg(not(feature = "ftps"))]
+type FtpStream = suppaftp::FtpStream;
+
+#[cfg(feature = "ftps")]
+type FtpStream = suppaftp::RustlsFtpStream;
+
+/// The transport security used for an FTP connection.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum FtpSecurity {
+    /// Plaintext FTP (`ftp:`).
+    #[default]
+    None,
+
+    /// Explicit FTPS, upgrading the control connection with `AUTH TLS`
+    /// (`ftpes:`).
+    #[cfg(feature = "ftps")]
+    Explicit,
+
+    /// Implicit FTPS, speaking TLS from the outset (`ftps:`).
+    #[cfg(feature = "ftps")]
+    Implicit,
+}
+
+impl FtpSecurity {
+    fn default_port(&self) -> u16 {
+        match self {
+            #[cfg(feature = "ftps")]
+            FtpSecurity::Implicit => 990,
+            _ => 21,
+        }
+    }
+}
 
 /// See: https://en.wikipedia.org/wiki/FTP
 /// See: https://en.wikipedia.org/wiki/FTPS
-pub fn open<'a, 'b>(url: &'a Url<'b>, _secure: bool) -> Result<Box<dyn Read>> {
+/// See: https://www.rfc-editor.org/rfc/rfc4217.html
+pub fn open<'a, 'b>(
+    url: &'a Url<'b>,
+    security: FtpSecurity,
+    options: &OpenOptions,
+) -> Result<Resource> {
+    let offset = options.get_ftp_resume_offset();
+
+    let (reader, size, last_modified) = options.get_retry().run(|| {
+        let (mut stream, basename) = login(url, security, options)?;
+
+        // Not all servers support `SIZE` and `MDTM`, so these are best effort:
+        let size = stream.size(&basename).ok().map(|size| size as u64);
+        let last_modified = stream
+            .mdtm(&basename)
+            .ok()
+            .and_then(|mtime| u64::try_from(mtime.and_utc().timestamp()).ok())
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+
+        let reader: Box<dyn Read> = Box::new(retrieve(stream, &basename, offset)?);
+        Ok((reader, size, last_modified))
+    })?;
+
+    // After a dropped connection, log in again and resume with `REST`:
+    let attempts = options.get_max_resume_attempts();
+    let reader: Box<dyn Read> = match attempts {
+        0 => reader,
+        _ => {
+            let (url, options) = (url.to_string(), options.clone());
+            Box::new(Resume::new(reader, attempts, move |position| {
+                let url = url.parse::<Url>()?;
+                let (stream, basename) = login(&url, security, &options)?;
+                Ok(Box::new(retrieve(stream, &basename, offset + position)?))
+            }))
+        }
+    };
+
+    Ok(Resource::new(reader)
+        .with_content_length(size.map(|size| size.saturating_sub(offset)))
+        .with_last_modified(last_modified)
+        .with_final_url(url.to_string()))
+}
+
+/// Opens an FTP file for random access, resuming the transfer with `REST`
+/// at the new position after each seek.
+///
+/// See: https://www.rfc-editor.org/rfc/rfc3659.html#section-5
+pub fn open_seekable<'a, 'b>(
+    url: &'a Url<'b>,
+    security: FtpSecurity,
+    options: &OpenOptions,
+) -> Result<Box<dyn ReadSeek>> {
+    let (mut stream, basename) = options.get_retry().run(|| login(url, security, options))?;
+
+    // Seeking relative to the end requires `SIZE`:
+    let length = stream.size(&basename).ok().map(|size| size as u64);
+
+    Ok(Box::new(FtpSeeker {
+        stream: Some(stream),
+        basename,
+        length,
+        position: 0,
+        data: None,
+    }))
+}
+
+/// Connects and logs in to the server of the given URL, changing to the
+/// directory of the file and returning the file name.
+fn login(url: &Url, security: FtpSecurity, options: &OpenOptions) -> Result<(FtpStream, String)> {
     let authority = url
         .authority()
         .ok_or_else(|| Error::InvalidFtpUrl(url.to_string()))?;
@@ -33,17 +141,306 @@ pub fn open<'a, 'b>(url: &'a Url<'b>, _secure: bool) -> Result<Box<dyn Read>> {
 
     let dirname = path.join("/");
 
-    let mut stream = FtpStream::connect(&authority)?;
+    let host = authority.host_str();
+    let port = authority.port().unwrap_or(security.default_port());
+
+    let mut stream = connect(host, port, security, options)?;
+
+    stream
+        .get_ref()
+        .set_read_timeout(options.get_read_timeout())
+        .map_err(FtpError::ConnectionError)?;
+
+    if !options.get_ftp_passive() {
+        stream.set_mode(Mode::Active);
+    }
 
     stream.login(username, password.as_ref())?;
 
-    if dirname != "" {
+    if !dirname.is_empty() {
         stream.cwd(dirname)?;
     }
 
-    let buffer = stream.retr_as_buffer(&basename)?;
+    stream.transfer_type(FileType::Binary)?;
+
+    Ok((stream, basename))
+}
+
+/// Starts retrieving the given file from `offset` onwards.
+fn retrieve(mut stream: FtpStream, basename: &str, offset: u64) -> Result<FtpReader> {
+    if offset > 0 {
+        stream.resume_transfer(offset as usize)?;
+    }
+
+    let data = stream.retr_as_stream(basename)?;
 
-    stream.quit()?;
+    Ok(FtpReader {
+        stream: Some(stream),
+        data: Some(Box::new(data)),
+    })
+}
 
-    Ok(Box::new(buffer))
+/// A reader over an FTP data connection that owns the control connection,
+/// completing the transfer and logging out at EOF, or aborting the transfer
+/// if dropped early.
+struct FtpReader {
+    stream: Option<FtpStream>,
+    data: Option<Box<dyn Read>>,
+}
+
+impl Read for FtpReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(data) = self.data.as_mut() else {
+            return Ok(0);
+        };
+        let n = data.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.finish().map_err(|e| match e {
+                // The data connection closed without the server confirming
+                // the transfer, so the contents may be truncated:
+                FtpError::BadResponse => {
+                    std::io::Error::new(std::io::ErrorKind::UnexpectedEof, Error::from(e))
+                }
+                e => Error::from(e).into(),
+            })?;
+        }
+        Ok(n)
+    }
+}
+
+impl FtpReader {
+    fn finish(&mut self) -> core::result::Result<(), FtpError> {
+        if let (Some(mut stream), Some(data)) = (self.stream.take(), self.data.take()) {
+            stream.finalize_retr_stream(data)?;
+            stream.quit()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FtpReader {
+    fn drop(&mut self) {
+        if let Some(mut stream) = self.stream.take() {
+            if let Some(data) = self.data.take() {
+                let _ = stream.abort(data);
+            }
+            let _ = stream.quit();
+        }
+    }
+}
+
+/// A seekable reader over an FTP file, which opens a new data connection
+/// from the current position whenever it is read after seeking.
+struct FtpSeeker {
+    stream: Option<FtpStream>,
+    basename: String,
+    length: Option<u64>,
+    position: u64,
+    data: Option<Box<dyn Read>>,
+}
+
+impl Read for FtpSeeker {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let at_end = self.length.is_some_and(|length| self.position >= length);
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(0);
+        };
+        if at_end {
+            // Complete the transfer once the known length has been read:
+            if let Some(data) = self.data.take() {
+                stream.finalize_retr_stream(data).map_err(Error::from)?;
+            }
+            return Ok(0);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let data = match self.data.as_mut() {
+            Some(data) => data,
+            None => {
+                if self.position > 0 {
+                    stream
+                        .resume_transfer(self.position as usize)
+                        .map_err(Error::from)?;
+                }
+                let data = stream.retr_as_stream(&self.basename).map_err(Error::from)?;
+                self.data.insert(Box::new(data))
+            }
+        };
+
+        let n = data.read(buf)?;
+        if n == 0 {
+            if let Some(data) = self.data.take() {
+                stream.finalize_retr_stream(data).map_err(Error::from)?;
+            }
+            self.length.get_or_insert(self.position);
+        }
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for FtpSeeker {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => {
+                let length = self.length.ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::Unsupported,
+                        "the server does not report the file size",
+                    )
+                })?;
+                length.checked_add_signed(offset)
+            }
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        if position != self.position {
+            if let (Some(stream), Some(data)) = (self.stream.as_mut(), self.data.take()) {
+                stream.abort(data).map_err(Error::from)?;
+            }
+            self.position = position;
+        }
+        Ok(position)
+    }
+}
+
+impl Drop for FtpSeeker {
+    fn drop(&mut self) {
+        if let Some(mut stream) = self.stream.take() {
+            if let Some(data) = self.data.take() {
+                let _ = stream.abort(data);
+            }
+            let _ = stream.quit();
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "ftps"), allow(unused_variables))]
+fn connect(
+    host: &str,
+    port: u16,
+    security: FtpSecurity,
+    options: &OpenOptions,
+) -> Result<FtpStream> {
+    #[cfg(feature = "ftps")]
+    if security == FtpSecurity::Implicit {
+        return connect_implicit(host, port, options);
+    }
+
+    let stream = crate::net::connect(host, port, options).map_err(FtpError::ConnectionError)?;
+    let stream = FtpStream::connect_with_stream(stream)?;
+
+    #[cfg(feature = "ftps")]
+    if security == FtpSecurity::Explicit {
+        // See: https://docs.rs/suppaftp/latest/suppaftp/struct.ImplFtpStream.html#method.into_secure
+        return Ok(stream.into_secure(tls::connector(options)?, host)?);
+    }
+
+    Ok(stream)
+}
+
+/// Connects to a server over implicit FTPS, which starts with the TLS
+/// handshake.
+///
+/// As the connection can't be handed over to `suppaftp` before then, the
+/// connect timeout, if any, bounds the whole of connecting to each of the
+/// host's addresses in turn, the handshake, and the greeting.
+#[cfg(feature = "ftps")]
+fn connect_implicit(host: &str, port: u16, options: &OpenOptions) -> Result<FtpStream> {
+    // See: https://docs.rs/suppaftp/latest/suppaftp/struct.ImplFtpStream.html#method.connect_secure_implicit
+    let connector = tls::connector(options)?;
+    let Some(timeout) = options.get_connect_timeout() else {
+        return Ok(FtpStream::connect_secure_implicit(
+            (host, port),
+            connector,
+            host,
+        )?);
+    };
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let host = host.to_string();
+    std::thread::spawn(move || {
+        let result = FtpStream::connect_secure_implicit((host.as_str(), port), connector, &host);
+        let _ = sender.send(result);
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(FtpError::ConnectionError(std::io::ErrorKind::TimedOut.into()).into()),
+    }
+}
+
+#[cfg(feature = "ftps")]
+mod tls {
+    use crate::{OpenOptions, Result};
+    use std::sync::Arc;
+    use suppaftp::{
+        rustls::{
+            crypto::ring,
+            pki_types::{pem::PemObject, CertificateDer},
+            ClientConfig, RootCertStore,
+        },
+        FtpError, RustlsConnector,
+    };
+
+    /// Builds a TLS connector that verifies certificates against the
+    /// platform's native roots, plus any configured root certificates.
+    pub(super) fn connector(options: &OpenOptions) -> Result<RustlsConnector> {
+        let mut roots = RootCertStore::empty();
+
+        if options.get_tls_built_in_root_certs() {
+            // See: https://docs.rs/rustls-native-certs/latest/rustls_native_certs/fn.load_native_certs.html
+            let native = rustls_native_certs::load_native_certs();
+            roots.add_parsable_certificates(native.certs);
+        }
+
+        for pem in options.get_root_certificates() {
+            for cert in CertificateDer::pem_slice_iter(pem) {
+                let cert = cert.map_err(|e| FtpError::SecureError(e.to_string()))?;
+                roots
+                    .add(cert)
+                    .map_err(|e| FtpError::SecureError(e.to_string()))?;
+            }
+        }
+
+        // See: https://docs.rs/rustls/latest/rustls/client/struct.ClientConfig.html
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| FtpError::SecureError(e.to_string()))?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        Ok(RustlsConnector::from(Arc::new(config)))
+    }
+}
+
+#[cfg(all(test, feature = "ftps"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn implicit_connect_timeout() {
+        // The listener accepts connections, but never completes a handshake:
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut options = OpenOptions::new();
+        options.connect_timeout(Duration::from_millis(100));
+        let started = std::time::Instant::now();
+        let result = connect("127.0.0.1", port, FtpSecurity::Implicit, &options);
+        assert!(matches!(
+            result,
+            Err(Error::FailedFtpRequest(FtpError::ConnectionError(error)))
+                if error.kind() == std::io::ErrorKind::TimedOut
+        ));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
 }
@@ -0,0 +1,413 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{Error, OpenOptions, Read, Resource, Result, Url};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{ring, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme, StreamOwned,
+};
+use sha2::{Digest as _, Sha256};
+use std::{
+    env,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The default Gemini port.
+pub const DEFAULT_PORT: u16 = 1965;
+
+/// The content type of responses that don't specify one.
+const DEFAULT_CONTENT_TYPE: &str = "text/gemini; charset=utf-8";
+
+type GeminiStream = StreamOwned<ClientConnection, TcpStream>;
+
+/// Requests a resource from a Gemini server, following redirects.
+///
+/// Server certificates are trusted on first use: the fingerprint of each
+/// server's certificate is recorded in the known hosts file (see
+/// [`OpenOptions::gemini_known_hosts`]), and later connections fail if the
+/// server presents a different certificate.
+///
+/// See: https://en.wikipedia.org/wiki/Gemini_(protocol)
+/// See: https://geminiprotocol.net/docs/protocol-specification.gmi
+pub fn open<'a, 'b>(url: &'a Url<'b>, options: &OpenOptions) -> Result<Resource> {
+    let mut url = url.clone();
+    let mut redirects = 0;
+    loop {
+        let (stream, status, meta) = options.get_retry().run(|| {
+            let (stream, status, meta) = request(&url, options)?;
+            // Temporary failures, such as 44 SLOW DOWN, are retried:
+            match status / 10 {
+                4 => Err(Error::GeminiTemporaryFailure {
+                    status,
+                    url: request_url(&url).to_string(),
+                    meta,
+                }),
+                _ => Ok((stream, status, meta)),
+            }
+        })?;
+        let url_string = request_url(&url).to_string();
+        match status / 10 {
+            1 => {
+                return Err(Error::GeminiInputRequired {
+                    url: url_string,
+                    prompt: meta,
+                })
+            }
+            2 => {
+                let content_type = match meta.is_empty() {
+                    true => DEFAULT_CONTENT_TYPE,
+                    false => meta.as_str(),
+                };
+                return Ok(Resource::new(GeminiBody(stream))
+                    .with_content_type(content_type)
+                    .with_final_url(url_string));
+            }
+            3 => {
+                if redirects >= options.get_max_redirects() {
+                    return Err(Error::TooManyGeminiRedirects(url_string));
+                }
+                redirects += 1;
+                let location = resolve(&url, &meta);
+                url = location
+                    .parse::<Url>()
+                    .ok()
+                    .filter(|url| url.scheme_str().eq_ignore_ascii_case("gemini"))
+                    .ok_or_else(|| {
+                        Error::InvalidGeminiResponse(format!("redirect to {}", location))
+                    })?;
+            }
+            5 => {
+                return Err(Error::GeminiPermanentFailure {
+                    status,
+                    url: url_string,
+                    meta,
+                })
+            }
+            6 => {
+                return Err(Error::GeminiCertificateRequired {
+                    status,
+                    url: url_string,
+                    meta,
+                })
+            }
+            _ => {
+                return Err(Error::InvalidGeminiResponse(format!(
+                    "status {} for {}",
+                    status, url_string
+                )))
+            }
+        }
+    }
+}
+
+/// Sends a single request, returning the connection positioned at the
+/// start of the response body, along with the status and meta.
+fn request(url: &Url, options: &OpenOptions) -> Result<(GeminiStream, u8, String)> {
+    let invalid = || Error::InvalidGeminiUrl(url.to_string());
+
+    let authority = url.authority().ok_or_else(invalid)?;
+    let host = authority
+        .host_str()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    if host.is_empty() || authority.username().is_some() {
+        return Err(invalid());
+    }
+    let port = authority.port().unwrap_or(DEFAULT_PORT);
+
+    // The request is the absolute URL, which is limited to 1024 bytes:
+    let request = request_url(url);
+    if request.len() > 1024 {
+        return Err(invalid());
+    }
+
+    let server_name = ServerName::try_from(host.clone()).map_err(|_| invalid())?;
+    let connection = ClientConnection::new(tls_config(), server_name).map_err(tls_error)?;
+    let mut stream = StreamOwned::new(connection, connect(&host, port, options)?);
+
+    while stream.conn.is_handshaking() {
+        stream
+            .conn
+            .complete_io(&mut stream.sock)
+            .map_err(Error::FailedGeminiRequest)?;
+    }
+    let certificate = stream
+        .conn
+        .peer_certificates()
+        .and_then(|certificates| certificates.first())
+        .ok_or_else(|| {
+            Error::UntrustedGeminiCertificate(format!("no certificate presented by {}", host))
+        })?;
+    verify_certificate(&host, port, certificate, options)?;
+
+    stream
+        .write_all(format!("{}\r\n", request).as_bytes())
+        .and_then(|_| stream.flush())
+        .map_err(Error::FailedGeminiRequest)?;
+
+    let (status, meta) = read_header(&mut stream)?;
+    Ok((stream, status, meta))
+}
+
+fn connect(host: &str, port: u16, options: &OpenOptions) -> Result<TcpStream> {
+    let stream = crate::net::connect(host, port, options).map_err(Error::FailedGeminiRequest)?;
+    stream
+        .set_read_timeout(options.get_read_timeout())
+        .map_err(Error::FailedGeminiRequest)?;
+    Ok(stream)
+}
+
+/// Returns the URL without its fragment, as sent in requests.
+fn request_url<'a>(url: &'a Url) -> &'a str {
+    let url = url.as_str();
+    url.split_once('#').map_or(url, |(url, _)| url)
+}
+
+/// Reads the response header, which is a two-digit status code followed by
+/// a space and up to 1024 bytes of meta.
+fn read_header(stream: &mut impl Read) -> Result<(u8, String)> {
+    // Read byte by byte, so as not to consume any of the body:
+    let mut line = Vec::new();
+    let mut byte = [0; 1];
+    while line.len() < 1029 && !line.ends_with(b"\n") {
+        match stream.read(&mut byte).map_err(Error::FailedGeminiRequest)? {
+            0 => break,
+            _ => line.push(byte[0]),
+        }
+    }
+
+    let invalid = |line: &[u8]| {
+        Error::InvalidGeminiResponse(format!("header {:?}", String::from_utf8_lossy(line)))
+    };
+    let header = line
+        .strip_suffix(b"\r\n")
+        .and_then(|header| std::str::from_utf8(header).ok())
+        .ok_or_else(|| invalid(&line))?;
+    let (status, meta) = match header.split_at_checked(2) {
+        Some((status, meta)) if status.bytes().all(|b| b.is_ascii_digit()) => (status, meta),
+        _ => return Err(invalid(&line)),
+    };
+    let meta = match meta.strip_prefix(' ') {
+        Some(meta) => meta,
+        None if meta.is_empty() => meta,
+        None => return Err(invalid(&line)),
+    };
+
+    Ok((
+        status.parse().map_err(|_| invalid(&line))?,
+        meta.to_string(),
+    ))
+}
+
+/// Resolves a redirect target relative to the URL that was requested.
+fn resolve(base: &Url, location: &str) -> String {
+    let has_scheme = location
+        .split(['/', '?', '#'])
+        .next()
+        .is_some_and(|prefix| prefix.contains(':'));
+    if has_scheme {
+        return location.to_string();
+    }
+    if location.starts_with("//") {
+        return format!("gemini:{}", location);
+    }
+
+    let origin = format!("gemini://{}", base.authority_str().unwrap_or_default());
+    if location.starts_with('/') {
+        return format!("{}{}", origin, location);
+    }
+    let path = base.path();
+    if location.starts_with('?') {
+        return format!("{}{}{}", origin, path, location);
+    }
+    let directory = path.rsplit_once('/').map_or("", |(directory, _)| directory);
+    format!("{}{}/{}", origin, directory, location)
+}
+
+/// Builds a TLS configuration that accepts any certificate, since Gemini
+/// servers commonly use self-signed ones, which are instead pinned on first
+/// use by [`verify_certificate`].
+fn tls_config() -> Arc<ClientConfig> {
+    let provider = Arc::new(ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .expect("the default protocol versions are supported")
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(TrustOnFirstUse(provider)))
+        .with_no_client_auth();
+    Arc::new(config)
+}
+
+fn tls_error(error: rustls::Error) -> Error {
+    Error::FailedGeminiRequest(std::io::Error::new(ErrorKind::InvalidData, error))
+}
+
+/// A certificate verifier that checks handshake signatures, but leaves the
+/// certificate itself to be checked against the known hosts file.
+#[derive(Debug)]
+struct TrustOnFirstUse(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for TrustOnFirstUse {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> core::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> core::result::Result<HandshakeSignatureValid, rustls::Error> {
+        let algorithms = &self.0.signature_verification_algorithms;
+        rustls::crypto::verify_tls12_signature(message, cert, dss, algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> core::result::Result<HandshakeSignatureValid, rustls::Error> {
+        let algorithms = &self.0.signature_verification_algorithms;
+        rustls::crypto::verify_tls13_signature(message, cert, dss, algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Checks the server's certificate against the known hosts file, recording
+/// its fingerprint if the server hasn't been seen before.
+///
+/// Each line of the file holds a `host:port` followed by the SHA-256
+/// fingerprint of the certificate, e.g. `example.org:1965 sha256:9f86d0…`.
+fn verify_certificate(
+    host: &str,
+    port: u16,
+    certificate: &[u8],
+    options: &OpenOptions,
+) -> Result<()> {
+    let path = match options.get_gemini_known_hosts() {
+        Some(path) => path.to_path_buf(),
+        None => default_known_hosts(),
+    };
+    let key = format!("{}:{}", host, port);
+    let fingerprint = format!("sha256:{:x}", Sha256::digest(certificate));
+
+    match std::fs::File::open(&path) {
+        Ok(file) => {
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(Error::FailedGeminiRequest)?;
+                let mut fields = line.split_whitespace();
+                if fields.next() != Some(key.as_str()) {
+                    continue;
+                }
+                return match fields.next() == Some(fingerprint.as_str()) {
+                    true => Ok(()),
+                    false => Err(Error::UntrustedGeminiCertificate(format!(
+                        "certificate mismatch for {} in {}",
+                        key,
+                        path.display()
+                    ))),
+                };
+            }
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => {}
+        Err(error) => return Err(Error::FailedGeminiRequest(error)),
+    }
+
+    trust(&path, &key, &fingerprint).map_err(Error::FailedGeminiRequest)
+}
+
+fn trust(path: &Path, key: &str, fingerprint: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{} {}", key, fingerprint)
+}
+
+/// Returns the default known hosts file, in the user's data directory.
+fn default_known_hosts() -> PathBuf {
+    env::var_os("XDG_DATA_HOME")
+        .or_else(|| env::var_os("LOCALAPPDATA"))
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .unwrap_or_else(env::temp_dir)
+        .join("gofer")
+        .join("gemini_known_hosts")
+}
+
+/// The body of a successful response, which ends when the server closes
+/// the connection.
+///
+/// Only a TLS `close_notify` marks the end of the body: a connection closed
+/// without one may have been cut short, and fails with an unexpected EOF.
+///
+/// See: https://geminiprotocol.net/docs/protocol-specification.gmi
+struct GeminiBody(GeminiStream);
+
+impl Read for GeminiBody {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf).map_err(|error| match error.kind() {
+            ErrorKind::UnexpectedEof => std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "the connection was closed without a TLS close_notify, so the body may be truncated",
+            ),
+            _ => error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let parse = |header: &[u8]| read_header(&mut &header[..]);
+
+        let (status, meta) = parse(b"20 text/gemini; lang=en\r\nbody").unwrap();
+        assert_eq!((status, meta.as_str()), (20, "text/gemini; lang=en"));
+
+        let (status, meta) = parse(b"20\r\n").unwrap();
+        assert_eq!((status, meta.as_str()), (20, ""));
+
+        assert!(parse(b"2 text/gemini\r\n").is_err());
+        assert!(parse(b"20 text/gemini\n").is_err());
+        assert!(parse(&[b'2'; 2000]).is_err());
+    }
+
+    #[test]
+    fn redirects() {
+        let base = "gemini://example.org/docs/index.gmi?q"
+            .parse::<Url>()
+            .unwrap();
+        let cases = [
+            ("gemini://other.org/", "gemini://other.org/"),
+            ("//other.org/a", "gemini://other.org/a"),
+            ("/about.gmi", "gemini://example.org/about.gmi"),
+            ("?other", "gemini://example.org/docs/index.gmi?other"),
+            ("intro.gmi", "gemini://example.org/docs/intro.gmi"),
+        ];
+        for (location, expected) in cases {
+            assert_eq!(resolve(&base, location), expected);
+        }
+    }
+}
@@ -1,77 +1,551 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{Error, Read, Result, Url};
-use reqwest::{blocking::ClientBuilder, redirect};
+use crate::{Error, OpenOptions, Resource, Result, Url};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use std::{io::Read, path::PathBuf};
 
-static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+/// The forges known without configuration, by hostname.
+static FORGES: &[(&str, GitForge)] = &[
+    ("bitbucket.org", GitForge::Bitbucket),
+    ("codeberg.org", GitForge::Gitea),
+    ("dev.azure.com", GitForge::AzureDevOps),
+    ("git.sr.ht", GitForge::SourceHut),
+    ("gitea.com", GitForge::Gitea),
+    ("github.com", GitForge::GitHub),
+    ("gitlab.com", GitForge::GitLab),
+];
+
+/// A git forge, which determines how the raw files of its repositories are
+/// fetched: over HTTPS for the known forges and raw file URL templates, and
+/// with the git protocol itself for any other git server.
+///
+/// Self-hosted forges are configured with
+/// [`OpenOptions::git_forge`](crate::OpenOptions::git_forge).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GitForge {
+    /// GitHub, whose raw files are served from `raw.githubusercontent.com`.
+    GitHub,
+    /// GitLab, including self-managed instances.
+    GitLab,
+    /// Gitea and Forgejo, including Codeberg.
+    Gitea,
+    /// Bitbucket Cloud.
+    Bitbucket,
+    /// sourcehut, whose owners are users prefixed with `~`.
+    SourceHut,
+    /// Azure DevOps, whose repositories belong to a project within an
+    /// organization, given as the owner. The version must be a branch.
+    AzureDevOps,
+    /// A raw file URL template, such as
+    /// `https://{host}/{owner}/{repo}/raw/{ref}/{path}`.
+    ///
+    /// The placeholders are replaced by the segments of the `git:` URL,
+    /// which is of the form `git://{host}/{owner}/{repo}/{ref}/{path}`, or
+    /// `git://{host}/{owner}/{project}/{repo}/{ref}/{path}` if the template
+    /// has a `{project}` placeholder.
+    Custom(String),
+    /// Any git server, read with the git protocol v2 from the repositories
+    /// under the given base: a `git://{host}[:{port}]` URL for a git daemon,
+    /// an `http://` or `https://` URL for smart HTTP, or a local directory.
+    ///
+    /// The `git:` URL is of the form `git://{host}/{repo}/{ref}/{path}`,
+    /// where the repository spans every segment up to the first one ending
+    /// in `.git`, if any, or else is the first segment. This is the default
+    /// for hosts that aren't known forges, as `https://{host}`, so a git
+    /// daemon, which serves in cleartext, is only used when configured.
+    Remote(String),
+}
+
+impl GitForge {
+    /// Returns the raw file URL template of this forge, unless it is a
+    /// [`GitForge::Remote`].
+    ///
+    /// See [`GitForge::Custom`] for the placeholders.
+    pub fn raw_url_template(&self) -> Option<&str> {
+        Some(match self {
+            GitForge::GitHub => "https://raw.githubusercontent.com/{owner}/{repo}/{ref}/{path}",
+            GitForge::GitLab => "https://{host}/{owner}/{repo}/-/raw/{ref}/{path}",
+            GitForge::Gitea => "https://{host}/{owner}/{repo}/raw/{ref}/{path}",
+            GitForge::Bitbucket => "https://{host}/{owner}/{repo}/raw/{ref}/{path}",
+            GitForge::SourceHut => "https://{host}/~{owner}/{repo}/blob/{ref}/{path}",
+            // See: https://learn.microsoft.com/en-us/rest/api/azure/devops/git/items/get
+            GitForge::AzureDevOps => "https://{host}/{owner}/{project}/_apis/git/repositories/{repo}/items?path=/{path}&versionDescriptor.version={ref}&$format=octetStream&api-version=7.1",
+            GitForge::Custom(template) => template,
+            GitForge::Remote(_) => return None,
+        })
+    }
+
+    /// Returns the forge known for the given hostname, if any.
+    pub fn for_host(host: &str) -> Option<GitForge> {
+        FORGES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(host))
+            .map(|(_, forge)| forge.clone())
+    }
+}
+
+impl From<&str> for GitForge {
+    fn from(template: &str) -> Self {
+        GitForge::Custom(template.into())
+    }
+}
+
+impl From<String> for GitForge {
+    fn from(template: String) -> Self {
+        GitForge::Custom(template)
+    }
+}
 
 /// Downloads a file from a git repository.
 ///
-/// Supports:
-/// - GitHub: git://github.com/owner/repo/branch/...path
-/// - GitLab: git://gitlab.com/owner/repo/branch/...path
+/// Supports `git://{host}/{owner}/{repo}/{ref}/{path}` URLs on GitHub,
+/// GitLab, Codeberg, Gitea, Bitbucket, sourcehut, and Azure DevOps, as well
+/// as on any self-hosted forges configured with
+/// [`OpenOptions::git_forge`](crate::OpenOptions::git_forge). On any other
+/// host, `git://{host}/{repo}/{ref}/{path}` URLs are read over smart HTTPS,
+/// or from a git daemon if configured as one (see [`GitForge::Remote`]).
+///
+/// Files on git servers are unpacked in memory along with the trees of the
+/// commit and, if the server doesn't support filters, the blobs of other
+/// files, up to the maximum size set with
+/// [`OpenOptions::max_size`](crate::OpenOptions::max_size) in all, or else
+/// 1 GiB.
+///
+/// Files in local repositories, bare or with a working tree, are read from
+/// disk with `git+file:///path/to/repo?ref={ref}#{path}` URLs, where the
+/// ref is a branch, tag, or full or abbreviated commit ID, and defaults to
+/// `HEAD`.
+///
+/// Files in private repositories are fetched with the first credentials
+/// found for the host among:
+/// - the tokens configured with [`OpenOptions::git_token`](crate::OpenOptions::git_token),
+/// - `$GITHUB_TOKEN` for `github.com`, and `$GITLAB_TOKEN` for `gitlab.com`,
+/// - the git credential helper, unless disabled with
+///   [`OpenOptions::git_credential_helper`](crate::OpenOptions::git_credential_helper).
 ///
 /// See: https://git-scm.com/docs/protocol-v2
 /// See: https://git-scm.com/docs/gitweb
 /// See: https://docs.github.com/en/repositories/working-with-files/using-files/getting-permanent-links-to-files
-pub fn open<'a, 'b>(url: &'a Url<'b>) -> Result<Box<dyn Read>> {
-    // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.ClientBuilder.html
-    let client = ClientBuilder::new()
-        .user_agent(USER_AGENT)
-        .redirect(redirect::Policy::default())
-        .https_only(true);
+pub fn open<'a, 'b>(url: &'a Url<'b>, options: &OpenOptions) -> Result<Resource> {
+    if is_local(url) {
+        let (len, reader) = LocalGitPath::parse(url)?.open()?;
+        return Ok(Resource::new(reader).with_content_length(len));
+    }
+    let path = GitPath::parse(url.as_str(), options)?;
+    if let GitForge::Remote(_) = path.forge {
+        let data = path.read(options)?;
+        let len = data.len() as u64;
+        return Ok(Resource::new(std::io::Cursor::new(data)).with_content_length(len));
+    }
+    let (url, options) = path.request(options);
 
-    let url = map_git_url_to_raw_url(url.as_str())?;
+    crate::schemes::http::fetch(&url, url.starts_with("https:"), &options)
+}
 
-    // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.Client.html#method.get
-    // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.RequestBuilder.html
-    let response = client.build()?.get(url).send()?;
+/// Opens a file from a git repository for random access.
+pub fn open_seekable<'a, 'b>(
+    url: &'a Url<'b>,
+    options: &OpenOptions,
+) -> Result<Box<dyn crate::ReadSeek>> {
+    if is_local(url) {
+        let data = LocalGitPath::parse(url)?.read()?;
+        return Ok(Box::new(std::io::Cursor::new(data)));
+    }
+    let path = GitPath::parse(url.as_str(), options)?;
+    if let GitForge::Remote(_) = path.forge {
+        return Ok(Box::new(std::io::Cursor::new(path.read(options)?)));
+    }
+    let (url, options) = path.request(options);
 
-    Ok(Box::new(response))
+    Ok(Box::new(crate::schemes::http::RangeReader::new(
+        &url,
+        url.starts_with("https:"),
+        &options,
+    )?))
 }
 
-/// Maps a git URL to a raw content URL for supported git providers.
-/// - GitHub: git://github.com/owner/repo/branch/...path -> https://raw.githubusercontent.com/owner/repo/refs/heads/branch/...path
+/// Downloads a file from a git repository, asynchronously.
+#[cfg(feature = "async")]
+pub async fn open_async<'a, 'b>(
+    url: &'a Url<'b>,
+    options: &OpenOptions,
+) -> Result<crate::r#async::ByteStream> {
+    if is_local(url) {
+        let path = LocalGitPath::parse(url)?;
+        return read_in_background(move || path.read()).await;
+    }
+    let path = GitPath::parse(url.as_str(), options)?;
+    if let GitForge::Remote(_) = path.forge {
+        let (url, options) = (url.as_str().to_string(), options.clone());
+        return read_in_background(move || {
+            GitPath::parse(&url, &options).and_then(|path| path.read(&options))
+        })
+        .await;
+    }
+    let (url, options) = path.request(options);
+
+    let response =
+        crate::schemes::http::get_async(&url, url.starts_with("https:"), &options).await?;
+
+    Ok(crate::schemes::http::into_stream(response))
+}
+
+/// Reads a file on its own thread, as the git protocol client and the
+/// repository reader are blocking, and returns it as a single chunk.
+#[cfg(feature = "async")]
+async fn read_in_background(
+    read: impl FnOnce() -> Result<Vec<u8>> + Send + 'static,
+) -> Result<crate::r#async::ByteStream> {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(read());
+    });
+    let data = receiver
+        .await
+        .map_err(|e| Error::FailedGitRequest(std::io::Error::other(e)))??;
+    let chunk = bytes::Bytes::from(data);
+    Ok(Box::pin(futures_util::stream::once(async { Ok(chunk) })))
+}
+
+/// Maps a git URL to a raw content URL using the forge for its host, e.g.:
+/// - GitHub: git://github.com/owner/repo/branch/...path -> https://raw.githubusercontent.com/owner/repo/branch/...path
 /// - GitLab: git://gitlab.com/owner/repo/branch/...path -> https://gitlab.com/owner/repo/-/raw/branch/...path
-fn map_git_url_to_raw_url(url_str: &str) -> Result<String> {
-    let Some(path) = url_str.strip_prefix("git://") else {
-        return Err(Error::InvalidGitUrl(format!(
-            "Invalid git URL format, expected `git://`: {}",
-            url_str
-        )));
-    };
-
-    let components: Vec<&str> = path.split('/').collect();
-
-    let host = components
-        .first()
-        .ok_or_else(|| Error::InvalidGitUrl(format!("Invalid git URL format: {}", url_str)))?;
-
-    if components.len() < 5 {
-        return Err(Error::InvalidGitUrl(format!(
-            "Invalid GitHub git URL format (need at least 5 components): {}",
-            url_str
-        )));
-    }
-
-    let owner = components[1];
-    let repo = components[2];
-    let version = components[3];
-    let file_path = components[4..].join("/");
-
-    match *host {
-        "github.com" => Ok(format!(
-            "https://raw.githubusercontent.com/{}/{}/{}/{}",
-            owner, repo, version, file_path
-        )),
-        "gitlab.com" => Ok(format!(
-            "https://gitlab.com/{}/{}/-/raw/{}/{}",
-            owner, repo, version, file_path
-        )),
-        _ => Err(Error::InvalidGitUrl(format!(
-            "Unsupported git host: {}",
-            host
-        ))),
+#[cfg(test)]
+fn map_git_url_to_raw_url(url_str: &str, options: &OpenOptions) -> Result<String> {
+    GitPath::parse(url_str, options).map(|path| path.raw_url())
+}
+
+/// A file in a repository on a forge, as given by a `git:` URL.
+struct GitPath<'a> {
+    forge: GitForge,
+    host: &'a str,
+    owner: &'a str,
+    project: &'a str,
+    repo: &'a str,
+    version: &'a str,
+    path: String,
+}
+
+impl<'a> GitPath<'a> {
+    fn parse(url_str: &'a str, options: &OpenOptions) -> Result<Self> {
+        let Some(path) = url_str.strip_prefix("git://") else {
+            return Err(Error::InvalidGitUrl(format!(
+                "Invalid git URL format, expected `git://`: {}",
+                url_str
+            )));
+        };
+
+        let components: Vec<&str> = path.split('/').collect();
+
+        let host = components
+            .first()
+            .ok_or_else(|| Error::InvalidGitUrl(format!("Invalid git URL format: {}", url_str)))?;
+
+        let forge = options
+            .get_git_forge(host)
+            .or_else(|| GitForge::for_host(host))
+            .unwrap_or_else(|| GitForge::Remote(format!("https://{}", host)));
+
+        if let GitForge::Remote(_) = forge {
+            let rest = path.get(host.len() + 1..).unwrap_or_default();
+            let parts = split_repo(rest)
+                .and_then(|(repo, rest)| Some((repo, rest.split_once('/')?)))
+                .filter(|(repo, (version, path))| {
+                    !repo.is_empty() && !version.is_empty() && !path.is_empty()
+                });
+            let Some((repo, (version, file_path))) = parts else {
+                return Err(Error::InvalidGitUrl(format!(
+                    "Invalid git URL format (need a host, repository, version, and path): {}",
+                    url_str
+                )));
+            };
+            return Ok(Self {
+                forge,
+                host,
+                owner: "",
+                project: "",
+                repo,
+                version,
+                path: file_path.to_string(),
+            });
+        }
+
+        let template = forge.raw_url_template().unwrap_or_default();
+        let (owner, project, rest) = match template.contains("{project}") {
+            true => (components.get(1), components.get(2), components.get(3..)),
+            false => (components.get(1), None, components.get(2..)),
+        };
+        let (Some(owner), Some([repo, version, file_path @ ..])) = (owner, rest) else {
+            return Err(Error::InvalidGitUrl(format!(
+                "Invalid git URL format (need a host, owner, repository, version, and path): {}",
+                url_str
+            )));
+        };
+        if file_path.is_empty() {
+            return Err(Error::InvalidGitUrl(format!(
+                "Invalid git URL format (need a host, owner, repository, version, and path): {}",
+                url_str
+            )));
+        }
+
+        Ok(Self {
+            forge,
+            host,
+            owner: owner.trim_start_matches('~'),
+            project: project.unwrap_or(&""),
+            repo,
+            version,
+            path: file_path.join("/"),
+        })
+    }
+
+    fn raw_url(&self) -> String {
+        self.forge
+            .raw_url_template()
+            .unwrap_or_default()
+            .replace("{host}", self.host)
+            .replace("{owner}", self.owner)
+            .replace("{project}", self.project)
+            .replace("{repo}", self.repo)
+            .replace("{ref}", self.version)
+            .replace("{path}", &self.path)
+    }
+
+    /// Returns the URL to fetch the file from, together with the options to
+    /// fetch it with, which carry any credentials found for the host.
+    fn request(&self, options: &OpenOptions) -> (String, OpenOptions) {
+        let url = self.raw_url();
+        let Some(credentials) = GitCredentials::find(self.host, &url, options) else {
+            return (url, options.clone());
+        };
+        let mut options = options.clone();
+        let url = match self.forge {
+            // GitLab only accepts tokens for raw files through its API:
+            // See: https://docs.gitlab.com/api/repository_files/#get-raw-file-from-repository
+            GitForge::GitLab => {
+                let encode = |s| utf8_percent_encode(s, NON_ALPHANUMERIC).to_string();
+                format!(
+                    "https://{}/api/v4/projects/{}/repository/files/{}/raw?ref={}",
+                    self.host,
+                    encode(&format!("{}/{}", self.owner, self.repo)),
+                    encode(&self.path),
+                    encode(self.version)
+                )
+            }
+            _ => url,
+        };
+        let (name, value) = credentials.header(&self.forge);
+        options.header(name, value).same_origin_redirects(true);
+        (url, options)
+    }
+
+    /// Returns the URL or path of the repository on a git server.
+    fn remote(&self) -> Option<String> {
+        let GitForge::Remote(base) = &self.forge else {
+            return None;
+        };
+        Some(format!("{}/{}", base.trim_end_matches('/'), self.repo))
+    }
+
+    /// Reads the file from a git server, with any credentials found for the
+    /// host of its smart HTTP URL.
+    fn read(&self, options: &OpenOptions) -> Result<Vec<u8>> {
+        let remote = self.remote().expect("the forge is a git server");
+        let host = remote
+            .split_once("://")
+            .and_then(|(_, url)| url.split(['/', ':']).next());
+        let mut options = options.clone();
+        if let Some(credentials) =
+            host.and_then(|host| GitCredentials::find(host, &remote, &options))
+        {
+            let (name, value) = credentials.header(&self.forge);
+            options.header(name, value).same_origin_redirects(true);
+        }
+        crate::upload_pack::read_file(&remote, self.version, &self.path, &options)
+    }
+}
+
+/// Checks whether a URL is a `git+file:` URL of a local repository.
+fn is_local(url: &Url) -> bool {
+    url.scheme_str().eq_ignore_ascii_case("git+file")
+}
+
+/// A file in a local repository, as given by a `git+file:` URL.
+struct LocalGitPath {
+    repository: PathBuf,
+    version: String,
+    path: String,
+}
+
+impl LocalGitPath {
+    fn parse(url: &Url) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidGitUrl(format!(
+                "Invalid git+file URL format (need a repository path and a `#path`): {}",
+                url
+            ))
+        };
+        let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+
+        // The repository is at the `file:` URL without the query and fragment:
+        let location = url.as_str().get("git+".len()..).unwrap_or_default();
+        let location = location.split(['?', '#']).next().unwrap_or_default();
+        let repository = location
+            .parse::<Url>()
+            .ok()
+            .and_then(|url| url.to_path())
+            .ok_or_else(invalid)?;
+        let version = url
+            .query_str()
+            .unwrap_or_default()
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("ref="))
+            .map(decode)
+            .filter(|version| !version.is_empty())
+            .unwrap_or_else(|| "HEAD".into());
+        let path = url
+            .fragment_str()
+            .map(decode)
+            .filter(|path| path.split('/').any(|segment| !segment.is_empty()))
+            .ok_or_else(invalid)?;
+
+        Ok(Self {
+            repository,
+            version,
+            path,
+        })
+    }
+
+    /// Opens the file, returning its size and a reader of its contents.
+    fn open(&self) -> Result<(u64, Box<dyn Read + Send>)> {
+        let repository = crate::repository::Repository::open(&self.repository)?;
+        let commit = repository.resolve(&self.version)?;
+        let blob = crate::pack::find_blob(&commit, &self.path, |oid| repository.read(oid))?;
+        repository.open_blob(&blob)
+    }
+
+    /// Reads the file in full.
+    fn read(&self) -> Result<Vec<u8>> {
+        let (len, mut reader) = self.open()?;
+        let mut data = Vec::with_capacity(len.min(1 << 20) as usize);
+        reader
+            .read_to_end(&mut data)
+            .map_err(Error::FailedGitRequest)?;
+        Ok(data)
+    }
+}
+
+/// Splits a path into the repository, which spans every segment up to the
+/// first one ending in `.git` or else is the first segment, and the rest.
+fn split_repo(path: &str) -> Option<(&str, &str)> {
+    let mut start = 0;
+    while let Some(len) = path[start..].find('/') {
+        if path[start..start + len].ends_with(".git") {
+            return Some((&path[..start + len], &path[start + len + 1..]));
+        }
+        start += len + 1;
+    }
+    path.split_once('/')
+}
+
+/// A token, or a username and password, for a forge.
+struct GitCredentials {
+    username: Option<String>,
+    password: String,
+}
+
+impl GitCredentials {
+    /// Returns the first credentials found for the given host, if they are
+    /// to be sent to the given URL, which must use HTTPS so that they never
+    /// travel in cleartext.
+    fn find(host: &str, url: &str, options: &OpenOptions) -> Option<Self> {
+        let (protocol, _) = url.split_once("://")?;
+        if !protocol.eq_ignore_ascii_case("https") {
+            return None;
+        }
+        let token = |token: &str| Self {
+            username: None,
+            password: token.to_string(),
+        };
+        if let Some(configured) = options.get_git_token(host) {
+            return Some(token(configured));
+        }
+        let var = match host.to_ascii_lowercase().as_str() {
+            "github.com" => "GITHUB_TOKEN",
+            "gitlab.com" => "GITLAB_TOKEN",
+            _ => "",
+        };
+        match std::env::var(var) {
+            Ok(value) if !var.is_empty() && !value.is_empty() => Some(token(&value)),
+            _ if options.get_git_credential_helper() => Self::fill(protocol, host, options),
+            _ => None,
+        }
+    }
+
+    /// Asks the git credential helper for the credentials of the host,
+    /// without letting git prompt for them.
+    ///
+    /// See: https://git-scm.com/docs/git-credential
+    fn fill(protocol: &str, host: &str, options: &OpenOptions) -> Option<Self> {
+        use std::{io::Write, process::*};
+
+        if host.chars().any(char::is_control) {
+            return None;
+        }
+        let mut git = Command::new("git")
+            .args(crate::upload_pack::config_args(options))
+            .args(["-c", "credential.interactive=false", "credential", "fill"])
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let request = format!("protocol={}\nhost={}\n\n", protocol, host);
+        let written = git.stdin.take()?.write_all(request.as_bytes());
+        let output = git.wait_with_output().ok()?;
+        if written.is_err() || !output.status.success() {
+            return None;
+        }
+
+        let output = String::from_utf8(output.stdout).ok()?;
+        let field = |name| {
+            output.lines().find_map(|line| {
+                let (key, value) = line.split_once('=')?;
+                (key == name).then(|| value.to_string())
+            })
+        };
+        Some(Self {
+            username: field("username"),
+            password: field("password").filter(|password| !password.is_empty())?,
+        })
+    }
+
+    /// Returns the request header that carries these credentials to the
+    /// given forge.
+    fn header(&self, forge: &GitForge) -> (&'static str, String) {
+        let basic = |username: &str| {
+            let credentials = format!("{}:{}", username, self.password);
+            (
+                "Authorization",
+                format!("Basic {}", BASE64.encode(credentials)),
+            )
+        };
+        match (forge, self.username.as_deref()) {
+            // See: https://docs.gitlab.com/api/rest/authentication/#personalprojectgroup-access-tokens
+            (GitForge::GitLab, _) => ("PRIVATE-TOKEN", self.password.clone()),
+            // See: https://learn.microsoft.com/en-us/azure/devops/organizations/accounts/use-personal-access-tokens-to-authenticate
+            (GitForge::AzureDevOps, username) => basic(username.unwrap_or_default()),
+            // As git itself sends them over smart HTTP:
+            (GitForge::Remote(_), username) => basic(username.unwrap_or_default()),
+            (GitForge::Gitea, None) => ("Authorization", format!("token {}", self.password)),
+            // Such as Bitbucket app passwords:
+            (GitForge::Gitea | GitForge::Bitbucket | GitForge::Custom(_), Some(username)) => {
+                basic(username)
+            }
+            _ => ("Authorization", format!("Bearer {}", self.password)),
+        }
     }
 }
 
@@ -81,23 +555,176 @@ mod test {
 
     #[test]
     fn url_mapping() {
+        let options = OpenOptions::new();
         assert_eq!(
             map_git_url_to_raw_url(
-                "git://github.com/dryrust/gofer.rs/master/lib/gofer/src/schemes/git.rs"
+                "git://github.com/dryrust/gofer.rs/master/lib/gofer/src/schemes/git.rs",
+                &options
             )
             .unwrap(),
             "https://raw.githubusercontent.com/dryrust/gofer.rs/master/lib/gofer/src/schemes/git.rs"
         );
         assert_eq!(
             map_git_url_to_raw_url(
-                "git://github.com/dryrust/gofer.rs/f4ea4a585c009aefd570cefcb6062dc5d579c6ab/VERSION"
+                "git://github.com/dryrust/gofer.rs/f4ea4a585c009aefd570cefcb6062dc5d579c6ab/VERSION",
+                &options
             )
             .unwrap(),
             "https://raw.githubusercontent.com/dryrust/gofer.rs/f4ea4a585c009aefd570cefcb6062dc5d579c6ab/VERSION"
         );
         assert_eq!(
-            map_git_url_to_raw_url("git://gitlab.com/rust-lang/rust/master/src/README.md").unwrap(),
+            map_git_url_to_raw_url(
+                "git://gitlab.com/rust-lang/rust/master/src/README.md",
+                &options
+            )
+            .unwrap(),
             "https://gitlab.com/rust-lang/rust/-/raw/master/src/README.md"
-        )
+        );
+        assert_eq!(
+            map_git_url_to_raw_url(
+                "git://codeberg.org/forgejo/forgejo/v11.0/README.md",
+                &options
+            )
+            .unwrap(),
+            "https://codeberg.org/forgejo/forgejo/raw/v11.0/README.md"
+        );
+        assert_eq!(
+            map_git_url_to_raw_url("git://bitbucket.org/owner/repo/main/README.md", &options)
+                .unwrap(),
+            "https://bitbucket.org/owner/repo/raw/main/README.md"
+        );
+        assert_eq!(
+            map_git_url_to_raw_url("git://git.sr.ht/~sircmpwn/scdoc/master/README.md", &options)
+                .unwrap(),
+            "https://git.sr.ht/~sircmpwn/scdoc/blob/master/README.md"
+        );
+        assert_eq!(
+            map_git_url_to_raw_url(
+                "git://dev.azure.com/org/project/repo/main/src/lib.rs",
+                &options
+            )
+            .unwrap(),
+            "https://dev.azure.com/org/project/_apis/git/repositories/repo/items?path=/src/lib.rs&versionDescriptor.version=main&$format=octetStream&api-version=7.1"
+        );
+        assert!(map_git_url_to_raw_url("git://github.com/owner/repo/main", &options).is_err());
+    }
+
+    #[test]
+    fn self_hosted_forges() {
+        let mut options = OpenOptions::new();
+        options
+            .git_forge("gitlab.example.org", GitForge::GitLab)
+            .git_forge(
+                "git.example.org",
+                "http://{host}:3000/{owner}/{repo}/raw/{ref}/{path}",
+            );
+        assert_eq!(
+            map_git_url_to_raw_url("git://gitlab.example.org/team/app/v1.0/README.md", &options)
+                .unwrap(),
+            "https://gitlab.example.org/team/app/-/raw/v1.0/README.md"
+        );
+        assert_eq!(
+            map_git_url_to_raw_url(
+                "git://Git.Example.org/team/app/main/docs/index.md",
+                &options
+            )
+            .unwrap(),
+            "http://Git.Example.org:3000/team/app/raw/main/docs/index.md"
+        );
+    }
+
+    #[test]
+    fn git_servers() {
+        let mut options = OpenOptions::new();
+        options.git_forge(
+            "git.example.org",
+            GitForge::Remote("https://git.example.org/cgit/".into()),
+        );
+        let remote = |url| {
+            let path = GitPath::parse(url, &options).unwrap();
+            (path.remote().unwrap(), path.version, path.path)
+        };
+        assert_eq!(
+            remote("git://example.org/repo/v1.0/docs/README.md"),
+            (
+                "https://example.org/repo".into(),
+                "v1.0",
+                "docs/README.md".into()
+            )
+        );
+        assert_eq!(
+            remote("git://git.example.org/team/app.git/main/README.md"),
+            (
+                "https://git.example.org/cgit/team/app.git".into(),
+                "main",
+                "README.md".into()
+            )
+        );
+        assert!(GitPath::parse("git://example.org/repo/main", &options).is_err());
+        assert!(GitPath::parse("git://example.org/repo.git/main/", &options).is_err());
+    }
+
+    #[test]
+    fn local_repositories() {
+        let parse = |url: &str| LocalGitPath::parse(&url.parse::<Url>().unwrap());
+        let path = parse("git+file:///srv/repo.git?ref=v1.0#docs/README%201.md").unwrap();
+        assert_eq!(path.repository, PathBuf::from("/srv/repo.git"));
+        assert_eq!(path.version, "v1.0");
+        assert_eq!(path.path, "docs/README 1.md");
+        let path = parse("git+file:///home/user/project#src/lib.rs").unwrap();
+        assert_eq!(path.repository, PathBuf::from("/home/user/project"));
+        assert_eq!(path.version, "HEAD");
+        assert!(parse("git+file:///srv/repo.git?ref=main").is_err());
+        assert!(parse("git+file:///srv/repo.git#/").is_err());
+    }
+
+    #[test]
+    fn authorization() {
+        let token = GitCredentials {
+            username: None,
+            password: "secret".into(),
+        };
+        let login = GitCredentials {
+            username: Some("alice".into()),
+            password: "secret".into(),
+        };
+        let header = |credentials: &GitCredentials, forge| credentials.header(&forge);
+        assert_eq!(
+            header(&token, GitForge::GitHub),
+            ("Authorization", "Bearer secret".into())
+        );
+        assert_eq!(
+            header(&token, GitForge::GitLab),
+            ("PRIVATE-TOKEN", "secret".into())
+        );
+        assert_eq!(
+            header(&token, GitForge::Gitea),
+            ("Authorization", "token secret".into())
+        );
+        assert_eq!(
+            header(&login, GitForge::Bitbucket),
+            ("Authorization", "Basic YWxpY2U6c2VjcmV0".into())
+        );
+        assert_eq!(
+            header(&token, GitForge::AzureDevOps),
+            ("Authorization", "Basic OnNlY3JldA==".into())
+        );
+    }
+
+    #[test]
+    fn gitlab_api_with_token() {
+        let mut options = OpenOptions::new();
+        options.git_token("GitLab.com", "secret");
+        assert!(!format!("{:?}", options).contains("secret"));
+
+        let path = GitPath::parse("git://gitlab.com/group/project/v1.0/src/lib.rs", &options);
+        let (url, options) = path.unwrap().request(&options);
+        assert_eq!(
+            url,
+            "https://gitlab.com/api/v4/projects/group%2Fproject/repository/files/src%2Flib%2Ers/raw?ref=v1%2E0"
+        );
+        assert!(options
+            .get_headers()
+            .any(|header| header == ("PRIVATE-TOKEN", "secret")));
     }
 }
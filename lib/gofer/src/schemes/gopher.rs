@@ -0,0 +1,285 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{Error, OpenOptions, Read, Resource, Result, Url};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::{io::Write, net::TcpStream};
+
+/// The default Gopher port.
+pub const DEFAULT_PORT: u16 = 70;
+
+/// The size of the largest menu read, unless a maximum size is configured.
+const MAX_MENU_LEN: u64 = 16 << 20;
+
+/// The characters percent-encoded in the selectors of generated URLs.
+const SELECTOR: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// A Gopher menu, as returned for item type `1`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct GopherMenu {
+    /// The menu items, in the order listed by the server.
+    pub items: Vec<GopherItem>,
+}
+
+/// An item in a Gopher menu.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GopherItem {
+    /// The item type, such as `0` for text files, `1` for menus, or `i` for
+    /// informational messages.
+    pub item_type: char,
+    /// The user-visible description of the item.
+    pub display: String,
+    /// The selector to send to the server to retrieve the item.
+    pub selector: String,
+    /// The host name of the server holding the item.
+    pub host: String,
+    /// The port of the server holding the item.
+    pub port: u16,
+}
+
+impl GopherMenu {
+    /// Parses a menu in the format described in RFC 1436, section 3.8.
+    ///
+    /// Parsing is lenient: missing fields are left empty, and the listing
+    /// ends at the terminating `.` line, if any.
+    pub fn parse(input: &[u8]) -> Self {
+        let items = String::from_utf8_lossy(input)
+            .lines()
+            .take_while(|line| *line != ".")
+            .filter_map(GopherItem::parse)
+            .collect();
+        Self { items }
+    }
+}
+
+impl GopherItem {
+    fn parse(line: &str) -> Option<Self> {
+        let mut chars = line.chars();
+        let item_type = chars.next()?;
+        let mut fields = chars.as_str().split('\t');
+        let mut field = || fields.next().unwrap_or_default().to_string();
+        Some(Self {
+            item_type,
+            display: field(),
+            selector: field(),
+            host: field(),
+            port: field().trim().parse().unwrap_or(DEFAULT_PORT),
+        })
+    }
+
+    /// Returns the `gopher:` URL of the item, or `None` for informational
+    /// messages and errors, which don't refer to anything.
+    pub fn url(&self) -> Option<String> {
+        if matches!(self.item_type, 'i' | '3') || self.host.is_empty() {
+            return None;
+        }
+        let selector = utf8_percent_encode(&self.selector, SELECTOR);
+        Some(match self.port {
+            DEFAULT_PORT => format!("gopher://{}/{}{}", self.host, self.item_type, selector),
+            port => format!(
+                "gopher://{}:{}/{}{}",
+                self.host, port, self.item_type, selector
+            ),
+        })
+    }
+}
+
+/// Retrieves a Gopher item, returning its raw bytes as sent by the server.
+///
+/// The first character of the URL path is the item type, which defaults to
+/// `1` (a menu) and determines the content type. The rest of the path is
+/// the selector, optionally followed by a `%09` and a search query.
+///
+/// See: https://en.wikipedia.org/wiki/Gopher_(protocol)
+/// See: https://datatracker.ietf.org/doc/html/rfc1436
+/// See: https://datatracker.ietf.org/doc/html/rfc4266
+pub fn open<'a, 'b>(url: &'a Url<'b>, options: &OpenOptions) -> Result<Resource> {
+    let request = GopherRequest::parse(url)?;
+    let stream = request.send(options)?;
+
+    let resource = Resource::new(stream).with_final_url(url.to_string());
+    Ok(match content_type(request.item_type) {
+        Some(content_type) => resource.with_content_type(content_type),
+        None => resource,
+    })
+}
+
+/// Retrieves and parses a Gopher menu.
+///
+/// The menu is read into memory first, failing with [`Error::TooLarge`] past
+/// the [maximum size](OpenOptions::max_size), or 16 MiB by default.
+pub fn read_menu<'a, 'b>(url: &'a Url<'b>, options: &OpenOptions) -> Result<GopherMenu> {
+    let request = GopherRequest::parse(url)?;
+    if !matches!(request.item_type, '1' | '7') {
+        return Err(Error::InvalidGopherUrl(url.to_string()));
+    }
+
+    // Read up to one byte past the limit, so as to detect overflow:
+    let limit = options.get_max_size().unwrap_or(MAX_MENU_LEN);
+    let mut input = Vec::new();
+    request
+        .send(options)?
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut input)
+        .map_err(Error::FailedGopherRequest)?;
+    if input.len() as u64 > limit {
+        return Err(Error::TooLarge(limit));
+    }
+
+    Ok(GopherMenu::parse(&input))
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct GopherRequest {
+    host: String,
+    port: u16,
+    item_type: char,
+    selector: String,
+    search: Option<String>,
+}
+
+impl GopherRequest {
+    /// See: https://datatracker.ietf.org/doc/html/rfc4266#section-2.1
+    fn parse(url: &Url) -> Result<Self> {
+        let invalid = || Error::InvalidGopherUrl(url.to_string());
+
+        let authority = url.authority().ok_or_else(invalid)?;
+        let host = authority.host_str();
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let port = authority.port().unwrap_or(DEFAULT_PORT);
+
+        // A `?` is an ordinary character in selectors:
+        let mut path = url.path().strip_prefix('/').unwrap_or_default().to_string();
+        if let Some(query) = url.query_str() {
+            path.push('?');
+            path.push_str(query);
+        }
+
+        let mut chars = path.chars();
+        let item_type = chars.next().unwrap_or('1');
+        let mut parts = chars.as_str().split("%09");
+        let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+        let selector = decode(parts.next().unwrap_or_default());
+        let search = parts.next().map(decode);
+        let unsafe_chars = ['\t', '\r', '\n'];
+        if selector.contains(unsafe_chars)
+            || search.as_ref().is_some_and(|s| s.contains(unsafe_chars))
+        {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            item_type,
+            selector,
+            search,
+        })
+    }
+
+    fn send(&self, options: &OpenOptions) -> Result<TcpStream> {
+        options
+            .get_retry()
+            .run(|| self.send_once(options).map_err(Error::FailedGopherRequest))
+    }
+
+    fn send_once(&self, options: &OpenOptions) -> std::io::Result<TcpStream> {
+        let mut stream = crate::net::connect(&self.host, self.port, options)?;
+        stream.set_read_timeout(options.get_read_timeout())?;
+
+        let request = match &self.search {
+            None => format!("{}\r\n", self.selector),
+            Some(search) => format!("{}\t{}\r\n", self.selector, search),
+        };
+        stream.write_all(request.as_bytes())?;
+        Ok(stream)
+    }
+}
+
+/// Returns the content type of the given item type, if well known.
+///
+/// See: https://datatracker.ietf.org/doc/html/rfc1436#section-3.8
+fn content_type(item_type: char) -> Option<&'static str> {
+    Some(match item_type {
+        '0' => "text/plain",
+        '1' | '7' => "application/gopher-menu",
+        '4' => "application/mac-binhex40",
+        '5' | '9' => "application/octet-stream",
+        '6' => "text/x-uuencode",
+        'g' => "image/gif",
+        'h' => "text/html",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(url: &str) -> GopherRequest {
+        GopherRequest::parse(&url.parse::<Url>().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parse_url() {
+        let r = request("gopher://example.org");
+        assert_eq!((r.port, r.item_type, r.selector.as_str()), (70, '1', ""));
+
+        let r = request("gopher://example.org:7070/0/docs/read%20me.txt");
+        assert_eq!(r.port, 7070);
+        assert_eq!(r.item_type, '0');
+        assert_eq!(r.selector, "/docs/read me.txt");
+        assert_eq!(r.search, None);
+
+        let r = request("gopher://example.org/7/search%09gofer%20rust");
+        assert_eq!(r.item_type, '7');
+        assert_eq!(r.selector, "/search");
+        assert_eq!(r.search.as_deref(), Some("gofer rust"));
+    }
+
+    #[test]
+    fn reject_injected_lines() {
+        for url in [
+            "gopher://example.org/0/a%0D%0Ab",
+            "gopher://example.org/7/search%09gofer%0D%0A/secret",
+            "gopher://example.org/7/search%09gofer%0A",
+        ] {
+            let result = GopherRequest::parse(&url.parse::<Url>().unwrap());
+            assert!(matches!(result, Err(Error::InvalidGopherUrl(_))), "{}", url);
+        }
+    }
+
+    #[test]
+    fn parse_menu() {
+        let menu = GopherMenu::parse(
+            b"iWelcome!\tfake\t(NULL)\t0\r\n\
+              0About\t/about.txt\texample.org\t70\r\n\
+              1Elsewhere\t/a b\tother.org\t7070\t+\r\n\
+              .\r\n\
+              0Ignored\t/\texample.org\t70\r\n",
+        );
+        assert_eq!(menu.items.len(), 3);
+        assert_eq!(menu.items[0].item_type, 'i');
+        assert_eq!(menu.items[0].display, "Welcome!");
+        assert_eq!(menu.items[0].url(), None);
+        assert_eq!(
+            menu.items[1].url().as_deref(),
+            Some("gopher://example.org/0/about.txt")
+        );
+        assert_eq!(
+            menu.items[2].url().as_deref(),
+            Some("gopher://other.org:7070/1/a%20b")
+        );
+    }
+}
@@ -1,25 +1,676 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{Read, Result, Url};
-use reqwest::{blocking::ClientBuilder, redirect};
+use crate::{readers::Resume, Error, OpenOptions, Read, ReadSeek, Resource, Result, Url};
+use reqwest::{
+    blocking::{Client, ClientBuilder, Response},
+    header, redirect, Method, StatusCode,
+};
+use std::{
+    io::{Seek, SeekFrom},
+    time::{Duration, SystemTime},
+};
 
-static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+#[cfg(feature = "async")]
+use crate::r#async::ByteStream;
+
+/// The maximum number of bytes of an error response body to keep.
+const BODY_EXCERPT_LEN: u64 = 512;
+
+/// Applies the options shared by the blocking and async client builders,
+/// which have identical but unrelated APIs.
+macro_rules! configure {
+    ($builder:expr, $https_only:expr, $options:expr) => {{
+        let options: &OpenOptions = $options;
+        #[cfg_attr(not(feature = "https"), allow(unused_mut))]
+        let mut builder = $builder
+            .user_agent(options.get_user_agent())
+            .redirect(redirect_policy(options))
+            .https_only($https_only);
+
+        #[cfg(feature = "https")]
+        {
+            builder = builder.tls_built_in_root_certs(options.get_tls_built_in_root_certs());
+            for pem in options.get_root_certificates() {
+                // See: https://docs.rs/reqwest/latest/reqwest/tls/struct.Certificate.html
+                for cert in reqwest::Certificate::from_pem_bundle(pem)? {
+                    builder = builder.add_root_certificate(cert);
+                }
+            }
+        }
+
+        builder
+    }};
+}
+
+/// Returns the redirect policy for the given options, which keeps requests
+/// that carry credentials on the origin they were sent to.
+fn redirect_policy(options: &OpenOptions) -> redirect::Policy {
+    let max_redirects = options.get_max_redirects();
+
+    #[cfg(feature = "git")]
+    if max_redirects > 0 && options.get_same_origin_redirects() {
+        return redirect::Policy::custom(move |attempt| {
+            let origin = attempt.previous().first().map(|url| url.origin());
+            if attempt.previous().len() > max_redirects {
+                attempt.error("too many redirects")
+            } else if origin != Some(attempt.url().origin()) {
+                attempt.error("refusing to send credentials to another origin")
+            } else {
+                attempt.follow()
+            }
+        });
+    }
+
+    match max_redirects {
+        0 => redirect::Policy::none(),
+        n => redirect::Policy::limited(n),
+    }
+}
 
 /// See: https://en.wikipedia.org/wiki/HTTP
 /// See: https://en.wikipedia.org/wiki/HTTPS
-pub fn open<'a, 'b>(url: &'a Url<'b>, secure: bool) -> Result<Box<dyn Read>> {
+pub fn open<'a, 'b>(url: &'a Url<'b>, secure: bool, options: &OpenOptions) -> Result<Resource> {
+    fetch(url.as_str(), secure, options)
+}
+
+/// Sends a `GET` request configured by the given options, going through
+/// the cache if one is configured and the request carries no credentials.
+pub(crate) fn fetch(url: &str, https_only: bool, options: &OpenOptions) -> Result<Resource> {
+    #[cfg(feature = "cache")]
+    if let Some(cache) = options
+        .get_cache()
+        .filter(|_| !crate::cache::sends_credentials(url, options))
+    {
+        return crate::cache::fetch(cache, url, https_only, options);
+    }
+
+    let response = get(url, https_only, options)?;
+
+    into_resumable_resource(response, https_only, options)
+}
+
+/// Wraps a response body, exposing the response headers as metadata.
+pub(crate) fn into_resource(response: Response) -> Resource {
+    let metadata = describe(
+        response.headers(),
+        response.url(),
+        response.content_length(),
+    );
+    metadata.map_reader(|_| Box::new(response))
+}
+
+/// Returns the metadata in the headers of a blocking or async response, as
+/// a resource without any contents.
+fn describe(
+    headers: &header::HeaderMap,
+    url: &reqwest::Url,
+    content_length: Option<u64>,
+) -> Resource {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let content_type = header(header::CONTENT_TYPE).map(String::from);
+    let content_encoding = header(header::CONTENT_ENCODING).map(String::from);
+    let last_modified =
+        header(header::LAST_MODIFIED).and_then(|v| httpdate::parse_http_date(v).ok());
+    let etag = header(header::ETAG).map(String::from);
+
+    let resource = Resource::new(std::io::empty())
+        .with_content_encoding(content_encoding)
+        .with_content_length(content_length)
+        .with_last_modified(last_modified)
+        .with_etag(etag)
+        .with_final_url(url.to_string());
+
+    match content_type {
+        Some(content_type) => resource.with_content_type(content_type),
+        None => resource,
+    }
+}
+
+/// Sends a `GET` request configured by the given options.
+pub(crate) fn get(url: &str, https_only: bool, options: &OpenOptions) -> Result<Response> {
+    get_with_headers(url, https_only, options, &[])
+}
+
+/// Sends a `GET` request configured by the given options, with additional
+/// request headers.
+pub(crate) fn get_with_headers(
+    url: &str,
+    https_only: bool,
+    options: &OpenOptions,
+    headers: &[(header::HeaderName, String)],
+) -> Result<Response> {
+    send(Method::GET, url, https_only, options, headers, &[])
+}
+
+/// Sends a `POST` request without a body, configured by the given options.
+#[cfg(feature = "ipfs")]
+pub(crate) fn post(url: &str, https_only: bool, options: &OpenOptions) -> Result<Response> {
+    send(Method::POST, url, https_only, options, &[], &[])
+}
+
+/// Sends a `POST` request with the given body, configured by the given
+/// options, with additional request headers.
+#[cfg(feature = "git")]
+pub(crate) fn post_with_body(
+    url: &str,
+    https_only: bool,
+    options: &OpenOptions,
+    headers: &[(header::HeaderName, String)],
+    body: &[u8],
+) -> Result<Response> {
+    send(Method::POST, url, https_only, options, headers, body)
+}
+
+fn send(
+    method: Method,
+    url: &str,
+    https_only: bool,
+    options: &OpenOptions,
+    headers: &[(header::HeaderName, String)],
+    body: &[u8],
+) -> Result<Response> {
+    let client = client(https_only, options)?;
+
+    options.get_retry().run(|| {
+        // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.Client.html#method.request
+        // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.RequestBuilder.html
+        let mut request = client.request(method.clone(), url);
+        for (name, value) in options.get_headers() {
+            request = request.header(name, value);
+        }
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        if !body.is_empty() {
+            request = request.body(body.to_vec());
+        }
+        let mut response = request.send()?;
+
+        let status = response.status();
+        if is_error_status(status, options) {
+            let retry_after = retry_after(response.headers());
+            let mut body_excerpt = Vec::with_capacity(BODY_EXCERPT_LEN as usize);
+            let _ = response
+                .by_ref()
+                .take(BODY_EXCERPT_LEN)
+                .read_to_end(&mut body_excerpt);
+            let url = response.url().as_str();
+            return Err(status_error(status, url, &body_excerpt, retry_after));
+        }
+
+        check_content_length(response.content_length(), options)?;
+
+        Ok(response)
+    })
+}
+
+/// Returns an HTTP client configured by the given options, reusing a pooled
+/// client if the options belong to a [`Client`](crate::Client).
+pub(crate) fn client(https_only: bool, options: &OpenOptions) -> Result<Client> {
+    let Some(state) = options.get_state() else {
+        return build_client(https_only, options);
+    };
+
+    let key = ClientKey::new(https_only, options);
+    let mut pool = state.http.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = pool.get(&key) {
+        return Ok(client.clone());
+    }
+    let client = build_client(https_only, options)?;
+    pool.insert(key, client.clone());
+    Ok(client)
+}
+
+/// The options that an HTTP client is built from, identifying which
+/// pooled client may serve a request.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct ClientKey {
+    https_only: bool,
+    user_agent: String,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    max_redirects: usize,
+    root_certificates: Vec<Vec<u8>>,
+    tls_built_in_root_certs: bool,
+    #[cfg(feature = "git")]
+    same_origin_redirects: bool,
+}
+
+impl ClientKey {
+    fn new(https_only: bool, options: &OpenOptions) -> Self {
+        Self {
+            https_only,
+            user_agent: options.get_user_agent().into(),
+            connect_timeout: options.get_connect_timeout(),
+            read_timeout: options.get_read_timeout(),
+            max_redirects: options.get_max_redirects(),
+            root_certificates: options.get_root_certificates().map(Vec::from).collect(),
+            tls_built_in_root_certs: options.get_tls_built_in_root_certs(),
+            #[cfg(feature = "git")]
+            same_origin_redirects: options.get_same_origin_redirects(),
+        }
+    }
+}
+
+/// Builds a new HTTP client configured by the given options.
+fn build_client(https_only: bool, options: &OpenOptions) -> Result<Client> {
     // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.ClientBuilder.html
-    let mut client = ClientBuilder::new()
-        .user_agent(USER_AGENT)
-        .redirect(redirect::Policy::default());
+    let mut client = configure!(ClientBuilder::new(), https_only, options)
+        .connect_timeout(options.get_connect_timeout());
+
+    if let Some(timeout) = options.get_read_timeout() {
+        client = client.timeout(timeout);
+    }
+
+    Ok(client.build()?)
+}
+
+fn is_error_status(status: StatusCode, options: &OpenOptions) -> bool {
+    options.get_error_for_status() && (status.is_client_error() || status.is_server_error())
+}
+
+fn status_error(
+    status: StatusCode,
+    url: &str,
+    body_excerpt: &[u8],
+    retry_after: Option<Duration>,
+) -> Error {
+    Error::HttpStatus {
+        status: status.as_u16(),
+        url: url.to_string(),
+        body_excerpt: String::from_utf8_lossy(body_excerpt).into_owned(),
+        retry_after,
+    }
+}
+
+/// Parses the `Retry-After` header, given either as a number of seconds or
+/// as a date.
+///
+/// See: https://www.rfc-editor.org/rfc/rfc9110.html#name-retry-after
+fn retry_after(headers: &header::HeaderMap) -> Option<Duration> {
+    let value = headers.get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => {
+            let date = httpdate::parse_http_date(value).ok()?;
+            Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+        }
+    }
+}
+
+/// Fails early if the announced length already exceeds the size limit.
+fn check_content_length(length: Option<u64>, options: &OpenOptions) -> Result<()> {
+    if let (Some(limit), Some(length)) = (options.get_max_size(), length) {
+        if length > limit {
+            return Err(Error::TooLarge(limit));
+        }
+    }
+    Ok(())
+}
+
+/// Opens a URL for random access, using `Range` requests.
+///
+/// See: https://www.rfc-editor.org/rfc/rfc9110.html#name-range-requests
+pub fn open_seekable<'a, 'b>(
+    url: &'a Url<'b>,
+    secure: bool,
+    options: &OpenOptions,
+) -> Result<Box<dyn ReadSeek>> {
+    Ok(Box::new(RangeReader::new(url.as_str(), secure, options)?))
+}
+
+/// A seekable reader over an HTTP resource, which issues a new `Range`
+/// request whenever it is read after seeking.
+pub(crate) struct RangeReader {
+    request: RangeRequest,
+    length: u64,
+    position: u64,
+    response: Option<Response>,
+}
+
+impl RangeReader {
+    /// Requests the whole resource, failing with
+    /// [`Error::RangeNotSupported`] unless the server accepts byte ranges.
+    pub(crate) fn new(url: &str, https_only: bool, options: &OpenOptions) -> Result<Self> {
+        let mut request = RangeRequest::new(client(https_only, options)?, url, options);
+
+        let response = options.get_retry().run(|| request.send(0))?;
+        let headers = response.headers();
+        let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+        let length = match response.status() {
+            StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE => {
+                header(header::CONTENT_RANGE).and_then(parse_content_range_length)
+            }
+            StatusCode::OK if header(header::ACCEPT_RANGES) == Some("bytes") => {
+                response.content_length()
+            }
+            _ => None,
+        }
+        .ok_or_else(|| Error::RangeNotSupported(url.to_string()))?;
+
+        request.pin(&response);
+
+        Ok(Self {
+            request,
+            length,
+            position: 0,
+            response: (response.status() != StatusCode::RANGE_NOT_SATISFIABLE).then_some(response),
+        })
+    }
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.position >= self.length || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let response = match self.response.as_mut() {
+            Some(response) => response,
+            None => self.response.insert(self.request.resume(self.position)?),
+        };
+
+        let n = response.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+/// A `GET` request for the contents of a resource from some offset
+/// onwards, conditional on the resource not having changed.
+///
+/// See: https://www.rfc-editor.org/rfc/rfc9110.html#name-if-range
+struct RangeRequest {
+    client: Client,
+    url: String,
+    headers: Vec<(String, String)>,
+    validator: Option<String>,
+}
+
+impl RangeRequest {
+    fn new(client: Client, url: &str, options: &OpenOptions) -> Self {
+        Self {
+            client,
+            url: url.to_string(),
+            headers: options
+                .get_headers()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            validator: None,
+        }
+    }
 
-    if secure {
-        client = client.https_only(true);
+    /// Pins later requests to the version of the resource in the given
+    /// response, which also needn't follow the same redirects again.
+    fn pin(&mut self, response: &Response) {
+        let headers = response.headers();
+        let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+        // Weak entity tags can't be used with `If-Range`:
+        self.validator = match header(header::ETAG) {
+            Some(etag) if !etag.starts_with("W/") => Some(etag.to_string()),
+            _ => header(header::LAST_MODIFIED).map(String::from),
+        };
+        self.url = response.url().to_string();
+    }
+
+    /// Sends a `GET` request for the contents from `offset` onwards.
+    fn send(&self, offset: u64) -> Result<Response> {
+        let mut request = self
+            .client
+            .get(&self.url)
+            .header(header::RANGE, format!("bytes={}-", offset));
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(validator) = &self.validator {
+            request = request.header(header::IF_RANGE, validator);
+        }
+        let mut response = request.send()?;
+
+        let status = response.status();
+        if status.is_client_error() && status != StatusCode::RANGE_NOT_SATISFIABLE
+            || status.is_server_error()
+        {
+            let retry_after = retry_after(response.headers());
+            let mut body_excerpt = Vec::with_capacity(BODY_EXCERPT_LEN as usize);
+            let _ = response
+                .by_ref()
+                .take(BODY_EXCERPT_LEN)
+                .read_to_end(&mut body_excerpt);
+            let url = response.url().as_str();
+            return Err(status_error(status, url, &body_excerpt, retry_after));
+        }
+
+        Ok(response)
+    }
+
+    /// Sends a `GET` request for the contents from `offset` onwards,
+    /// failing unless the server responds with exactly that range.
+    fn resume(&self, offset: u64) -> Result<Response> {
+        let response = self.send(offset)?;
+
+        let start = response
+            .headers()
+            .get(header::CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_content_range_start);
+
+        match (response.status(), start) {
+            (StatusCode::PARTIAL_CONTENT, Some(start)) if start == offset => Ok(response),
+            // A full response to an `If-Range` request means the resource changed:
+            (StatusCode::OK, _) if self.validator.is_some() => {
+                Err(Error::ResourceChanged(self.url.clone()))
+            }
+            _ => Err(Error::RangeNotSupported(self.url.clone())),
+        }
     }
+}
+
+/// Wraps a response body, exposing the response headers as metadata, so
+/// that reading resumes with a `Range` request after a transient error.
+///
+/// Resumption requires a strong validator, so as not to splice together
+/// different versions of the resource.
+pub(crate) fn into_resumable_resource(
+    response: Response,
+    https_only: bool,
+    options: &OpenOptions,
+) -> Result<Resource> {
+    let attempts = options.get_max_resume_attempts();
+    let accepts_ranges = response
+        .headers()
+        .get(header::ACCEPT_RANGES)
+        .map_or(true, |v| v != "none");
+    if attempts == 0 || response.status() != StatusCode::OK || !accepts_ranges {
+        return Ok(into_resource(response));
+    }
+
+    let mut request = RangeRequest::new(
+        client(https_only, options)?,
+        response.url().as_str(),
+        options,
+    );
+    request.pin(&response);
+    if request.validator.is_none() {
+        return Ok(into_resource(response));
+    }
+
+    Ok(into_resource(response).map_reader(|reader| {
+        Box::new(Resume::new(reader, attempts, move |offset| {
+            Ok(Box::new(request.resume(offset)?))
+        }))
+    }))
+}
+
+impl Seek for RangeReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.length.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
 
-    // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.Client.html#method.get
-    // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.RequestBuilder.html
-    let response = client.build()?.get(url.as_str()).send()?;
+        if position != self.position {
+            self.response = None;
+            self.position = position;
+        }
+        Ok(position)
+    }
+}
+
+/// Parses the first byte position from a `Content-Range` header value,
+/// e.g. `bytes 100-199/1234`.
+fn parse_content_range_start(value: &str) -> Option<u64> {
+    let (start, _) = value.strip_prefix("bytes ")?.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+/// Parses the complete length from a `Content-Range` header value, e.g.
+/// `bytes 0-99/1234` or `bytes */1234`.
+fn parse_content_range_length(value: &str) -> Option<u64> {
+    let (_, length) = value.strip_prefix("bytes ")?.rsplit_once('/')?;
+    length.trim().parse().ok()
+}
+
+/// Opens a URL asynchronously, streaming the response body.
+#[cfg(feature = "async")]
+pub async fn open_async<'a, 'b>(
+    url: &'a Url<'b>,
+    secure: bool,
+    options: &OpenOptions,
+) -> Result<ByteStream> {
+    let (stream, _) = open_async_with_metadata(url, secure, options).await?;
+
+    Ok(stream)
+}
+
+/// Opens a URL asynchronously, streaming the response body, together with
+/// the metadata in the response headers as a resource without contents.
+#[cfg(feature = "async")]
+pub(crate) async fn open_async_with_metadata(
+    url: &Url<'_>,
+    secure: bool,
+    options: &OpenOptions,
+) -> Result<(ByteStream, Resource)> {
+    let response = get_async(url.as_str(), secure, options).await?;
+    let metadata = describe(
+        response.headers(),
+        response.url(),
+        response.content_length(),
+    );
+
+    Ok((into_stream(response), metadata))
+}
+
+/// Wraps an async response body as a stream of chunks.
+#[cfg(feature = "async")]
+pub(crate) fn into_stream(response: reqwest::Response) -> ByteStream {
+    use futures_util::TryStreamExt;
+
+    // See: https://docs.rs/reqwest/latest/reqwest/struct.Response.html#method.bytes_stream
+    Box::pin(
+        response
+            .bytes_stream()
+            .map_err(|e| std::io::Error::from(Error::from(e))),
+    )
+}
+
+/// Sends a `GET` request configured by the given options, asynchronously.
+#[cfg(feature = "async")]
+pub(crate) async fn get_async(
+    url: &str,
+    https_only: bool,
+    options: &OpenOptions,
+) -> Result<reqwest::Response> {
+    send_async(Method::GET, url, https_only, options, &[]).await
+}
+
+/// Sends a request configured by the given options, with additional
+/// request headers, asynchronously.
+#[cfg(feature = "async")]
+pub(crate) async fn send_async(
+    method: Method,
+    url: &str,
+    https_only: bool,
+    options: &OpenOptions,
+    headers: &[(header::HeaderName, String)],
+) -> Result<reqwest::Response> {
+    let client = async_client(https_only, options)?;
+
+    let send = || async {
+        // See: https://docs.rs/reqwest/latest/reqwest/struct.RequestBuilder.html
+        let mut request = client.request(method.clone(), url);
+        for (name, value) in options.get_headers() {
+            request = request.header(name, value);
+        }
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let mut response = request.send().await?;
+
+        let status = response.status();
+        if is_error_status(status, options) {
+            let retry_after = retry_after(response.headers());
+            let mut body_excerpt = Vec::with_capacity(BODY_EXCERPT_LEN as usize);
+            while body_excerpt.len() < BODY_EXCERPT_LEN as usize {
+                match response.chunk().await {
+                    Ok(Some(chunk)) => body_excerpt.extend_from_slice(&chunk),
+                    _ => break,
+                }
+            }
+            body_excerpt.truncate(BODY_EXCERPT_LEN as usize);
+            let url = response.url().as_str();
+            return Err(status_error(status, url, &body_excerpt, retry_after));
+        }
+
+        check_content_length(response.content_length(), options)?;
+
+        Ok(response)
+    };
+
+    options.get_retry().run_async(send).await
+}
+
+/// Returns an async HTTP client configured by the given options, reusing a
+/// pooled client if the options belong to a [`Client`](crate::Client).
+#[cfg(feature = "async")]
+pub(crate) fn async_client(https_only: bool, options: &OpenOptions) -> Result<reqwest::Client> {
+    let Some(state) = options.get_state() else {
+        return build_async_client(https_only, options);
+    };
+
+    let key = ClientKey::new(https_only, options);
+    let mut pool = state.async_http.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = pool.get(&key) {
+        return Ok(client.clone());
+    }
+    let client = build_async_client(https_only, options)?;
+    pool.insert(key, client.clone());
+    Ok(client)
+}
+
+/// Builds a new async HTTP client configured by the given options.
+#[cfg(feature = "async")]
+fn build_async_client(https_only: bool, options: &OpenOptions) -> Result<reqwest::Client> {
+    // See: https://docs.rs/reqwest/latest/reqwest/struct.ClientBuilder.html
+    let mut client = configure!(reqwest::ClientBuilder::new(), https_only, options);
+
+    if let Some(timeout) = options.get_connect_timeout() {
+        client = client.connect_timeout(timeout);
+    }
+
+    if let Some(timeout) = options.get_read_timeout() {
+        client = client.read_timeout(timeout);
+    }
 
-    Ok(Box::new(response))
+    Ok(client.build()?)
 }
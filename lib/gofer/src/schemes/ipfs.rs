@@ -1,28 +1,401 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{Error, Read, Result, Url};
-use reqwest::{blocking::ClientBuilder, redirect};
+use crate::{
+    car::{CarReader, CarVerifier, Cid},
+    dns,
+    ipns::{self, Key, Name, Resolver},
+    schemes::http,
+    Error, OpenOptions, Resource, Result, RetryPolicy, Url,
+};
+use percent_encoding::percent_decode_str;
+use reqwest::header;
+use std::{io::Read, time::SystemTime};
 
-static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
-static GATEWAY: &str = "https://ipfs.io";
+/// The media type requested from trustless gateways.
+///
+/// See: https://specs.ipfs.tech/http-gateways/trustless-gateway/#car-responses
+const CAR: &str = "application/vnd.ipld.car; version=1; order=dfs; dups=y";
 
+/// The media type of a signed IPNS record.
+///
+/// See: https://specs.ipfs.tech/http-gateways/trustless-gateway/#ipns-record-responses
+const IPNS_RECORD: &str = "application/vnd.ipfs.ipns-record";
+
+/// Opens an IPFS path, trying each of the configured gateways in turn.
+///
+/// HTTP gateways are asked for a CAR, whose blocks are verified against the
+/// CID in the URL as they are read, so that a gateway can't substitute
+/// other content. A Kubo RPC API is trusted to do that verification itself.
+///
+/// The name in an `ipns:` URL is first resolved to a CID, which is reported
+/// in the [`cid`](Resource::cid) of the resource. A domain name is resolved
+/// through its DNSLink record, and any other name through an IPNS record,
+/// whose signature is verified unless it comes from a Kubo RPC API.
+///
 /// See: https://en.wikipedia.org/wiki/InterPlanetary_File_System
-pub fn open<'a, 'b>(url: &'a Url<'b>) -> Result<Box<dyn Read>> {
-    // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.ClientBuilder.html
-    let client = ClientBuilder::new()
-        .user_agent(USER_AGENT)
-        .redirect(redirect::Policy::default())
-        .https_only(true);
-
-    let url = url
-        .as_str()
-        .strip_prefix("ipfs://")
-        .ok_or_else(|| Error::InvalidIpfsUrl(url.to_string()))
-        .map(|id| format!("{}/ipfs/{}", GATEWAY, id))?;
-
-    // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.Client.html#method.get
-    // See: https://docs.rs/reqwest/latest/reqwest/blocking/struct.RequestBuilder.html
-    let response = client.build()?.get(url).send()?;
-
-    Ok(Box::new(response))
+/// See: https://specs.ipfs.tech/http-gateways/trustless-gateway/
+pub fn open<'a, 'b>(url: &'a Url<'b>, options: &OpenOptions) -> Result<Resource> {
+    let path = IpfsPath::resolve(url, options)?;
+    let cid = path.cid.to_string();
+
+    let resource = failover(options, |gateway, options| match gateway {
+        Gateway::Kubo(api) => {
+            let response = http::post(&path.kubo_url(api), false, options)?;
+            Ok(http::into_resource(response))
+        }
+        Gateway::Http(gateway) => {
+            // The CAR of immutable content is cached like any other response,
+            // and is verified whether it comes from the cache or not:
+            let mut options = options.clone();
+            options.header(header::ACCEPT.as_str(), CAR);
+            let url = path.gateway_url(gateway);
+            let car = http::fetch(&url, gateway.starts_with("https:"), &options)?;
+            let final_url = car.final_url.clone().unwrap_or(url);
+            check_car(car.content_type.as_deref(), &final_url)?;
+            let reader = CarReader::new(car.into_reader(), path.verifier());
+            Ok(Resource::new(reader).with_final_url(final_url))
+        }
+    })?;
+    Ok(resource.with_cid(cid))
+}
+
+/// Opens an IPFS file for random access through the configured gateways.
+///
+/// Ranges can't be verified against the CID, so this fails unless the
+/// gateways are [trusted](OpenOptions::ipfs_trust_gateway).
+pub fn open_seekable<'a, 'b>(
+    url: &'a Url<'b>,
+    options: &OpenOptions,
+) -> Result<Box<dyn crate::ReadSeek>> {
+    if !options.get_ipfs_trust_gateway() {
+        return Err(Error::NotSeekable(url.to_string()));
+    }
+    let path = IpfsPath::resolve(url, options)?;
+
+    failover(options, |gateway, options| match gateway {
+        Gateway::Kubo(api) => Err(Error::NotSeekable(api.to_string())),
+        Gateway::Http(gateway) => Ok(Box::new(http::RangeReader::new(
+            &path.gateway_url(gateway),
+            gateway.starts_with("https:"),
+            options,
+        )?) as Box<dyn crate::ReadSeek>),
+    })
+}
+
+/// See: https://en.wikipedia.org/wiki/InterPlanetary_File_System
+#[cfg(feature = "async")]
+pub async fn open_async<'a, 'b>(
+    url: &'a Url<'b>,
+    options: &OpenOptions,
+) -> Result<crate::r#async::ByteStream> {
+    use reqwest::Method;
+
+    let path = IpfsPath::resolve_async(url, options).await?;
+    let path = &path;
+
+    failover_async(options, |gateway, options| async move {
+        match Gateway::from(gateway.as_str()) {
+            Gateway::Kubo(api) => {
+                let url = path.kubo_url(api);
+                http::send_async(Method::POST, &url, false, &options, &[])
+                    .await
+                    .map(http::into_stream)
+            }
+            Gateway::Http(gateway) => {
+                let url = path.gateway_url(gateway);
+                let https_only = gateway.starts_with("https:");
+                let headers = [(header::ACCEPT, CAR.into())];
+                let response =
+                    http::send_async(Method::GET, &url, https_only, &options, &headers).await?;
+                let content_type = response.headers().get(header::CONTENT_TYPE);
+                check_car(
+                    content_type.and_then(|value| value.to_str().ok()),
+                    response.url().as_str(),
+                )?;
+                let stream = http::into_stream(response);
+                Ok(crate::car::verify_stream(stream, path.verifier()))
+            }
+        }
+    })
+    .await
+}
+
+/// Resolves an IPNS name that is a public key through the configured
+/// gateways.
+fn resolve_key(key: &Key, options: &OpenOptions) -> Result<String> {
+    failover(options, |gateway, options| match gateway {
+        Gateway::Kubo(api) => {
+            let response = http::post(&key.kubo_url(api), false, options)?;
+            let body = read_limited(response)?;
+            ipns::kubo_path(&key.name, &String::from_utf8_lossy(&body))
+        }
+        Gateway::Http(gateway) => {
+            let response = http::get_with_headers(
+                &key.gateway_url(gateway),
+                gateway.starts_with("https:"),
+                options,
+                &[(header::ACCEPT, IPNS_RECORD.into())],
+            )?;
+            let record = read_limited(response)?;
+            key.verify(&record, SystemTime::now())
+        }
+    })
+}
+
+/// Resolves an IPNS name that is a public key through the configured
+/// gateways, asynchronously.
+#[cfg(feature = "async")]
+async fn resolve_key_async(key: &Key, options: &OpenOptions) -> Result<String> {
+    use reqwest::Method;
+
+    failover_async(options, |gateway, options| async move {
+        match Gateway::from(gateway.as_str()) {
+            Gateway::Kubo(api) => {
+                let url = key.kubo_url(api);
+                let response = http::send_async(Method::POST, &url, false, &options, &[]).await?;
+                let body = read_limited_async(response).await?;
+                ipns::kubo_path(&key.name, &String::from_utf8_lossy(&body))
+            }
+            Gateway::Http(gateway) => {
+                let url = key.gateway_url(gateway);
+                let https_only = gateway.starts_with("https:");
+                let headers = [(header::ACCEPT, IPNS_RECORD.into())];
+                let response =
+                    http::send_async(Method::GET, &url, https_only, &options, &headers).await?;
+                let record = read_limited_async(response).await?;
+                key.verify(&record, SystemTime::now())
+            }
+        }
+    })
+    .await
+}
+
+/// Reads a response to an IPNS request, failing as soon as it is larger
+/// than a record may be.
+fn read_limited(response: reqwest::blocking::Response) -> Result<Vec<u8>> {
+    check_record_len(response.content_length().unwrap_or_default())?;
+    let mut body = Vec::new();
+    response
+        .take(ipns::MAX_RECORD_LEN + 1)
+        .read_to_end(&mut body)
+        .map_err(|error| Error::IpnsResolutionFailed(error.to_string()))?;
+    check_record_len(body.len() as u64)?;
+    Ok(body)
+}
+
+/// Reads a response to an IPNS request asynchronously, failing as soon as
+/// it is larger than a record may be.
+#[cfg(feature = "async")]
+async fn read_limited_async(mut response: reqwest::Response) -> Result<Vec<u8>> {
+    check_record_len(response.content_length().unwrap_or_default())?;
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        check_record_len(body.len() as u64)?;
+    }
+    Ok(body)
+}
+
+/// Checks that a response to an IPNS request is no larger than a record
+/// may be.
+fn check_record_len(len: u64) -> Result<()> {
+    match len <= ipns::MAX_RECORD_LEN {
+        true => Ok(()),
+        false => Err(Error::IpfsVerificationFailed(format!(
+            "IPNS response of {} bytes is too large",
+            len
+        ))),
+    }
+}
+
+/// A gateway through which IPFS content is retrieved.
+#[derive(Clone, Copy, Debug)]
+enum Gateway<'a> {
+    /// The RPC API of a Kubo node, such as `http://127.0.0.1:5001/api/v0`.
+    Kubo(&'a str),
+    /// An HTTP gateway that supports trustless requests.
+    Http(&'a str),
+}
+
+impl<'a> From<&'a str> for Gateway<'a> {
+    fn from(gateway: &'a str) -> Self {
+        let gateway = gateway.trim_end_matches('/');
+        match gateway.ends_with("/api/v0") {
+            true => Gateway::Kubo(gateway),
+            false => Gateway::Http(gateway),
+        }
+    }
+}
+
+/// Tries each gateway once in turn, failing over to the next one after any
+/// error, and retrying the whole list according to the retry policy.
+fn failover<T>(
+    options: &OpenOptions,
+    mut open: impl FnMut(Gateway, &OpenOptions) -> Result<T>,
+) -> Result<T> {
+    let gateways = options.get_ipfs_gateways();
+    let once = once(options);
+
+    options.get_retry().run(|| {
+        let mut last_error = None;
+        for gateway in &gateways {
+            match open(Gateway::from(gateway.as_str()), &once) {
+                Ok(result) => return Ok(result),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(last_error.expect("there is at least one gateway"))
+    })
+}
+
+/// Tries each gateway once in turn, asynchronously.
+///
+/// See [`failover`].
+#[cfg(feature = "async")]
+async fn failover_async<T, F: core::future::Future<Output = Result<T>>>(
+    options: &OpenOptions,
+    open: impl Fn(String, OpenOptions) -> F,
+) -> Result<T> {
+    let gateways = options.get_ipfs_gateways();
+    let once = once(options);
+
+    let attempt = || async {
+        let mut last_error = None;
+        for gateway in &gateways {
+            match open(gateway.clone(), once.clone()).await {
+                Ok(result) => return Ok(result),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(last_error.expect("there is at least one gateway"))
+    };
+
+    options.get_retry().run_async(attempt).await
+}
+
+/// Returns the options for a single attempt through one gateway.
+fn once(options: &OpenOptions) -> OpenOptions {
+    let mut options = options.clone();
+    options.retry(RetryPolicy::none());
+    options
+}
+
+/// Checks that a gateway responded with a CAR, rather than with content
+/// that can't be verified.
+fn check_car(content_type: Option<&str>, url: &str) -> Result<()> {
+    let content_type = content_type.unwrap_or_default();
+    match content_type.starts_with("application/vnd.ipld.car") {
+        true => Ok(()),
+        false => Err(Error::IpfsVerificationFailed(format!(
+            "expected a CAR from {}, got {:?}",
+            url, content_type
+        ))),
+    }
+}
+
+/// An immutable IPFS path, such as the one in an `ipfs://<cid>/<path>` URL.
+struct IpfsPath {
+    cid: Cid,
+    /// The CID and path, without a leading slash.
+    path: String,
+}
+
+impl IpfsPath {
+    /// Parses an `ipfs:` URL, or resolves an `ipns:` URL to the immutable
+    /// path that its name currently points to.
+    fn resolve(url: &Url, options: &OpenOptions) -> Result<Self> {
+        let mut resolver = Resolver::default();
+        let mut value = content_path(url)?;
+        loop {
+            if let Some(path) = resolver.step(&value)? {
+                return Self::parse(url, &path);
+            }
+            value = match resolver.name()? {
+                Name::Domain(domain) => {
+                    let records = dns::lookup_txt(&format!("_dnslink.{}", domain), options)?;
+                    ipns::dnslink(&domain, &records)?
+                }
+                Name::Key(key) => resolve_key(&key, options)?,
+            };
+        }
+    }
+
+    /// Parses an `ipfs:` URL, or resolves an `ipns:` URL, asynchronously.
+    ///
+    /// See [`resolve`](Self::resolve).
+    #[cfg(feature = "async")]
+    async fn resolve_async(url: &Url<'_>, options: &OpenOptions) -> Result<Self> {
+        let mut resolver = Resolver::default();
+        let mut value = content_path(url)?;
+        loop {
+            if let Some(path) = resolver.step(&value)? {
+                return Self::parse(url, &path);
+            }
+            value = match resolver.name()? {
+                Name::Domain(domain) => {
+                    let name = format!("_dnslink.{}", domain);
+                    let records = dns::lookup_txt_async(&name, options).await?;
+                    ipns::dnslink(&domain, &records)?
+                }
+                Name::Key(key) => resolve_key_async(&key, options).await?,
+            };
+        }
+    }
+
+    /// Parses a `<cid>/<path>` that the given URL resolved to.
+    fn parse(url: &Url, path: &str) -> Result<Self> {
+        let cid = path.split('/').next().unwrap_or_default();
+        let cid = Cid::parse(cid).ok_or_else(|| match url.as_str().starts_with("ipfs:") {
+            true => Error::InvalidIpfsUrl(url.to_string()),
+            false => Error::IpnsResolutionFailed(format!("{} points to invalid CID {}", url, cid)),
+        })?;
+        Ok(Self {
+            cid,
+            path: path.to_string(),
+        })
+    }
+
+    /// Returns a verifier for the entity at this path.
+    fn verifier(&self) -> CarVerifier {
+        let segments = self.path.split('/').skip(1).filter(|s| !s.is_empty());
+        let segments = segments.map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned());
+        CarVerifier::new(self.cid.clone(), segments)
+    }
+
+    /// See: https://specs.ipfs.tech/http-gateways/path-gateway/
+    fn gateway_url(&self, gateway: &str) -> String {
+        format!("{}/ipfs/{}?dag-scope=entity", gateway, self.path)
+    }
+
+    /// See: https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-cat
+    fn kubo_url(&self, api: &str) -> String {
+        format!("{}/cat?arg=/ipfs/{}", api, self.path.replace('&', "%26"))
+    }
+}
+
+/// Returns the content path in an `ipfs:` or `ipns:` URL, such as
+/// `/ipns/<name>/<path>`.
+fn content_path(url: &Url) -> Result<String> {
+    let invalid = || Error::InvalidIpfsUrl(url.to_string());
+    let (scheme, rest) = url.as_str().split_once("://").ok_or_else(invalid)?;
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    if rest.starts_with('/') || rest.is_empty() {
+        return Err(invalid());
+    }
+    Ok(format!("/{}/{}", scheme.to_ascii_lowercase(), rest))
+}
+
+impl Key {
+    /// See: https://specs.ipfs.tech/http-gateways/trustless-gateway/#ipns-record-responses
+    fn gateway_url(&self, gateway: &str) -> String {
+        format!("{}/ipns/{}", gateway, self.name)
+    }
+
+    /// See: https://docs.ipfs.tech/reference/kubo/rpc/#api-v0-name-resolve
+    fn kubo_url(&self, api: &str) -> String {
+        format!(
+            "{}/name/resolve?arg=/ipns/{}&recursive=false",
+            api, self.name
+        )
+    }
 }
@@ -1,9 +1,51 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{Read, Result, Url};
+use super::ssh::{connect, redacted, remote_path};
+use crate::{Error, OpenOptions, Read, Resource, Result, Url};
+use ssh2::Channel;
+use std::path::Path;
 
 /// See: https://en.wikipedia.org/wiki/Secure_copy_protocol
 /// See: https://man.openbsd.org/scp
-pub fn open<'a, 'b>(_url: &'a Url<'b>) -> Result<Box<dyn Read>> {
-    todo!() // TODO
+/// See: https://datatracker.ietf.org/doc/html/draft-ietf-secsh-scp-sftp-ssh-uri-04
+pub fn open<'a, 'b>(url: &'a Url<'b>, options: &OpenOptions) -> Result<Resource> {
+    let path = remote_path(url)
+        .filter(|path| !path.ends_with('/'))
+        .ok_or_else(|| Error::InvalidScpUrl(redacted(url)))?;
+
+    let session = connect(url, options)?;
+
+    // See: https://docs.rs/ssh2/latest/ssh2/struct.Session.html#method.scp_recv
+    let (channel, stat) = session.scp_recv(Path::new(&path))?;
+
+    // The channel keeps the session alive for as long as it is being read.
+    // The SCP protocol follows the file contents with a status byte, so we
+    // must take care not to read past the end of the file:
+    let reader = ScpReader {
+        inner: channel.take(stat.size()),
+    };
+
+    Ok(Resource::new(reader)
+        .with_content_length(stat.size())
+        .with_final_url(redacted(url)))
+}
+
+/// A reader over an SCP download that closes the channel when dropped.
+struct ScpReader {
+    inner: std::io::Take<Channel>,
+}
+
+impl Read for ScpReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Drop for ScpReader {
+    fn drop(&mut self) {
+        let channel = self.inner.get_mut();
+        let _ = channel.send_eof();
+        let _ = channel.close();
+        let _ = channel.wait_close();
+    }
 }
@@ -0,0 +1,108 @@
+// This is free and unencumbered software released into the public domain.
+
+use super::ssh::{connect, redacted, remote_path};
+use crate::{Cursor, Error, OpenOptions, Resource, Result, Url};
+use ssh2::{FileStat, Sftp};
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// An entry in a remote directory listing.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SftpEntry {
+    /// The file name, relative to the listed directory.
+    pub name: String,
+    /// Whether the entry is itself a directory.
+    pub is_dir: bool,
+    /// The size of the file in bytes, if known.
+    pub size: Option<u64>,
+    /// The time at which the file was last modified, if known.
+    pub modified: Option<SystemTime>,
+}
+
+/// Opens a remote file for streaming, or lists a remote directory.
+///
+/// Directories are returned as a `text/plain` listing with one entry per
+/// line, where the names of subdirectories end with a `/`.
+///
+/// See: https://en.wikipedia.org/wiki/SSH_File_Transfer_Protocol
+/// See: https://datatracker.ietf.org/doc/html/draft-ietf-secsh-filexfer-02
+/// See: https://datatracker.ietf.org/doc/html/draft-ietf-secsh-scp-sftp-ssh-uri-04
+pub fn open<'a, 'b>(url: &'a Url<'b>, options: &OpenOptions) -> Result<Resource> {
+    let path = remote_path(url).ok_or_else(|| Error::InvalidSftpUrl(redacted(url)))?;
+
+    let sftp = connect_sftp(url, options)?;
+
+    // See: https://docs.rs/ssh2/latest/ssh2/struct.Sftp.html#method.stat
+    let stat = sftp
+        .stat(Path::new(&path))
+        .map_err(Error::FailedSftpRequest)?;
+
+    if stat.is_dir() {
+        let listing: String = list(&sftp, &path)?
+            .into_iter()
+            .map(|entry| match entry.is_dir {
+                true => format!("{}/\n", entry.name),
+                false => format!("{}\n", entry.name),
+            })
+            .collect();
+        let content_length = listing.len() as u64;
+        return Ok(Resource::new(Cursor::new(listing))
+            .with_content_type("text/plain; charset=utf-8")
+            .with_content_length(content_length)
+            .with_final_url(redacted(url)));
+    }
+
+    // See: https://docs.rs/ssh2/latest/ssh2/struct.Sftp.html#method.open
+    // The file handle keeps the session alive for as long as it is read:
+    let file = sftp
+        .open(Path::new(&path))
+        .map_err(Error::FailedSftpRequest)?;
+
+    Ok(Resource::new(file)
+        .with_content_length(stat.size)
+        .with_last_modified(modified(&stat))
+        .with_final_url(redacted(url)))
+}
+
+/// Lists the entries of a remote directory, sorted by name.
+pub fn read_dir<'a, 'b>(url: &'a Url<'b>, options: &OpenOptions) -> Result<Vec<SftpEntry>> {
+    let path = remote_path(url).ok_or_else(|| Error::InvalidSftpUrl(redacted(url)))?;
+
+    let sftp = connect_sftp(url, options)?;
+
+    list(&sftp, &path)
+}
+
+fn connect_sftp(url: &Url, options: &OpenOptions) -> Result<Sftp> {
+    // See: https://docs.rs/ssh2/latest/ssh2/struct.Session.html#method.sftp
+    connect(url, options)?
+        .sftp()
+        .map_err(Error::FailedSftpRequest)
+}
+
+fn list(sftp: &Sftp, path: &str) -> Result<Vec<SftpEntry>> {
+    // See: https://docs.rs/ssh2/latest/ssh2/struct.Sftp.html#method.readdir
+    let mut entries: Vec<SftpEntry> = sftp
+        .readdir(Path::new(path))
+        .map_err(Error::FailedSftpRequest)?
+        .into_iter()
+        .filter_map(|(path, stat)| {
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            Some(SftpEntry {
+                name,
+                is_dir: stat.is_dir(),
+                size: stat.size,
+                modified: modified(&stat),
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+fn modified(stat: &FileStat) -> Option<SystemTime> {
+    stat.mtime
+        .map(|mtime| SystemTime::UNIX_EPOCH + Duration::from_secs(mtime))
+}
@@ -1,8 +1,19 @@
 // This is free and unencumbered software released into the public domain.
 
-use crate::{Read, Result, Url};
+use crate::{OpenOptions, Resource, Result, Url};
 
 /// See: https://en.wikipedia.org/wiki/Standard_streams#Standard_input_(stdin)
-pub fn open<'a, 'b>(_url: &'a Url<'b>) -> Result<Box<dyn Read>> {
-    Ok(Box::new(std::io::stdin().lock()))
+pub fn open<'a, 'b>(_url: &'a Url<'b>, _options: &OpenOptions) -> Result<Resource> {
+    Ok(Resource::new(std::io::stdin().lock()))
+}
+
+/// See: https://docs.rs/tokio/latest/tokio/io/struct.Stdin.html
+#[cfg(feature = "async")]
+pub async fn open_async<'a, 'b>(
+    _url: &'a Url<'b>,
+    _options: &OpenOptions,
+) -> Result<crate::r#async::ByteStream> {
+    Ok(Box::pin(tokio_util::io::ReaderStream::new(
+        tokio::io::stdin(),
+    )))
 }
@@ -0,0 +1,72 @@
+// This is free and unencumbered software released into the public domain.
+
+use crate::{default_registry, readers::SizeLimit, Client, Error, OpenOptions, Read, Result, Url};
+use std::io::Seek;
+
+/// A reader that can also seek, as returned by [`open_seekable`].
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+
+/// Opens the given URL for random access, using the
+/// [`global`](Client::global) client.
+///
+/// The URL is opened by the seekable handler registered for its scheme in
+/// the [`default_registry`]. The built-in ones support `file:`, `data:`,
+/// and `git+file:` URLs, HTTP-based URLs (`http:`, `https:`, `git:`) on
+/// servers that accept `Range` requests, and FTP URLs using the `REST`
+/// command, as well as `ipfs:` and `ipns:` URLs through
+/// [trusted](OpenOptions::ipfs_trust_gateway) gateways. Other schemes, and
+/// schemes whose handler was overridden without a seekable one, fail with
+/// [`Error::NotSeekable`].
+///
+/// URLs with an expected digest fail the same way, since a digest can't be
+/// checked against the parts of a resource that are read. The
+/// [maximum size](OpenOptions::max_size), if any, bounds the offsets that
+/// can be read.
+///
+/// # Examples
+///
+/// ```edition2021,no_run
+/// # use gofer::*;
+/// # use std::io::{Seek, SeekFrom};
+/// let mut reader = open_seekable("https://example.org/data.parquet")?;
+/// reader.seek(SeekFrom::End(-8))?;
+/// let mut footer = [0u8; 8];
+/// reader.read_exact(&mut footer)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn open_seekable(url: impl AsRef<str>) -> Result<Box<dyn ReadSeek>> {
+    Client::global().open_seekable(url)
+}
+
+pub(crate) fn open_seekable_url_with(
+    url: &Url,
+    options: &OpenOptions,
+) -> Result<Box<dyn ReadSeek>> {
+    #[cfg(feature = "integrity")]
+    if options.get_expected_digest().is_some()
+        || url
+            .fragment_str()
+            .and_then(crate::Digest::from_fragment)
+            .is_some()
+    {
+        return Err(Error::NotSeekable(format!(
+            "{} (its digest can't be verified under random access)",
+            url
+        )));
+    }
+
+    // The registry lock is released before opening, as handlers may block:
+    let handler = default_registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_seekable(url.scheme_str())
+        .ok_or_else(|| Error::NotSeekable(url.scheme_str().to_ascii_lowercase()))?;
+
+    let reader = handler.open_seekable(url, options)?;
+    Ok(match options.get_max_size() {
+        Some(limit) => Box::new(SizeLimit::new(reader, limit)),
+        None => reader,
+    })
+}
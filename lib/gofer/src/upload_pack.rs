@@ -0,0 +1,501 @@
+// This is free and unencumbered software released into the public domain.
+
+//! A client for the git protocol v2, which fetches just the objects needed
+//! to read a single file from any git server.
+//!
+//! See: https://git-scm.com/docs/protocol-v2
+//! See: https://git-scm.com/docs/protocol-common#_pkt_line_format
+//! See: https://git-scm.com/docs/http-protocol
+
+use crate::{
+    pack::{self, Oid},
+    Error, OpenOptions, Result, RetryPolicy,
+};
+use reqwest::header::{HeaderName, ACCEPT, CONTENT_TYPE};
+use std::{
+    collections::HashMap,
+    io::{BufReader, ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    process::{Child, Command, Stdio},
+};
+
+/// The port of the git daemon.
+const DEFAULT_PORT: u16 = 9418;
+
+/// The largest pkt-line, including its length.
+const MAX_PKT_LEN: usize = 65520;
+
+/// The largest packfile read, and the most object data unpacked from it,
+/// unless a maximum size is configured.
+const MAX_PACK_LEN: u64 = 1 << 30;
+
+/// Reads the file at the given path in the given version of a remote
+/// repository, which is a branch, a tag, or a full commit ID.
+///
+/// The remote is a `git://` URL served by a git daemon, an `http://` or
+/// `https://` URL served over smart HTTP, or the path of a local
+/// repository. Only the commit is fetched, without its history and, if the
+/// server supports filters, without the blobs of other files.
+///
+/// The objects in the pack are held in memory, up to the configured
+/// maximum size in all, or 1 GiB without one.
+pub(crate) fn read_file(
+    remote: &str,
+    version: &str,
+    path: &str,
+    options: &OpenOptions,
+) -> Result<Vec<u8>> {
+    let mut once = options.clone();
+    once.retry(RetryPolicy::none());
+    let limit = options.get_max_size().unwrap_or(MAX_PACK_LEN);
+    let lookup = |objects: &HashMap<Oid, pack::Object>, oid: &Oid| {
+        objects
+            .get(oid)
+            .cloned()
+            .ok_or_else(|| Error::InvalidGitData("the server sent an incomplete pack".into()))
+    };
+
+    options.get_retry().run(|| {
+        let mut session = Session::connect(remote, &once)?;
+        let commit = match pack::parse_oid(version) {
+            Some(oid) => oid,
+            None => session.ls_refs(version)?,
+        };
+
+        // Servers without filters send every blob, so stop at the file:
+        let mut objects = session.fetch(&commit, true, limit, |objects| {
+            pack::find_blob(&commit, path, |oid| lookup(objects, oid))
+                .is_ok_and(|blob| objects.contains_key(&blob))
+        })?;
+        let blob = pack::find_blob(&commit, path, |oid| lookup(&objects, oid))?;
+        if let Some(object) = objects.remove(&blob) {
+            return Ok(object.data);
+        }
+
+        // The blob was filtered out, so fetch it on its own:
+        let mut objects =
+            session.fetch(&blob, false, limit, |objects| objects.contains_key(&blob))?;
+        objects
+            .remove(&blob)
+            .map(|object| object.data)
+            .ok_or_else(|| Error::InvalidGitData("the server didn't send the file".into()))
+    })
+}
+
+/// A connection to a git server, after the capability advertisement.
+struct Session {
+    transport: Transport,
+    capabilities: Vec<String>,
+}
+
+enum Transport {
+    /// A git daemon, or `git upload-pack` on this machine, which both take
+    /// any number of commands over the same connection.
+    Stream {
+        reader: BufReader<Box<dyn Read>>,
+        writer: Box<dyn Write>,
+        child: Option<Child>,
+    },
+    /// Smart HTTP, which takes each command in a separate request.
+    Http {
+        url: String,
+        options: Box<OpenOptions>,
+    },
+}
+
+impl Session {
+    fn connect(remote: &str, options: &OpenOptions) -> Result<Self> {
+        let mut transport = if let Some(address) = remote.strip_prefix("git://") {
+            let (authority, path) = address.split_at(address.find('/').unwrap_or(address.len()));
+            let stream = connect(authority, options).map_err(Error::FailedGitRequest)?;
+            let mut request = Vec::new();
+            let header = format!(
+                "git-upload-pack {}\0host={}\0\0version=2\0",
+                path, authority
+            );
+            write_pkt(&mut request, header.as_bytes());
+            let mut writer = stream.try_clone().map_err(Error::FailedGitRequest)?;
+            writer
+                .write_all(&request)
+                .map_err(Error::FailedGitRequest)?;
+            Transport::Stream {
+                reader: BufReader::new(Box::new(stream)),
+                writer: Box::new(writer),
+                child: None,
+            }
+        } else if remote.starts_with("http://") || remote.starts_with("https://") {
+            let url = remote.trim_end_matches('/').to_string();
+            let advertisement = crate::schemes::http::get_with_headers(
+                &format!("{}/info/refs?service=git-upload-pack", url),
+                url.starts_with("https:"),
+                options,
+                &[(HeaderName::from_static("git-protocol"), "version=2".into())],
+            )?;
+            let mut reader = BufReader::new(advertisement);
+            // Unless the server speaks the protocol v2, it names the service:
+            let mut first = read_pkt(&mut reader)?;
+            if matches!(&first, Pkt::Line(line) if line.starts_with(b"# service=")) {
+                read_pkt(&mut reader)?;
+                first = read_pkt(&mut reader)?;
+            }
+            let capabilities = read_capabilities(first, &mut reader)?;
+            let options = Box::new(options.clone());
+            return Ok(Self {
+                transport: Transport::Http { url, options },
+                capabilities,
+            });
+        } else {
+            let path = remote.strip_prefix("file://").unwrap_or(remote);
+            let mut child = Command::new("git")
+                .args(config_args(options))
+                .args(["upload-pack", "--", path])
+                .env("GIT_PROTOCOL", "version=2")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(Error::FailedGitRequest)?;
+            let reader = child.stdout.take().expect("stdout is piped");
+            let writer = child.stdin.take().expect("stdin is piped");
+            Transport::Stream {
+                reader: BufReader::new(Box::new(reader)),
+                writer: Box::new(writer),
+                child: Some(child),
+            }
+        };
+
+        let Transport::Stream { reader, .. } = &mut transport else {
+            unreachable!()
+        };
+        let first = read_pkt(reader)?;
+        let capabilities = read_capabilities(first, reader)?;
+        Ok(Self {
+            transport,
+            capabilities,
+        })
+    }
+
+    /// Checks whether the server supports the given feature of a command.
+    fn supports(&self, command: &str, feature: &str) -> bool {
+        self.capabilities.iter().any(|capability| {
+            capability
+                .strip_prefix(command)
+                .and_then(|features| features.strip_prefix('='))
+                .is_some_and(|features| features.split(' ').any(|f| f == feature))
+        })
+    }
+
+    /// Sends a command, returning a reader of its response.
+    fn command(&mut self, command: &str, arguments: &[String]) -> Result<Box<dyn Read + '_>> {
+        let mut request = Vec::new();
+        write_pkt(&mut request, format!("command={}\n", command).as_bytes());
+        request.extend(b"0001");
+        for argument in arguments {
+            write_pkt(&mut request, format!("{}\n", argument).as_bytes());
+        }
+        request.extend(b"0000");
+
+        match &mut self.transport {
+            Transport::Stream { reader, writer, .. } => {
+                writer
+                    .write_all(&request)
+                    .map_err(Error::FailedGitRequest)?;
+                writer.flush().map_err(Error::FailedGitRequest)?;
+                Ok(Box::new(reader))
+            }
+            Transport::Http { url, options } => {
+                let response = crate::schemes::http::post_with_body(
+                    &format!("{}/git-upload-pack", url),
+                    url.starts_with("https:"),
+                    options,
+                    &[
+                        (CONTENT_TYPE, "application/x-git-upload-pack-request".into()),
+                        (ACCEPT, "application/x-git-upload-pack-result".into()),
+                        (HeaderName::from_static("git-protocol"), "version=2".into()),
+                    ],
+                    &request,
+                )?;
+                Ok(Box::new(BufReader::new(response)))
+            }
+        }
+    }
+
+    /// Returns the commit that the given branch or tag points to, peeling
+    /// annotated tags.
+    fn ls_refs(&mut self, version: &str) -> Result<Oid> {
+        let names = [
+            format!("refs/heads/{}", version),
+            format!("refs/tags/{}", version),
+            version.to_string(),
+        ];
+        let mut arguments = vec!["peel".to_string()];
+        arguments.extend(names.iter().map(|name| format!("ref-prefix {}", name)));
+
+        let mut response = self.command("ls-refs", &arguments)?;
+        let mut refs = Vec::new();
+        while let Pkt::Line(line) = read_pkt(&mut response)? {
+            let line = String::from_utf8_lossy(&line);
+            let mut fields = line.trim_end().split(' ');
+            let (Some(oid), Some(name)) = (fields.next(), fields.next()) else {
+                return Err(Error::InvalidGitData("malformed ref".into()));
+            };
+            let peeled = fields.find_map(|field| field.strip_prefix("peeled:"));
+            let oid = pack::parse_oid(peeled.unwrap_or(oid))
+                .ok_or_else(|| Error::InvalidGitData("malformed ref".into()))?;
+            refs.push((name.to_string(), oid));
+        }
+
+        // Branches take precedence over tags, as with `git checkout`:
+        names
+            .iter()
+            .find_map(|name| refs.iter().find(|(found, _)| found == name))
+            .map(|(_, oid)| *oid)
+            .ok_or_else(|| Error::GitObjectNotFound(version.to_string()))
+    }
+
+    /// Fetches the given object, with a commit's trees but not its history
+    /// nor, if the server supports filters, its blobs.
+    ///
+    /// Reading the pack stops once it is over the given limit, or as soon
+    /// as the objects received so far are `enough`.
+    fn fetch(
+        &mut self,
+        want: &Oid,
+        commit: bool,
+        limit: u64,
+        enough: impl Fn(&HashMap<Oid, pack::Object>) -> bool,
+    ) -> Result<HashMap<Oid, pack::Object>> {
+        let mut arguments = vec![
+            "no-progress".to_string(),
+            "ofs-delta".to_string(),
+            format!("want {}", pack::to_hex(want)),
+        ];
+        if commit && self.supports("fetch", "shallow") {
+            arguments.push("deepen 1".into());
+        }
+        if commit && self.supports("fetch", "filter") {
+            arguments.push("filter blob:none".into());
+        }
+        arguments.push("done".into());
+
+        let mut response = self.command("fetch", &arguments)?;
+        loop {
+            let section = match read_pkt(&mut response)? {
+                Pkt::Line(line) => line,
+                _ => return Err(Error::InvalidGitData("the server sent no pack".into())),
+            };
+            if section.trim_ascii_end() == b"packfile" {
+                break;
+            }
+            // Skip sections such as `shallow-info`:
+            while let Pkt::Line(_) = read_pkt(&mut response)? {}
+        }
+
+        let mut unpacker = pack::Unpacker::new(usize::try_from(limit).unwrap_or(usize::MAX));
+        let mut len = 0;
+        while let Pkt::Line(line) = read_pkt(&mut response)? {
+            match line.split_first() {
+                Some((1, data)) => {
+                    len += data.len() as u64;
+                    if len > limit {
+                        return Err(Error::TooLarge(limit));
+                    }
+                    unpacker.push(data)?;
+                    if enough(unpacker.objects()) {
+                        return Ok(unpacker.into_objects());
+                    }
+                }
+                Some((2, _)) => {} // progress
+                Some((3, message)) => {
+                    return Err(Error::FailedGitRequest(std::io::Error::other(
+                        String::from_utf8_lossy(message).trim_end().to_string(),
+                    )))
+                }
+                _ => return Err(Error::InvalidGitData("malformed sideband".into())),
+            }
+        }
+        unpacker.finish()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Transport::Stream {
+            reader,
+            writer,
+            child: Some(child),
+        } = &mut self.transport
+        {
+            // Ending the session lets `git upload-pack` exit, and closing
+            // its output stops any pack that was only read in part:
+            *reader = BufReader::new(Box::new(std::io::empty()));
+            let _ = writer.write_all(b"0000");
+            let _ = writer.flush();
+            *writer = Box::new(std::io::sink());
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Returns the arguments that pass the configured values on to `git`.
+pub(crate) fn config_args(options: &OpenOptions) -> Vec<String> {
+    options
+        .get_git_config()
+        .flat_map(|(key, value)| ["-c".into(), format!("{}={}", key, value)])
+        .collect()
+}
+
+/// Connects to a git daemon.
+fn connect(authority: &str, options: &OpenOptions) -> std::io::Result<TcpStream> {
+    let has_port = authority.rsplit_once(':').is_some_and(|(host, port)| {
+        port.parse::<u16>().is_ok() && (!host.contains(':') || host.ends_with(']'))
+    });
+    let addresses: Vec<_> = match has_port {
+        true => authority.to_socket_addrs()?.collect(),
+        false => {
+            let host = authority.trim_start_matches('[').trim_end_matches(']');
+            (host, DEFAULT_PORT).to_socket_addrs()?.collect()
+        }
+    };
+
+    let mut last_error = None;
+    for address in addresses {
+        let stream = match options.get_connect_timeout() {
+            Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+            None => TcpStream::connect(address),
+        };
+        match stream {
+            Ok(stream) => {
+                stream.set_read_timeout(options.get_read_timeout())?;
+                return Ok(stream);
+            }
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| ErrorKind::NotFound.into()))
+}
+
+/// Reads the capability advertisement that starts every session, given
+/// its first pkt-line.
+fn read_capabilities(first: Pkt, reader: &mut impl Read) -> Result<Vec<String>> {
+    match first {
+        Pkt::Line(line) if line.trim_ascii_end() == b"version 2" => {}
+        _ => {
+            return Err(Error::FailedGitRequest(std::io::Error::new(
+                ErrorKind::Unsupported,
+                "the server does not support the git protocol v2",
+            )))
+        }
+    }
+    let mut capabilities = Vec::new();
+    while let Pkt::Line(line) = read_pkt(reader)? {
+        capabilities.push(String::from_utf8_lossy(line.trim_ascii_end()).into_owned());
+    }
+    Ok(capabilities)
+}
+
+#[derive(Debug, PartialEq)]
+enum Pkt {
+    Flush,
+    Delimiter,
+    ResponseEnd,
+    Line(Vec<u8>),
+}
+
+fn write_pkt(output: &mut Vec<u8>, data: &[u8]) {
+    output.extend(format!("{:04x}", data.len() + 4).as_bytes());
+    output.extend(data);
+}
+
+/// Reads a pkt-line, failing on the `ERR` lines that servers may send in
+/// place of any other.
+fn read_pkt(reader: &mut impl Read) -> Result<Pkt> {
+    let mut len = [0; 4];
+    reader
+        .read_exact(&mut len)
+        .map_err(Error::FailedGitRequest)?;
+    let len = std::str::from_utf8(&len)
+        .ok()
+        .and_then(|len| usize::from_str_radix(len, 16).ok())
+        .ok_or_else(|| Error::InvalidGitData("malformed pkt-line".into()))?;
+    let line = match len {
+        0 => return Ok(Pkt::Flush),
+        1 => return Ok(Pkt::Delimiter),
+        2 => return Ok(Pkt::ResponseEnd),
+        3 => return Err(Error::InvalidGitData("malformed pkt-line".into())),
+        len if len > MAX_PKT_LEN => return Err(Error::InvalidGitData("malformed pkt-line".into())),
+        len => {
+            let mut line = vec![0; len - 4];
+            reader
+                .read_exact(&mut line)
+                .map_err(Error::FailedGitRequest)?;
+            line
+        }
+    };
+    match line.strip_prefix(b"ERR ") {
+        Some(message) => {
+            let message = String::from_utf8_lossy(message).trim_end().to_string();
+            // Such as `upload-pack: not our ref`, for a missing commit:
+            Err(match message.contains("not our ref") {
+                true => Error::GitObjectNotFound(message),
+                false => Error::FailedGitRequest(std::io::Error::other(message)),
+            })
+        }
+        None => Ok(Pkt::Line(line)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pkt_lines() {
+        let mut output = Vec::new();
+        write_pkt(&mut output, b"version 2\n");
+        assert_eq!(output, b"000eversion 2\n");
+
+        let mut input = &b"000eversion 2\n000100000002"[..];
+        assert_eq!(
+            read_pkt(&mut input).unwrap(),
+            Pkt::Line(b"version 2\n".to_vec())
+        );
+        assert_eq!(read_pkt(&mut input).unwrap(), Pkt::Delimiter);
+        assert_eq!(read_pkt(&mut input).unwrap(), Pkt::Flush);
+        assert_eq!(read_pkt(&mut input).unwrap(), Pkt::ResponseEnd);
+        assert!(read_pkt(&mut input).is_err());
+
+        let mut input = &b"0003"[..];
+        assert!(matches!(
+            read_pkt(&mut input),
+            Err(Error::InvalidGitData(_))
+        ));
+        let mut input = &b"0021ERR upload-pack: not our ref\n"[..];
+        assert!(matches!(
+            read_pkt(&mut input),
+            Err(Error::GitObjectNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn capabilities() {
+        let mut input =
+            &b"000eversion 2\n0013ls-refs=unborn\n0020fetch=shallow wait-for-done\n0000"[..];
+        let first = read_pkt(&mut input).unwrap();
+        let capabilities = read_capabilities(first, &mut input).unwrap();
+        let session = Session {
+            transport: Transport::Http {
+                url: String::new(),
+                options: Box::default(),
+            },
+            capabilities,
+        };
+        assert!(session.supports("fetch", "shallow"));
+        assert!(!session.supports("fetch", "filter"));
+        assert!(session.supports("ls-refs", "unborn"));
+
+        let mut input = &b"0000"[..];
+        let first = Pkt::Line(b"# service=git-upload-pack\n".to_vec());
+        assert!(read_capabilities(first, &mut input).is_err());
+    }
+}
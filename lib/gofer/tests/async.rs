@@ -0,0 +1,132 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Integration tests of opening URLs asynchronously against a local HTTP
+//! server.
+
+#![cfg(all(feature = "async", feature = "http"))]
+
+use gofer::{r#async, Client, OpenOptions};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::mpsc,
+    thread,
+};
+use tokio::io::AsyncReadExt;
+
+/// Serves the given response to every request, keeping connections alive,
+/// and reports each connection accepted.
+fn serve(headers: &'static str, body: Vec<u8>) -> (String, mpsc::Receiver<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hello.txt", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let body = body.clone();
+            let _ = sender.send(());
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        return;
+                    }
+                    while line != "\r\n" {
+                        line.clear();
+                        reader.read_line(&mut line).unwrap();
+                    }
+                    let head = format!(
+                        "HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\n\r\n",
+                        headers,
+                        body.len()
+                    );
+                    stream.write_all(head.as_bytes()).unwrap();
+                    stream.write_all(&body).unwrap();
+                }
+            });
+        }
+    });
+    (url, receiver)
+}
+
+/// Returns options that bypass the cache, if enabled.
+fn options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.retry(gofer::RetryPolicy::none());
+    #[cfg(feature = "cache")]
+    options.cache(None);
+    options
+}
+
+async fn read(url: &str, options: &OpenOptions) -> std::io::Result<String> {
+    let mut output = String::new();
+    r#async::open_with(url, options)
+        .await?
+        .read_to_string(&mut output)
+        .await?;
+    Ok(output)
+}
+
+#[tokio::test]
+async fn reuse_connections() {
+    let (url, connections) = serve("", b"hello".to_vec());
+
+    let client = Client::with_options(options());
+    assert_eq!(read(&url, client.options()).await.unwrap(), "hello");
+    assert_eq!(read(&url, client.options()).await.unwrap(), "hello");
+    assert_eq!(connections.try_iter().count(), 1);
+}
+
+#[tokio::test]
+async fn limit_size() {
+    let (url, _) = serve("", b"hello".to_vec());
+
+    let mut options = options();
+    options.max_size(4);
+    assert!(read(&url, &options).await.is_err());
+}
+
+#[cfg(feature = "integrity")]
+#[tokio::test]
+async fn check_digest() {
+    // A described body isn't sniffed for compression, which would read it
+    // all before the stream is returned:
+    let (url, _) = serve("Content-Type: text/plain\r\n", b"hello".to_vec());
+    let digest = "sha256=2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    let options = options();
+    let valid = format!("{}#{}", url, digest);
+    assert_eq!(read(&valid, &options).await.unwrap(), "hello");
+    let invalid = format!("{}#{}", url, digest.replace("2cf2", "0000"));
+    let mut reader = r#async::open_with(&invalid, &options).await.unwrap();
+    let mut output = Vec::new();
+    let error = reader.read_to_end(&mut output).await.unwrap_err();
+    assert!(error.to_string().contains("integrity"));
+
+    // Reading again after the error fails the same way:
+    let error = reader.read_to_end(&mut output).await.unwrap_err();
+    assert!(error.to_string().contains("integrity"));
+}
+
+#[cfg(feature = "decompress")]
+#[tokio::test]
+async fn decompress_content_encoding() {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(b"hello").unwrap();
+    let (url, _) = serve("Content-Encoding: gzip\r\n", encoder.finish().unwrap());
+
+    let mut options = options();
+    assert_eq!(read(&url, &options).await.unwrap(), "hello");
+
+    // Unless disabled, when the raw bytes are passed through:
+    options.decompress(false);
+    let mut output = Vec::new();
+    r#async::open_with(&url, &options)
+        .await
+        .unwrap()
+        .read_to_end(&mut output)
+        .await
+        .unwrap();
+    assert!(output.starts_with(&[0x1F, 0x8B]));
+}
@@ -0,0 +1,175 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Integration tests of the HTTP cache against a local HTTP server.
+
+#![cfg(feature = "cache")]
+
+use gofer::{Cache, OpenOptions, Read};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::mpsc,
+    thread,
+};
+
+/// Serves the given responses, one per connection, reporting the request
+/// headers of each request.
+fn serve(responses: Vec<&'static str>) -> (String, mpsc::Receiver<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hello.txt", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let headers: Vec<String> = BufReader::new(&stream)
+                .lines()
+                .map(Result::unwrap)
+                .take_while(|line| !line.is_empty())
+                .map(|line| line.to_ascii_lowercase())
+                .collect();
+            stream.write_all(response.as_bytes()).unwrap();
+            sender.send(headers).unwrap();
+        }
+    });
+    (url, receiver)
+}
+
+fn read(options: &OpenOptions, url: &str) -> String {
+    let mut output = String::new();
+    options
+        .open(url)
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+    output
+}
+
+fn temp_cache(name: &str) -> Cache {
+    let cache = Cache::new(std::env::temp_dir().join(format!(
+        "gofer-test-{}-{}",
+        name,
+        std::process::id()
+    )));
+    cache.clear().unwrap();
+    cache
+}
+
+#[test]
+fn revalidate_with_etag() {
+    let (url, requests) = serve(vec![
+        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nCache-Control: max-age=0\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nCache-Control: max-age=60\r\nConnection: close\r\n\r\n",
+    ]);
+    let cache = temp_cache("revalidate");
+    let mut options = OpenOptions::new();
+    options.cache(cache.clone());
+
+    assert_eq!(read(&options, &url), "hello");
+    assert!(!requests
+        .recv()
+        .unwrap()
+        .iter()
+        .any(|h| h.starts_with("if-none-match")));
+
+    // The entry is stale, so it is revalidated:
+    assert_eq!(read(&options, &url), "hello");
+    assert!(requests
+        .recv()
+        .unwrap()
+        .contains(&"if-none-match: \"v1\"".to_string()));
+
+    // The entry is now fresh, so the server is not contacted:
+    assert_eq!(read(&options, &url), "hello");
+
+    let entries = cache.entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].url, url);
+    assert_eq!(entries[0].size, 5);
+    assert!(entries[0].is_fresh());
+
+    assert_eq!(cache.remove(&url).unwrap(), 1);
+    assert!(cache.entries().unwrap().is_empty());
+    cache.clear().unwrap();
+}
+
+#[test]
+fn serve_stale_when_offline() {
+    let (url, requests) = serve(vec![
+        "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nLast-Modified: Wed, 21 Oct 2015 07:28:00 GMT\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+    ]);
+    let cache = temp_cache("offline");
+    let mut options = OpenOptions::new();
+    options.cache(cache.clone());
+
+    assert_eq!(read(&options, &url), "hello");
+    requests.recv().unwrap();
+
+    // Once the server has gone away, the stale entry is served:
+    drop(requests);
+    assert_eq!(read(&options, &url), "hello");
+    cache.clear().unwrap();
+}
+
+#[test]
+fn no_store() {
+    let (url, _requests) = serve(vec![
+        "HTTP/1.1 200 OK\r\nCache-Control: no-store\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+    ]);
+    let cache = temp_cache("no-store");
+    let mut options = OpenOptions::new();
+    options.cache(cache.clone());
+
+    assert_eq!(read(&options, &url), "hello");
+    assert!(cache.entries().unwrap().is_empty());
+}
+
+#[test]
+fn skip_private_responses() {
+    let (url, _requests) = serve(vec![
+        "HTTP/1.1 200 OK\r\nCache-Control: private, max-age=60\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecret",
+    ]);
+    let cache = temp_cache("private");
+    let mut options = OpenOptions::new();
+    options.cache(cache.clone());
+
+    assert_eq!(read(&options, &url), "hello");
+    assert!(cache.entries().unwrap().is_empty());
+
+    // Requests with credentials bypass the cache altogether:
+    options.header("Authorization", "Bearer token");
+    assert_eq!(read(&options, &url), "secret");
+    assert!(cache.entries().unwrap().is_empty());
+}
+
+#[test]
+fn replace_entry() {
+    let (url, requests) = serve(vec![
+        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nCache-Control: max-age=0\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        "HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nCache-Control: max-age=60\r\nContent-Length: 7\r\nConnection: close\r\n\r\nhello!!",
+    ]);
+    let cache = temp_cache("replace");
+    let mut options = OpenOptions::new();
+    options.cache(cache.clone());
+
+    assert_eq!(read(&options, &url), "hello");
+    requests.recv().unwrap();
+
+    // The new body replaces the old one, which is removed:
+    assert_eq!(read(&options, &url), "hello!!");
+    requests.recv().unwrap();
+    let bodies = std::fs::read_dir(cache.dir())
+        .unwrap()
+        .filter(|file| {
+            let path = file.as_ref().unwrap().path();
+            path.extension().is_some_and(|ext| ext == "body")
+        })
+        .count();
+    assert_eq!(bodies, 1);
+
+    let entries = cache.entries().unwrap();
+    assert_eq!(entries[0].etag.as_deref(), Some("\"v2\""));
+    assert_eq!(entries[0].size, 7);
+    assert_eq!(read(&options, &url), "hello!!");
+    cache.clear().unwrap();
+}
@@ -0,0 +1,82 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Integration tests against a local Gopher server.
+
+#![cfg(feature = "gopher")]
+
+use gofer::{gopher, OpenOptions, Read, Url};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    sync::mpsc,
+    thread,
+};
+
+/// Serves the given response to a single request on a non-default port,
+/// reporting the request line.
+fn serve(response: &'static str) -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let authority = listener.local_addr().unwrap().to_string();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line).unwrap();
+        stream.write_all(response.as_bytes()).unwrap();
+        sender.send(line).unwrap();
+    });
+    (authority, receiver)
+}
+
+#[test]
+fn read_text_file() {
+    let (authority, requests) = serve("Hello, world!\r\n.\r\n");
+
+    let url = format!("gopher://{}/0/hello%20world.txt", authority);
+    let mut resource = OpenOptions::new().open_with_metadata(&url).unwrap();
+    let mut output = String::new();
+    resource.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "Hello, world!\r\n.\r\n");
+    assert_eq!(resource.content_type.as_deref(), Some("text/plain"));
+
+    assert_eq!(requests.recv().unwrap(), "/hello world.txt\r\n");
+}
+
+#[test]
+fn search() {
+    let (authority, requests) = serve("0Result\t/result.txt\texample.org\t70\r\n.\r\n");
+
+    let url = format!("gopher://{}/7/search%09gofer", authority);
+    let menu = gopher::read_menu(&url.parse::<Url>().unwrap(), &OpenOptions::new()).unwrap();
+    assert_eq!(menu.items.len(), 1);
+    assert_eq!(menu.items[0].display, "Result");
+    assert_eq!(
+        menu.items[0].url().as_deref(),
+        Some("gopher://example.org/0/result.txt")
+    );
+
+    assert_eq!(requests.recv().unwrap(), "/search\tgofer\r\n");
+}
+
+#[test]
+fn read_root_menu() {
+    let (authority, requests) = serve("iWelcome\tfake\t(NULL)\t0\r\n.\r\n");
+
+    let url = format!("gopher://{}", authority);
+    let menu = gopher::read_menu(&url.parse::<Url>().unwrap(), &OpenOptions::new()).unwrap();
+    assert_eq!(menu.items[0].item_type, 'i');
+    assert_eq!(menu.items[0].display, "Welcome");
+
+    assert_eq!(requests.recv().unwrap(), "\r\n");
+}
+
+#[test]
+fn limit_menu_size() {
+    let (authority, _) = serve("iWelcome\tfake\t(NULL)\t0\r\n.\r\n");
+
+    let url = format!("gopher://{}", authority);
+    let mut options = OpenOptions::new();
+    options.max_size(16);
+    let error = gopher::read_menu(&url.parse::<Url>().unwrap(), &options).unwrap_err();
+    assert!(matches!(error, gofer::Error::TooLarge(16)));
+}
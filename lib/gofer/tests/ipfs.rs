@@ -0,0 +1,386 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Integration tests of IPFS gateway failover and CAR verification against
+//! local HTTP servers.
+
+#![cfg(feature = "ipfs")]
+
+use gofer::{OpenOptions, Read};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use sha2::{Digest, Sha256};
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::{SocketAddr, TcpListener, UdpSocket},
+    sync::mpsc,
+    thread,
+};
+
+static CONTENT: &[u8] = b"Hello, IPFS!\n";
+
+/// Returns the base32 CIDv1 of the given raw block.
+fn cid(block: &[u8]) -> String {
+    let mut bytes = vec![0x01, 0x55, 0x12, 0x20];
+    bytes.extend(Sha256::digest(block));
+    format!("b{}", base32(&bytes))
+}
+
+fn base32(input: &[u8]) -> String {
+    let bits: String = input.iter().map(|byte| format!("{:08b}", byte)).collect();
+    bits.as_bytes()
+        .chunks(5)
+        .map(|chunk| {
+            let chunk = format!("{:0<5}", std::str::from_utf8(chunk).unwrap());
+            b"abcdefghijklmnopqrstuvwxyz234567"[usize::from_str_radix(&chunk, 2).unwrap()] as char
+        })
+        .collect()
+}
+
+/// Returns a CAR holding the given raw block.
+fn car(block: &[u8]) -> Vec<u8> {
+    let header = b"\xa2eroots\x80gversion\x01";
+    let mut car = vec![header.len() as u8];
+    car.extend(header);
+    car.push(36 + block.len() as u8);
+    car.extend([0x01, 0x55, 0x12, 0x20]);
+    car.extend(Sha256::digest(block));
+    car.extend(block);
+    car
+}
+
+/// Serves the given responses, one per connection, reporting the request
+/// line and headers of each request.
+fn serve(responses: Vec<Vec<u8>>) -> (String, mpsc::Receiver<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let headers: Vec<String> = BufReader::new(&stream)
+                .lines()
+                .map(Result::unwrap)
+                .take_while(|line| !line.is_empty())
+                .map(|line| line.to_ascii_lowercase())
+                .collect();
+            stream.write_all(&response).unwrap();
+            sender.send(headers).unwrap();
+        }
+    });
+    (url, receiver)
+}
+
+fn response(status: &str, content_type: &str, body: &[u8]) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )
+    .into_bytes();
+    response.extend(body);
+    response
+}
+
+/// Returns options that bypass decompression and the cache, if enabled.
+fn options() -> OpenOptions {
+    #[allow(unused_mut)]
+    let mut options = OpenOptions::new();
+    #[cfg(feature = "decompress")]
+    options.decompress(false);
+    #[cfg(feature = "cache")]
+    options.cache(None);
+    options
+}
+
+#[test]
+fn verify_car_with_failover() {
+    let (down, _) = serve(vec![response("404 Not Found", "text/plain", b"")]);
+    let (up, requests) = serve(vec![response(
+        "200 OK",
+        "application/vnd.ipld.car; version=1",
+        &car(CONTENT),
+    )]);
+    let url = format!("ipfs://{}", cid(CONTENT));
+
+    let mut output = Vec::new();
+    options()
+        .ipfs_gateways([down, up])
+        .open(&url)
+        .unwrap()
+        .read_to_end(&mut output)
+        .unwrap();
+    assert_eq!(output, CONTENT);
+
+    let headers = requests.recv().unwrap();
+    let request = format!("get /ipfs/{}?dag-scope=entity http/1.1", cid(CONTENT));
+    assert_eq!(headers[0], request);
+    assert!(headers
+        .iter()
+        .any(|h| h.starts_with("accept: application/vnd.ipld.car")));
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn cache_verified_car() {
+    let body = car(CONTENT);
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/vnd.ipld.car\r\n\
+         Cache-Control: public, max-age=29030400, immutable\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    response.extend(body);
+    let (gateway, requests) = serve(vec![response]);
+    let url = format!("ipfs://{}", cid(CONTENT));
+
+    let cache = gofer::Cache::new(
+        std::env::temp_dir().join(format!("gofer-test-ipfs-{}", std::process::id())),
+    );
+    cache.clear().unwrap();
+    let mut options = options();
+    options.ipfs_gateway(gateway).cache(cache.clone());
+
+    // The second read is served from the cache, and verified again:
+    for _ in 0..2 {
+        let mut output = Vec::new();
+        options
+            .open(&url)
+            .unwrap()
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, CONTENT);
+    }
+    requests.recv().unwrap();
+    assert!(requests.try_recv().is_err());
+    assert_eq!(cache.entries().unwrap().len(), 1);
+    cache.clear().unwrap();
+}
+
+#[test]
+fn reject_tampered_car() {
+    let (gateway, _) = serve(vec![response(
+        "200 OK",
+        "application/vnd.ipld.car; version=1",
+        &car(b"Hello, IPFS?\n"),
+    )]);
+    let url = format!("ipfs://{}", cid(CONTENT));
+
+    let mut output = Vec::new();
+    let error = options()
+        .ipfs_gateway(gateway)
+        .open(&url)
+        .unwrap()
+        .read_to_end(&mut output)
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn reject_unverifiable_response() {
+    let (gateway, _) = serve(vec![response("200 OK", "text/plain", CONTENT)]);
+    let url = format!("ipfs://{}", cid(CONTENT));
+
+    let error = options()
+        .ipfs_gateway(gateway)
+        .open_with_metadata(&url)
+        .unwrap_err();
+    assert!(matches!(error, gofer::Error::IpfsVerificationFailed(_)));
+}
+
+#[test]
+fn refuse_unverifiable_random_access() {
+    let (gateway, requests) = serve(vec![response("200 OK", "text/plain", CONTENT)]);
+    let url = format!("ipfs://{}", cid(CONTENT));
+
+    let error = options()
+        .ipfs_gateway(gateway)
+        .open_seekable(&url)
+        .err()
+        .unwrap();
+    assert!(matches!(error, gofer::Error::NotSeekable(_)));
+    assert!(requests.try_recv().is_err());
+}
+
+#[test]
+fn kubo_rpc_api() {
+    let (api, requests) = serve(vec![response("200 OK", "text/plain", CONTENT)]);
+    let url = format!("ipfs://{}/", cid(CONTENT));
+
+    let mut output = Vec::new();
+    options()
+        .ipfs_gateway(format!("{}/api/v0", api))
+        .open(&url)
+        .unwrap()
+        .read_to_end(&mut output)
+        .unwrap();
+    assert_eq!(output, CONTENT);
+
+    let headers = requests.recv().unwrap();
+    let request = format!("post /api/v0/cat?arg=/ipfs/{} http/1.1", cid(CONTENT));
+    assert_eq!(headers[0], request);
+}
+
+/// Answers a single DNS query with the given TXT record.
+fn serve_dns(record: &'static str) -> (SocketAddr, mpsc::Receiver<Vec<u8>>) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = socket.local_addr().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut query = [0; 512];
+        let (len, peer) = socket.recv_from(&mut query).unwrap();
+        let mut response = query[..len].to_vec();
+        response[2..4].copy_from_slice(&[0x81, 0x80]);
+        response[7] = 1;
+        response.extend([
+            0xc0,
+            12,
+            0,
+            16,
+            0,
+            1,
+            0,
+            0,
+            0,
+            60,
+            0,
+            record.len() as u8 + 1,
+        ]);
+        response.push(record.len() as u8);
+        response.extend(record.as_bytes());
+        socket.send_to(&response, peer).unwrap();
+        sender.send(query[12..len - 4].to_vec()).unwrap();
+    });
+    (address, receiver)
+}
+
+/// Returns the IPNS name of the given key pair, and an IPNS record that
+/// points the name to the given value.
+fn ipns_record(key: &Ed25519KeyPair, value: &str) -> (String, Vec<u8>) {
+    let mut public_key = vec![0x08, 0x01, 0x12, 0x20];
+    public_key.extend(key.public_key().as_ref());
+    let mut name = vec![0x01, 0x72, 0x00, public_key.len() as u8];
+    name.extend(&public_key);
+
+    let validity = b"2999-12-31T23:59:59.000000000Z";
+    let mut data = vec![0xa3, 0x65];
+    data.extend(b"Value");
+    data.extend([0x58, value.len() as u8]);
+    data.extend(value.as_bytes());
+    data.push(0x68);
+    data.extend(b"Validity");
+    data.extend([0x58, validity.len() as u8]);
+    data.extend(validity);
+    data.push(0x6c);
+    data.extend(b"ValidityType");
+    data.push(0x00);
+
+    let mut message = b"ipns-signature:".to_vec();
+    message.extend(&data);
+    let signature = key.sign(&message);
+
+    let mut record = vec![0x42, signature.as_ref().len() as u8];
+    record.extend(signature.as_ref());
+    // The data needs a two-byte varint for its length:
+    record.extend([0x4a, data.len() as u8 | 0x80, (data.len() >> 7) as u8]);
+    record.extend(&data);
+    (format!("b{}", base32(&name)), record)
+}
+
+#[test]
+fn resolve_dnslink() {
+    let record = format!("dnslink=/ipfs/{}", cid(CONTENT)).leak();
+    let (dns_server, queries) = serve_dns(record);
+    let (gateway, requests) = serve(vec![response(
+        "200 OK",
+        "application/vnd.ipld.car; version=1",
+        &car(CONTENT),
+    )]);
+
+    let mut resource = options()
+        .ipfs_gateway(gateway)
+        .dns_server(dns_server)
+        .open_with_metadata("ipns://example.org")
+        .unwrap();
+    let mut output = Vec::new();
+    resource.read_to_end(&mut output).unwrap();
+    assert_eq!(output, CONTENT);
+    assert_eq!(resource.cid, Some(cid(CONTENT)));
+
+    assert_eq!(
+        queries.recv().unwrap(),
+        b"\x08_dnslink\x07example\x03org\x00"
+    );
+    let headers = requests.recv().unwrap();
+    let request = format!("get /ipfs/{}?dag-scope=entity http/1.1", cid(CONTENT));
+    assert_eq!(headers[0], request);
+}
+
+#[test]
+fn resolve_ipns_record() {
+    let key = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+    let (name, record) = ipns_record(&key, &format!("/ipfs/{}", cid(CONTENT)));
+    let (gateway, requests) = serve(vec![
+        response("200 OK", "application/vnd.ipfs.ipns-record", &record),
+        response(
+            "200 OK",
+            "application/vnd.ipld.car; version=1",
+            &car(CONTENT),
+        ),
+    ]);
+
+    let mut resource = options()
+        .ipfs_gateway(gateway)
+        .open_with_metadata(format!("ipns://{}", name))
+        .unwrap();
+    let mut output = Vec::new();
+    resource.read_to_end(&mut output).unwrap();
+    assert_eq!(output, CONTENT);
+    assert_eq!(resource.cid, Some(cid(CONTENT)));
+
+    let headers = requests.recv().unwrap();
+    assert_eq!(headers[0], format!("get /ipns/{} http/1.1", name));
+    assert!(headers
+        .iter()
+        .any(|h| h == "accept: application/vnd.ipfs.ipns-record"));
+}
+
+#[test]
+fn reject_forged_ipns_record() {
+    let key = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+    let forger = Ed25519KeyPair::from_seed_unchecked(&[8; 32]).unwrap();
+    let (name, _) = ipns_record(&key, "/ipfs/bafkqaaa");
+    let (_, forged) = ipns_record(&forger, &format!("/ipfs/{}", cid(CONTENT)));
+    let (gateway, _) = serve(vec![response(
+        "200 OK",
+        "application/vnd.ipfs.ipns-record",
+        &forged,
+    )]);
+
+    let error = options()
+        .ipfs_gateway(gateway)
+        .open_with_metadata(format!("ipns://{}", name))
+        .unwrap_err();
+    assert!(matches!(error, gofer::Error::IpfsVerificationFailed(_)));
+    assert!(error.to_string().contains("bad signature"));
+}
+
+#[test]
+fn reject_oversized_ipns_record() {
+    let key = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+    let (name, _) = ipns_record(&key, "/ipfs/bafkqaaa");
+    // Without a `Content-Length`, the record is only known to be too large
+    // once it has been read that far:
+    let mut response = b"HTTP/1.1 200 OK\r\nContent-Type: application/vnd.ipfs.ipns-record\r\n\
+          Connection: close\r\n\r\n"
+        .to_vec();
+    response.extend([0; 64 << 10]);
+    let (gateway, _) = serve(vec![response]);
+
+    let error = options()
+        .ipfs_gateway(gateway)
+        .open_with_metadata(format!("ipns://{}", name))
+        .unwrap_err();
+    assert!(error.to_string().contains("too large"));
+}
@@ -0,0 +1,95 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Integration tests of resuming interrupted HTTP downloads against a local
+//! HTTP server.
+
+#![cfg(feature = "http")]
+
+use gofer::{OpenOptions, Read};
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::TcpListener,
+    sync::mpsc,
+    thread,
+};
+
+/// Serves the given responses, one per connection, reporting the request
+/// headers of each request. Each connection is closed after the response,
+/// even if the response announced a longer body.
+fn serve(responses: Vec<&'static str>) -> (String, mpsc::Receiver<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hello.txt", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let headers: Vec<String> = BufReader::new(&stream)
+                .lines()
+                .map(Result::unwrap)
+                .take_while(|line| !line.is_empty())
+                .map(|line| line.to_ascii_lowercase())
+                .collect();
+            stream.write_all(response.as_bytes()).unwrap();
+            sender.send(headers).unwrap();
+        }
+    });
+    (url, receiver)
+}
+
+/// Returns options that bypass the cache and decompression, if enabled, so
+/// that errors surface when reading rather than when opening.
+fn options() -> OpenOptions {
+    #[allow(unused_mut)]
+    let mut options = OpenOptions::new();
+    #[cfg(feature = "cache")]
+    options.cache(None);
+    #[cfg(feature = "decompress")]
+    options.decompress(false);
+    options
+}
+
+#[test]
+fn resume_after_connection_drop() {
+    let (url, requests) = serve(vec![
+        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 11\r\n\r\nhello",
+        "HTTP/1.1 206 Partial Content\r\nETag: \"v1\"\r\nContent-Range: bytes 5-10/11\r\nContent-Length: 6\r\nConnection: close\r\n\r\n world",
+    ]);
+
+    let mut output = String::new();
+    options()
+        .open(&url)
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+    assert_eq!(output, "hello world");
+
+    requests.recv().unwrap();
+    let headers = requests.recv().unwrap();
+    assert!(headers.contains(&"range: bytes=5-".to_string()));
+    assert!(headers.contains(&"if-range: \"v1\"".to_string()));
+}
+
+#[test]
+fn resource_changed() {
+    let (url, _requests) = serve(vec![
+        "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 11\r\n\r\nhello",
+        "HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nContent-Length: 11\r\nConnection: close\r\n\r\nHELLO WORLD",
+    ]);
+
+    let mut output = String::new();
+    let error = options()
+        .open(&url)
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn no_resume_without_validator() {
+    let (url, _requests) = serve(vec!["HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nhello"]);
+
+    let mut output = String::new();
+    let result = options().open(&url).unwrap().read_to_string(&mut output);
+    assert!(result.is_err());
+}
@@ -0,0 +1,84 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Integration tests of retrying transient HTTP failures against a local
+//! HTTP server.
+
+#![cfg(feature = "http")]
+
+use gofer::{Error, OpenOptions, Read, RetryPolicy};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    thread,
+    time::Duration,
+};
+
+/// Serves the given responses, one per connection.
+fn serve(responses: Vec<&'static str>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hello.txt", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            BufReader::new(&stream)
+                .lines()
+                .map(Result::unwrap)
+                .take_while(|line| !line.is_empty())
+                .for_each(drop);
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    url
+}
+
+/// Returns options that bypass the cache, if enabled.
+fn options(policy: RetryPolicy) -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.retry(policy.with_initial_backoff(Duration::from_millis(10)));
+    #[cfg(feature = "cache")]
+    options.cache(None);
+    options
+}
+
+#[test]
+fn retry_transient_statuses() {
+    let url = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        "HTTP/1.1 429 Too Many Requests\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+    ]);
+
+    let mut output = String::new();
+    options(RetryPolicy::new())
+        .open(&url)
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+    assert_eq!(output, "hello");
+}
+
+#[test]
+fn give_up_after_max_attempts() {
+    let url = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    ]);
+
+    let error = options(RetryPolicy::new().with_max_attempts(2))
+        .open_with_metadata(&url)
+        .unwrap_err();
+    assert!(error.is_transient());
+    assert!(matches!(error, Error::HttpStatus { status: 503, .. }));
+}
+
+#[test]
+fn no_retry_for_permanent_errors() {
+    let url = serve(vec![
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    ]);
+
+    let error = options(RetryPolicy::new())
+        .open_with_metadata(&url)
+        .unwrap_err();
+    assert!(!error.is_transient());
+}
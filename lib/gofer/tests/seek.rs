@@ -0,0 +1,117 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Integration tests of seekable HTTP reads against a local HTTP server.
+
+#![cfg(feature = "http")]
+
+use gofer::{Error, OpenOptions, Read};
+use std::{
+    io::{BufRead, BufReader, Seek, SeekFrom, Write},
+    net::TcpListener,
+    thread,
+};
+
+const BODY: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Serves `BODY`, honoring `Range: bytes=N-` requests if `ranges` is set.
+fn serve(ranges: bool) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/data.bin", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let offset = BufReader::new(&stream)
+                .lines()
+                .map(Result::unwrap)
+                .take_while(|line| !line.is_empty())
+                .find_map(|line| {
+                    let line = line.to_ascii_lowercase();
+                    let range = line.strip_prefix("range: bytes=")?;
+                    range.trim_end_matches('-').parse::<usize>().ok()
+                });
+            let response = match offset {
+                Some(offset) if ranges => {
+                    let mut response = format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        offset,
+                        BODY.len() - 1,
+                        BODY.len(),
+                        BODY.len() - offset
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(&BODY[offset..]);
+                    response
+                }
+                _ => {
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        BODY.len()
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(BODY);
+                    response
+                }
+            };
+            let _ = stream.write_all(&response);
+        }
+    });
+    url
+}
+
+#[test]
+fn range_requests() {
+    let url = serve(true);
+    let mut reader = OpenOptions::new().open_seekable(&url).unwrap();
+
+    let mut buffer = [0u8; 4];
+    reader.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"0123");
+
+    assert_eq!(reader.seek(SeekFrom::End(-4)).unwrap(), 32);
+    let mut output = String::new();
+    reader.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "wxyz");
+
+    assert_eq!(reader.seek(SeekFrom::Start(10)).unwrap(), 10);
+    reader.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"abcd");
+
+    assert_eq!(reader.seek(SeekFrom::Current(2)).unwrap(), 16);
+    reader.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"ghij");
+}
+
+#[test]
+fn range_not_supported() {
+    let url = serve(false);
+    let result = OpenOptions::new().open_seekable(&url);
+    assert!(matches!(result, Err(Error::RangeNotSupported(_))));
+}
+
+#[test]
+fn limit_size() {
+    let url = serve(true);
+    let mut reader = OpenOptions::new().max_size(16).open_seekable(&url).unwrap();
+
+    let mut buffer = [0u8; 4];
+    reader.seek(SeekFrom::Start(10)).unwrap();
+    reader.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"abcd");
+
+    reader.seek(SeekFrom::End(-4)).unwrap();
+    assert!(reader.read_exact(&mut buffer).is_err());
+}
+
+#[cfg(feature = "integrity")]
+#[test]
+fn refuse_unverifiable_digest() {
+    let url = serve(true);
+    let digest = "sha256=2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    let result = OpenOptions::new().open_seekable(format!("{}#{}", url, digest));
+    assert!(matches!(result, Err(Error::NotSeekable(_))));
+
+    let result = OpenOptions::new()
+        .expect_digest(digest.parse::<gofer::Digest>().unwrap())
+        .open_seekable(&url);
+    assert!(matches!(result, Err(Error::NotSeekable(_))));
+}
@@ -0,0 +1,155 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Integration tests against a local SSH server.
+//!
+//! Each test starts its own `sshd` on a random port, with a host key and an
+//! authorized key generated into a temporary directory, so these tests need
+//! OpenSSH's `sshd` and `ssh-keygen`, and are skipped where `sshd` isn't
+//! installed. When run as root, `sshd` also needs its privilege separation
+//! directory (e.g., `/run/sshd`) to exist.
+
+#![cfg(any(feature = "scp", feature = "sftp"))]
+
+use gofer::{OpenOptions, Read};
+use std::{
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Generates an ECDSA key pair, in the PEM format that libssh2 reads.
+fn keygen(path: &Path) {
+    let status = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ecdsa", "-m", "PEM", "-N", "", "-f"])
+        .arg(path)
+        .status()
+        .unwrap();
+    assert!(status.success(), "ssh-keygen failed");
+}
+
+/// Returns the absolute path of `sshd`, which refuses to run otherwise, if
+/// it is installed.
+fn sshd() -> Option<PathBuf> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&path)
+        .chain(["/usr/sbin".into(), "/usr/local/sbin".into()])
+        .map(|dir| dir.join("sshd"))
+        .find(|path| path.is_file())
+}
+
+/// A local SSH server, which is killed when dropped.
+struct Daemon(Child, u16, PathBuf);
+
+impl Daemon {
+    /// Starts a server, unless `sshd` isn't installed.
+    fn start(name: &str) -> Option<Self> {
+        let sshd = sshd()?;
+        let dir = std::env::temp_dir().join(format!("gofer-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        keygen(&dir.join("host_key"));
+        keygen(&dir.join("id_ecdsa"));
+
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = dir.join("sshd_config");
+        std::fs::write(
+            &config,
+            format!(
+                "ListenAddress 127.0.0.1\n\
+                 Port {port}\n\
+                 HostKey {dir}/host_key\n\
+                 AuthorizedKeysFile {dir}/id_ecdsa.pub\n\
+                 PidFile {dir}/sshd.pid\n\
+                 StrictModes no\n\
+                 PasswordAuthentication no\n\
+                 KbdInteractiveAuthentication no\n\
+                 Subsystem sftp internal-sftp\n",
+                port = port,
+                dir = dir.display(),
+            ),
+        )
+        .unwrap();
+
+        let host_key = std::fs::read_to_string(dir.join("host_key.pub")).unwrap();
+        std::fs::write(
+            dir.join("known_hosts"),
+            format!("[127.0.0.1]:{} {}", port, host_key),
+        )
+        .unwrap();
+
+        let child = Command::new(sshd)
+            .args(["-D", "-e", "-f"])
+            .arg(&config)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let started = Instant::now();
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(started.elapsed() < Duration::from_secs(10), "no sshd");
+            thread::sleep(Duration::from_millis(20));
+        }
+        Some(Self(child, port, dir))
+    }
+
+    /// Returns options that trust this server and authenticate to it.
+    fn options(&self) -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options.retry(gofer::RetryPolicy::none());
+        #[cfg(feature = "cache")]
+        options.cache(None);
+        options.ssh_identity(self.2.join("id_ecdsa"));
+        options.ssh_known_hosts(self.2.join("known_hosts"));
+        options
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn read_remote_file(scheme: &str) {
+    let Some(daemon) = Daemon::start(scheme) else {
+        eprintln!("skipping the {} test, as sshd isn't installed", scheme);
+        return;
+    };
+    let path = daemon.2.join("hello.txt");
+    std::fs::write(&path, "Hello, SSH!\n").unwrap();
+
+    let output = Command::new("id").arg("-un").output().unwrap();
+    let user = String::from_utf8(output.stdout).unwrap();
+    let url = format!(
+        "{}://{}@127.0.0.1:{}{}",
+        scheme,
+        user.trim(),
+        daemon.1,
+        path.display()
+    );
+
+    let mut resource = daemon.options().open_with_metadata(url).unwrap();
+    let mut output = String::new();
+    resource.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "Hello, SSH!\n");
+    assert_eq!(resource.content_length, Some(output.len() as u64));
+}
+
+#[cfg(feature = "scp")]
+#[test]
+fn read_remote_file_via_scp() {
+    read_remote_file("scp");
+}
+
+#[cfg(feature = "sftp")]
+#[test]
+fn read_remote_file_via_sftp() {
+    read_remote_file("sftp");
+}